@@ -0,0 +1,126 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Files and directories the run is not allowed to read.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use clap::ValueEnum;
+use log::{info, warn};
+
+/// Directories listed in the summary before it is cut short.
+const SUMMARY_DIRS: usize = 10;
+
+/// What to do with paths that cannot be read for lack of permission.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PermissionPolicy {
+    /// Leave them out of the failure count
+    Skip,
+    /// Count and log them like any other failure
+    #[default]
+    Fail,
+}
+
+#[derive(Debug, Default)]
+pub struct Inaccessible {
+    paths: Mutex<BTreeSet<PathBuf>>,
+}
+
+impl Inaccessible {
+    /// Record a denied path; false when it was already, as the directories
+    /// are by each pass over the tree with `--priority`.
+    pub fn record(&self, path: &Path) -> bool {
+        self.paths.lock().unwrap().insert(path.to_path_buf())
+    }
+
+    /// Log how many paths were denied, grouped by parent directory.
+    pub fn log_summary(&self) {
+        let paths = self.paths.lock().unwrap();
+        if paths.is_empty() {
+            return;
+        }
+
+        let mut dirs: BTreeMap<&Path, usize> = BTreeMap::new();
+        for path in paths.iter() {
+            *dirs.entry(path.parent().unwrap_or(path)).or_default() += 1;
+        }
+
+        let mut dirs: Vec<_> = dirs.into_iter().collect();
+        dirs.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        warn!(
+            "inaccessible: {} paths in {} directories (permission denied)",
+            paths.len(),
+            dirs.len()
+        );
+        for (dir, count) in dirs.iter().take(SUMMARY_DIRS) {
+            info!("inaccessible: {count} under '{}'", dir.display());
+        }
+        if dirs.len() > SUMMARY_DIRS {
+            info!(
+                "inaccessible: ... and {} more directories",
+                dirs.len() - SUMMARY_DIRS
+            );
+        }
+    }
+}
+
+/// Whether reading or writing failed for lack of permission.
+pub fn is_permission_denied(err: &anyhow::Error) -> bool {
+    err.chain().any(|err| {
+        err.downcast_ref::<io::Error>()
+            .is_some_and(|err| err.kind() == io::ErrorKind::PermissionDenied)
+    })
+}
+
+/// Path of a directory the walker was not allowed to read.
+pub fn walk_denied_path(err: &ignore::Error) -> Option<&Path> {
+    let denied = err
+        .io_error()
+        .is_some_and(|err| err.kind() == io::ErrorKind::PermissionDenied);
+    if !denied {
+        return None;
+    }
+
+    let mut err = err;
+    loop {
+        match err {
+            ignore::Error::WithPath { path, .. } => return Some(path),
+            ignore::Error::WithDepth { err: inner, .. }
+            | ignore::Error::WithLineNumber { err: inner, .. } => err = inner,
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_recorded_once() {
+        let inaccessible = Inaccessible::default();
+        assert!(inaccessible.record(Path::new("a/locked")));
+        assert!(inaccessible.record(Path::new("b/locked")));
+        assert!(!inaccessible.record(Path::new("a/locked")));
+        assert_eq!(inaccessible.paths.lock().unwrap().len(), 2);
+    }
+}
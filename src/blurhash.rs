@@ -0,0 +1,154 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! BlurHash placeholders, for `--blurhash`.
+//!
+//! A BlurHash is a few DCT components of the image packed in base 83, short
+//! enough to inline in a page and decoded by front ends into a blurred
+//! placeholder while the image loads; see <https://blurha.sh>.
+
+use std::f32::consts::PI;
+
+use jpeg_encoder::ColorType;
+
+use crate::thumb::Image;
+
+/// Components across and down; 4x3 suits the usual landscape photo.
+const COMPONENTS: (usize, usize) = (4, 3);
+
+/// Side the image is decoded to: the components are smooth, so a few
+/// dozen pixels give the same hash as the full image.
+pub const SIDE: u16 = 32;
+
+const BASE83: &[u8; 83] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ\
+    abcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+/// The BlurHash of `image`, none for CMYK images.
+pub fn encode(image: &Image) -> Option<String> {
+    let channels = match image.color {
+        ColorType::Luma => 1,
+        ColorType::Rgb => 3,
+        _ => return None,
+    };
+    let (width, height) = (image.width as usize, image.height as usize);
+    let linear: Vec<f32> =
+        image.pixels.iter().map(|&v| to_linear(v)).collect();
+
+    let (nx, ny) = COMPONENTS;
+    let mut factors = Vec::with_capacity(nx * ny);
+    for j in 0..ny {
+        for i in 0..nx {
+            let mut sum = [0f32; 3];
+            for y in 0..height {
+                let cy = (PI * j as f32 * y as f32 / height as f32).cos();
+                for x in 0..width {
+                    let basis =
+                        cy * (PI * i as f32 * x as f32 / width as f32).cos();
+                    let px = (y * width + x) * channels;
+                    for (c, sum) in sum.iter_mut().enumerate() {
+                        *sum += basis * linear[px + c % channels];
+                    }
+                }
+            }
+            let norm = if (i, j) == (0, 0) { 1.0 } else { 2.0 };
+            let scale = norm / (width * height) as f32;
+            factors.push(sum.map(|sum| sum * scale));
+        }
+    }
+
+    let (dc, ac) = factors.split_first()?;
+    let mut hash = String::with_capacity(4 + 2 * nx * ny);
+    push83(&mut hash, ((nx - 1) + (ny - 1) * 9) as u32, 1);
+
+    let max = ac.iter().flatten().fold(0f32, |max, v| max.max(v.abs()));
+    let quantised = if ac.is_empty() {
+        0
+    } else {
+        ((max * 166.0 - 0.5).floor() as i32).clamp(0, 82) as u32
+    };
+    let max = (quantised + 1) as f32 / 166.0;
+    push83(&mut hash, quantised, 1);
+
+    let [r, g, b] = dc.map(to_srgb);
+    push83(&mut hash, (r << 16) | (g << 8) | b, 4);
+
+    for factor in ac {
+        let [r, g, b] = factor.map(|v| {
+            let v = (v / max).abs().sqrt().copysign(v);
+            ((v * 9.0 + 9.5).floor() as i32).clamp(0, 18) as u32
+        });
+        push83(&mut hash, r * 19 * 19 + g * 19 + b, 2);
+    }
+
+    Some(hash)
+}
+
+fn to_linear(v: u8) -> f32 {
+    let v = v as f32 / 255.0;
+    if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+}
+
+fn to_srgb(v: f32) -> u32 {
+    let v = v.clamp(0.0, 1.0);
+    let v = if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    (v * 255.0 + 0.5) as u32
+}
+
+/// Append `value` as `digits` base 83 digits, most significant first.
+fn push83(hash: &mut String, value: u32, digits: u32) {
+    for i in (0..digits).rev() {
+        let digit = value / 83u32.pow(i) % 83;
+        hash.push(BASE83[digit as usize] as char);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An 8x6 image with the colour or grey level `pixel` gives.
+    fn image(color: ColorType, pixel: fn(usize, usize) -> Vec<u8>) -> Image {
+        let (width, height) = (8, 6);
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).flat_map(move |x| pixel(x, y)))
+            .collect();
+        Image { pixels, width: width as u16, height: height as u16, color }
+    }
+
+    #[test]
+    fn matches_the_reference_encoder() {
+        // hashes from the woltapp/blurhash algorithm, in double precision
+        let rgb = image(ColorType::Rgb, |x, y| {
+            vec![(x * 32) as u8, (y * 40) as u8, (255 - x * 16 - y * 20) as u8]
+        });
+        assert_eq!(encode(&rgb).unwrap(), "LrF=a:7jb2xvzlRsfTnVevfAfRf9");
+
+        let grey = image(ColorType::Luma, |x, y| {
+            vec![((x * 37 + y * 53) % 256) as u8]
+        });
+        assert_eq!(encode(&grey).unwrap(), "LJHC1R-;9F~q?bof4nM{9F4n00%M");
+    }
+
+    #[test]
+    fn cmyk_has_no_hash() {
+        let cmyk = image(ColorType::Cmyk, |_, _| vec![0; 4]);
+        assert_eq!(encode(&cmyk), None);
+    }
+}
@@ -0,0 +1,53 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! ISO base media file format boxes, shared by JPEG XL and MP4/MOV.
+
+use std::io::{self, Read};
+
+/// A box header.
+pub struct BoxHeader {
+    pub kind: [u8; 4],
+    /// Length of the header itself.
+    pub header: u64,
+    /// Size of the whole box, `None` when it runs to the end of the file
+    /// (or of the enclosing box).
+    pub size: Option<u64>,
+}
+
+/// Read a box header; `None` at the end of the file.
+pub fn read_box_header<R: Read>(r: &mut R) -> io::Result<Option<BoxHeader>> {
+    let mut raw = [0u8; 8];
+    let n = r.read(&mut raw)?;
+    if n == 0 {
+        return Ok(None);
+    }
+    r.read_exact(&mut raw[n..])?;
+
+    let kind = [raw[4], raw[5], raw[6], raw[7]];
+    let (header, size) =
+        match u32::from_be_bytes([raw[0], raw[1], raw[2], raw[3]]) {
+            0 => (8, None),
+            1 => {
+                let mut large = [0u8; 8];
+                r.read_exact(&mut large)?;
+                (16, Some(u64::from_be_bytes(large)))
+            }
+            size => (8, Some(size as u64)),
+        };
+
+    Ok(Some(BoxHeader { kind, header, size }))
+}
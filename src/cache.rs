@@ -0,0 +1,64 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Keeping a one-time pass over a large tree out of the page cache.
+//!
+//! Real `O_DIRECT` needs aligned buffers and sizes, which whole-file reads
+//! and writes of arbitrary images do not have, so files are read and
+//! written normally and then dropped from the cache with `posix_fadvise`.
+
+use std::{fs::File, io, path::Path};
+
+/// Whether dropping files from the page cache works on this platform.
+pub const SUPPORTED: bool = cfg!(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd"
+));
+
+/// Drop the cached pages of `path`.
+///
+/// Dirty pages cannot be dropped, so a file that was just `written` is
+/// flushed to disk first.
+pub fn drop_cached(path: &Path, written: bool) -> io::Result<()> {
+    let file = File::open(path)?;
+    if written {
+        file.sync_data()?;
+    }
+    fadvise_dontneed(&file)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn fadvise_dontneed(file: &File) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: the descriptor is owned by `file` and stays open for the
+    // duration of the call
+    let ret = unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED)
+    };
+
+    if ret == 0 { Ok(()) } else { Err(io::Error::from_raw_os_error(ret)) }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd"
+)))]
+fn fadvise_dontneed(_file: &File) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,602 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Capture date rewriting for `--date-offset`, `--date-round` and
+//! `--date-remove`.
+//!
+//! The cleaners that keep dates hand them over here in their own encoding,
+//! the EXIF `YYYY:MM:DD HH:MM:SS` text or the QuickTime seconds since 1904,
+//! and write back what comes out in the same number of bytes, so nothing
+//! around them moves.
+
+use clap::ValueEnum;
+
+/// Seconds from 1904-01-01, the QuickTime epoch, to 1970-01-01.
+const QUICKTIME_EPOCH: i64 = 2_082_844_800;
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// How far `--date-round` coarsens dates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Round {
+    Hour,
+    Day,
+    Month,
+    Year,
+}
+
+/// What happens to the dates that survive cleaning.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Dates {
+    /// Seconds added to every date, before rounding.
+    pub offset: i64,
+    /// Round every date down to the start of this unit.
+    pub round: Option<Round>,
+    /// Remove the dates altogether.
+    pub remove: bool,
+}
+
+/// Parse `--date-offset`: a signed number with an `s`, `m`, `h`, `d` or
+/// `w` unit, e.g. `-3d` or `+90m`.
+pub fn parse_offset(s: &str) -> Result<i64, String> {
+    let (sign, rest) = match s.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let split = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    let (value, unit) = rest.split_at(split);
+    let value: i64 =
+        value.parse().map_err(|_| format!("invalid offset '{s}'"))?;
+
+    let unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => DAY,
+        "w" => 7 * DAY,
+        "" => return Err(format!("missing unit in offset '{s}'")),
+        _ => return Err(format!("unknown unit in offset '{s}'")),
+    };
+    value
+        .checked_mul(unit * sign)
+        .filter(|offset| offset.abs() < 10_000 * 365 * DAY)
+        .ok_or_else(|| format!("offset '{s}' is too large"))
+}
+
+impl Dates {
+    /// Whether dates are changed at all.
+    pub fn is_active(&self) -> bool {
+        self.offset != 0 || self.round.is_some() || self.remove
+    }
+
+    /// Whether the parts finer than a second, and the time zone offsets
+    /// once the dates are gone, are to be removed too.
+    pub fn drops_subsec(&self) -> bool {
+        self.round.is_some() || self.remove
+    }
+
+    /// Shift and round Unix time `secs`.
+    fn apply(&self, secs: i64) -> i64 {
+        let secs = secs + self.offset;
+        let days = secs.div_euclid(DAY);
+        match self.round {
+            None => secs,
+            Some(Round::Hour) => secs - secs.rem_euclid(60 * 60),
+            Some(Round::Day) => days * DAY,
+            Some(Round::Month) => {
+                let (year, month, _) = civil_from_days(days);
+                days_from_civil(year, month, 1) * DAY
+            }
+            Some(Round::Year) => {
+                let (year, ..) = civil_from_days(days);
+                days_from_civil(year, 1, 1) * DAY
+            }
+        }
+    }
+
+    /// Rewrite an EXIF date, `YYYY:MM:DD HH:MM:SS` with any ending after
+    /// it; `None` when it is not one, which leaves the value alone. Removed
+    /// dates become blanks, the EXIF way of saying unknown.
+    pub fn exif(&self, value: &[u8]) -> Option<[u8; 19]> {
+        let text = value.get(..19)?;
+        if self.remove {
+            return Some(*b"    :  :     :  :  ");
+        }
+
+        let seps = [(4, b':'), (7, b':'), (10, b' '), (13, b':'), (16, b':')];
+        if seps.iter().any(|&(at, sep)| text[at] != sep) {
+            return None;
+        }
+        let (year, month, day) =
+            (num(&text[0..4])?, num(&text[5..7])?, num(&text[8..10])?);
+        let clock =
+            [num(&text[11..13])?, num(&text[14..16])?, num(&text[17..19])?];
+        let secs = to_secs(year, month, day, clock)?;
+
+        let (year, month, day, [hour, min, sec]) =
+            from_secs(self.apply(secs))?;
+        let out = format!(
+            "{year:04}:{month:02}:{day:02} {hour:02}:{min:02}:{sec:02}"
+        );
+        out.into_bytes().try_into().ok()
+    }
+
+    /// Rewrite an XMP date, `YYYY`, `YYYY-MM`, `YYYY-MM-DD` or that with
+    /// `Thh:mm`, `:ss`, a fraction and a time zone after it, at the
+    /// precision it is written with; the time zone is kept as it is.
+    /// `None` when it is not one, which leaves the value alone; removed
+    /// dates have no blank form and are dropped by the caller.
+    pub fn xmp(&self, value: &[u8]) -> Option<Vec<u8>> {
+        let (date, time) = match value.iter().position(|&b| b == b'T') {
+            Some(t) => (&value[..t], Some(&value[t + 1..])),
+            None => (value, None),
+        };
+        let date = fields(date, b'-', &[4, 2, 2])?;
+        let (clock, fraction, zone) = match time {
+            Some(_) if date.len() < 3 => return None,
+            Some(time) => {
+                let zone = time
+                    .iter()
+                    .position(|&b| matches!(b, b'Z' | b'+' | b'-'))
+                    .unwrap_or(time.len());
+                let (clock, zone) = time.split_at(zone);
+                let dot = clock.iter().position(|&b| b == b'.');
+                let (clock, fraction) =
+                    clock.split_at(dot.unwrap_or(clock.len()));
+                (fields(clock, b':', &[2, 2, 2])?, fraction, zone)
+            }
+            None => (Vec::new(), &b""[..], &b""[..]),
+        };
+        // hours come with minutes, and a fraction with the seconds
+        if clock.len() == 1
+            || (!fraction.is_empty()
+                && (clock.len() < 3 || num(&fraction[1..]).is_none()))
+            || !(zone.is_empty() || zone == b"Z" || is_zone(zone, true))
+        {
+            return None;
+        }
+
+        let part =
+            |parts: &[i64], i: usize, none| *parts.get(i).unwrap_or(&none);
+        let hms = [part(&clock, 0, 0), part(&clock, 1, 0), part(&clock, 2, 0)];
+        let secs =
+            to_secs(date[0], part(&date, 1, 1), part(&date, 2, 1), hms)?;
+        let (year, month, day, [hour, min, sec]) =
+            from_secs(self.apply(secs))?;
+
+        let mut out = format!("{year:04}-{month:02}-{day:02}");
+        out.truncate([4, 7, 10][date.len() - 1]);
+        let time = format!("T{hour:02}:{min:02}:{sec:02}");
+        out += &time[..[0, 0, 6, 9][clock.len()]];
+        let mut out = out.into_bytes();
+        if !self.drops_subsec() {
+            out.extend_from_slice(fraction);
+        }
+        out.extend_from_slice(zone);
+        Some(out)
+    }
+
+    /// Rewrite an IPTC date, `CCYYMMDD`, and the time going with it,
+    /// `HHMMSS` with a `+HHMM` zone after it, which are shifted together;
+    /// either can be missing. `None` for what was not given or is not one,
+    /// which leaves it alone; removed dates are dropped by the caller.
+    pub fn iptc(
+        &self,
+        date: Option<&[u8]>,
+        time: Option<&[u8]>,
+    ) -> (Option<Vec<u8>>, Option<Vec<u8>>) {
+        let date = date.filter(|date| date.len() == 8).and_then(|date| {
+            to_secs(
+                num(&date[..4])?,
+                num(&date[4..6])?,
+                num(&date[6..])?,
+                [0; 3],
+            )
+        });
+        let time = time.and_then(|time| {
+            let (clock, zone) = time.split_at_checked(6)?;
+            if !(zone.is_empty() || is_zone(zone, false)) {
+                return None;
+            }
+            let hms =
+                [num(&clock[..2])?, num(&clock[2..4])?, num(&clock[4..])?];
+            let secs = hms[0] * 60 * 60 + hms[1] * 60 + hms[2];
+            (hms[0] < 24 && hms[1] < 60 && hms[2] < 61).then_some((secs, zone))
+        });
+        if date.is_none() && time.is_none() {
+            return (None, None);
+        }
+
+        let secs = date.unwrap_or(0) + time.map_or(0, |(secs, _)| secs);
+        let Some((year, month, day, [hour, min, sec])) =
+            from_secs(self.apply(secs))
+        else {
+            return (None, None);
+        };
+        (
+            date.map(|_| format!("{year:04}{month:02}{day:02}").into_bytes()),
+            time.map(|(_, zone)| {
+                let clock = format!("{hour:02}{min:02}{sec:02}");
+                [clock.as_bytes(), zone].concat()
+            }),
+        )
+    }
+
+    /// Rewrite a QuickTime time, seconds since 1904; zero means unset and
+    /// is what removed dates become.
+    pub fn quicktime(&self, secs: u64) -> u64 {
+        if secs == 0 || self.remove {
+            return 0;
+        }
+        let unix = secs as i64 - QUICKTIME_EPOCH;
+        (self.apply(unix) + QUICKTIME_EPOCH).max(0) as u64
+    }
+}
+
+/// Seconds since 1970-01-01 of a date and time of day, as written, with
+/// no time zone; `None` for a month or day out of range.
+fn to_secs(
+    year: i64,
+    month: i64,
+    day: i64,
+    [hour, min, sec]: [i64; 3],
+) -> Option<i64> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let days = days_from_civil(year, month as u32, day as u32);
+    Some(days * DAY + hour * 60 * 60 + min * 60 + sec)
+}
+
+/// The date and time of day `secs` after 1970-01-01; `None` past the
+/// years that fit four digits.
+fn from_secs(secs: i64) -> Option<(i64, u32, u32, [i64; 3])> {
+    let (year, month, day) = civil_from_days(secs.div_euclid(DAY));
+    let time = secs.rem_euclid(DAY);
+    let clock = [time / 3600, time % 3600 / 60, time % 60];
+    (0..=9999).contains(&year).then_some((year, month, day, clock))
+}
+
+/// The numbers of `s` between `sep`, each written with as many digits as
+/// `widths` says; fewer of them than that is fine, none is not.
+fn fields(s: &[u8], sep: u8, widths: &[usize]) -> Option<Vec<i64>> {
+    let parts: Vec<_> = s.split(|&b| b == sep).collect();
+    if parts.len() > widths.len() {
+        return None;
+    }
+    parts
+        .iter()
+        .zip(widths)
+        .map(|(part, &len)| (part.len() == len).then(|| num(part))?)
+        .collect()
+}
+
+/// A number written in ASCII digits only.
+fn num(digits: &[u8]) -> Option<i64> {
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+/// Whether `zone` is a time zone offset, `+HH:MM` with `colon` and `+HHMM`
+/// without.
+fn is_zone(zone: &[u8], colon: bool) -> bool {
+    let digits = match (colon, zone) {
+        (true, [b'+' | b'-', h1, h2, b':', m1, m2])
+        | (false, [b'+' | b'-', h1, h2, m1, m2]) => [h1, h2, m1, m2],
+        _ => return false,
+    };
+    digits.iter().all(|b| b.is_ascii_digit())
+}
+
+/// An RFC 3339 timestamp of `unix` seconds, in the time zone `offset`
+/// seconds east of UTC.
+pub fn rfc3339(unix: i64, offset: i64) -> String {
+    let local = unix + offset;
+    let (year, month, day) = civil_from_days(local.div_euclid(DAY));
+    let secs = local.rem_euclid(DAY);
+    let zone = match offset {
+        0 => "Z".to_string(),
+        _ => {
+            let sign = if offset < 0 { '-' } else { '+' };
+            let abs = offset.abs();
+            format!("{sign}{:02}:{:02}", abs / 3600, abs / 60 % 60)
+        }
+    };
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}{zone}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// The date `days` after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shifted(offset: &str) -> Dates {
+        Dates { offset: parse_offset(offset).unwrap(), ..Dates::default() }
+    }
+
+    fn rounded(round: Round) -> Dates {
+        Dates { round: Some(round), ..Dates::default() }
+    }
+
+    fn exif(dates: &Dates, value: &str) -> Option<String> {
+        let date = dates.exif(value.as_bytes())?;
+        Some(String::from_utf8(date.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn offsets_take_a_sign_and_unit() {
+        assert_eq!(parse_offset("90s"), Ok(90));
+        assert_eq!(parse_offset("+90m"), Ok(90 * 60));
+        assert_eq!(parse_offset("-2h"), Ok(-2 * 60 * 60));
+        assert_eq!(parse_offset("-3d"), Ok(-3 * DAY));
+        assert_eq!(parse_offset("1w"), Ok(7 * DAY));
+        assert_eq!(parse_offset("0d"), Ok(0));
+
+        for s in ["", "d", "-", "+-3d", " 3d", "x3d"] {
+            assert_eq!(parse_offset(s), Err(format!("invalid offset '{s}'")));
+        }
+        assert_eq!(
+            parse_offset("3"),
+            Err("missing unit in offset '3'".into())
+        );
+        for s in ["3y", "3D", "3 d", "3dd", "1.5d"] {
+            assert_eq!(
+                parse_offset(s),
+                Err(format!("unknown unit in offset '{s}'"))
+            );
+        }
+        for s in ["-9223372036854775807w", "99999999w"] {
+            assert_eq!(
+                parse_offset(s),
+                Err(format!("offset '{s}' is too large"))
+            );
+        }
+    }
+
+    #[test]
+    fn civil_days_round_trip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(days_from_civil(1904, 1, 1) * DAY, -QUICKTIME_EPOCH);
+        for days in (-800_000..800_000).step_by(997) {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days, "{days}");
+        }
+    }
+
+    #[test]
+    fn exif_dates_cross_months_and_leap_days() {
+        let cases = [
+            ("+1d", "2024:02:28 12:00:00", "2024:02:29 12:00:00"),
+            ("+1d", "2023:02:28 12:00:00", "2023:03:01 12:00:00"),
+            ("+1d", "2100:02:28 00:00:00", "2100:03:01 00:00:00"),
+            ("+1d", "2000:02:28 00:00:00", "2000:02:29 00:00:00"),
+            ("-1d", "2024:03:01 00:00:00", "2024:02:29 00:00:00"),
+            ("+1s", "2024:12:31 23:59:59", "2025:01:01 00:00:00"),
+            ("-1s", "1970:01:01 00:00:00", "1969:12:31 23:59:59"),
+            ("-3d", "1970:01:02 10:20:30", "1969:12:30 10:20:30"),
+            ("-1w", "1904:01:03 00:00:00", "1903:12:27 00:00:00"),
+            ("+0s", "2024:07:15 10:20:30", "2024:07:15 10:20:30"),
+        ];
+        for (offset, value, expected) in cases {
+            let dates = shifted(offset);
+            assert_eq!(
+                exif(&dates, value).as_deref(),
+                Some(expected),
+                "{value}"
+            );
+            // and back again
+            let back = Dates { offset: -dates.offset, ..dates };
+            assert_eq!(exif(&back, expected).as_deref(), Some(value));
+        }
+    }
+
+    #[test]
+    fn exif_dates_are_rounded_down() {
+        let value = "2024:02:29 23:59:59";
+        let cases = [
+            (Round::Hour, "2024:02:29 23:00:00"),
+            (Round::Day, "2024:02:29 00:00:00"),
+            (Round::Month, "2024:02:01 00:00:00"),
+            (Round::Year, "2024:01:01 00:00:00"),
+        ];
+        for (round, expected) in cases {
+            assert_eq!(
+                exif(&rounded(round), value).as_deref(),
+                Some(expected)
+            );
+        }
+
+        // the offset comes first
+        let dates = Dates { round: Some(Round::Month), ..shifted("+1s") };
+        assert_eq!(
+            exif(&dates, "2024:02:29 23:59:59").as_deref(),
+            Some("2024:03:01 00:00:00")
+        );
+        let dates = Dates { round: Some(Round::Day), ..shifted("-1h") };
+        assert_eq!(
+            exif(&dates, "1970:01:01 00:30:00").as_deref(),
+            Some("1969:12:31 00:00:00")
+        );
+    }
+
+    #[test]
+    fn exif_values_that_are_not_dates_stay() {
+        let dates = shifted("+1d");
+        for value in [
+            "",
+            "2024:07:15",
+            "2024-07-15 10:20:30",
+            "2024:13:15 10:20:30",
+            "2024:07:00 10:20:30",
+            "2024:07:15 1O:20:30",
+            "    :  :     :  :  ",
+        ] {
+            assert_eq!(exif(&dates, value), None, "{value}");
+        }
+        assert_eq!(exif(&shifted("+1d"), "9999:12:31 00:00:00"), None);
+        assert_eq!(exif(&shifted("-1d"), "0000:01:01 00:00:00"), None);
+
+        // any ending is left after the date
+        assert_eq!(
+            exif(&dates, "2024:07:15 10:20:30\0").as_deref(),
+            Some("2024:07:16 10:20:30")
+        );
+        let removed = Dates { remove: true, ..Dates::default() };
+        assert_eq!(
+            exif(&removed, "2024:07:15 10:20:30").as_deref(),
+            Some("    :  :     :  :  ")
+        );
+    }
+
+    #[test]
+    fn quicktime_times_count_from_1904() {
+        let july = QUICKTIME_EPOCH as u64 + 1_721_038_830;
+        assert_eq!(shifted("-1d").quicktime(july), july - DAY as u64);
+        assert_eq!(
+            rounded(Round::Day).quicktime(july),
+            july - 1_721_038_830 % DAY as u64
+        );
+        // before 1970, and clamped at 1904
+        assert_eq!(shifted("-1d").quicktime(2 * DAY as u64), DAY as u64);
+        assert_eq!(shifted("-1w").quicktime(DAY as u64), 0);
+        // 1904 was a leap year
+        assert_eq!(
+            rounded(Round::Year).quicktime(400 * DAY as u64),
+            366 * DAY as u64
+        );
+        // unset stays unset, removed becomes unset
+        assert_eq!(shifted("+1d").quicktime(0), 0);
+        let removed = Dates { remove: true, ..Dates::default() };
+        assert_eq!(removed.quicktime(july), 0);
+    }
+
+    #[test]
+    fn xmp_dates_keep_their_precision_and_zone() {
+        let xmp = |dates: &Dates, value: &str| {
+            let date = dates.xmp(value.as_bytes())?;
+            Some(String::from_utf8(date).unwrap())
+        };
+        let cases = [
+            ("2024", "2023"),
+            ("2024-03", "2024-02"),
+            ("2024-03-01", "2024-02-29"),
+            ("2024-03-01T10:20", "2024-02-29T10:20"),
+            ("2024-03-01T10:20:30", "2024-02-29T10:20:30"),
+            ("2024-03-01T10:20:30.25Z", "2024-02-29T10:20:30.25Z"),
+            ("2024-03-01T10:20:30-03:00", "2024-02-29T10:20:30-03:00"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(xmp(&shifted("-1d"), value).as_deref(), Some(expected));
+        }
+        assert_eq!(
+            xmp(&rounded(Round::Hour), "2024-03-01T10:20:30.25+02:00")
+                .as_deref(),
+            Some("2024-03-01T10:00:00+02:00")
+        );
+
+        for value in [
+            "",
+            "24",
+            "2024-3-01",
+            "2024-03T10:20",
+            "2024-03-01T10",
+            "2024-03-01T10:20.5",
+            "2024-03-01T10:20:30.",
+            "2024-03-01T10:20:30+2",
+            "2024-03-01 10:20:30",
+            "2024-13-01",
+            "Tuesday",
+        ] {
+            assert_eq!(xmp(&shifted("-1d"), value), None, "{value}");
+        }
+    }
+
+    #[test]
+    fn iptc_dates_move_with_their_time() {
+        let iptc = |dates: &Dates, date: Option<&str>, time: Option<&str>| {
+            let (date, time) =
+                dates.iptc(date.map(str::as_bytes), time.map(str::as_bytes));
+            let text = |value: Vec<u8>| String::from_utf8(value).unwrap();
+            (date.map(text), time.map(text))
+        };
+        let some = |s: &str| Some(s.to_string());
+
+        assert_eq!(
+            iptc(&shifted("+1h"), Some("20231231"), Some("233000+0100")),
+            (some("20240101"), some("003000+0100"))
+        );
+        assert_eq!(
+            iptc(&shifted("-1d"), Some("20240301"), None),
+            (some("20240229"), None)
+        );
+        assert_eq!(
+            iptc(&shifted("+1h"), None, Some("233000")),
+            (None, some("003000"))
+        );
+        assert_eq!(
+            iptc(
+                &rounded(Round::Month),
+                Some("20240229"),
+                Some("101010-0500")
+            ),
+            (some("20240201"), some("000000-0500"))
+        );
+        for (date, time) in [
+            (Some("2024"), None),
+            (Some("20241301"), None),
+            (None, Some("1020")),
+            (None, Some("102030+01")),
+            (None, Some("250000")),
+            (None, None),
+        ] {
+            assert_eq!(iptc(&shifted("+1h"), date, time), (None, None));
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Dry-run comparison of would-be outputs against an existing output tree.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::Context;
+use ignore::WalkBuilder;
+use log::{info, warn};
+
+use crate::{jpeg, thumb};
+
+/// What a real run would do to one output file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Add,
+    Update,
+    Unchanged,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Change::Add => "add",
+            Change::Update => "update",
+            Change::Unchanged => "keep",
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DryRunDiff {
+    added: AtomicUsize,
+    updated: AtomicUsize,
+    unchanged: AtomicUsize,
+}
+
+impl DryRunDiff {
+    /// Compare the cleaned bytes with the current contents of `dst`.
+    ///
+    /// `clean` only runs when `dst` already exists, so a dry run over a
+    /// fresh output tree still does not read any image data.
+    pub fn check<F>(&self, dst: &Path, clean: F) -> anyhow::Result<Change>
+    where
+        F: FnOnce() -> anyhow::Result<Vec<u8>>,
+    {
+        let change = if !dst.exists() {
+            Change::Add
+        } else {
+            let cleaned = clean()?;
+            let current = fs::read(dst).with_context(|| {
+                format!("failed to read '{}'", dst.display())
+            })?;
+
+            if cleaned == current { Change::Unchanged } else { Change::Update }
+        };
+
+        let counter = match change {
+            Change::Add => &self.added,
+            Change::Update => &self.updated,
+            Change::Unchanged => &self.unchanged,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        Ok(change)
+    }
+
+    pub fn log_summary(&self, stale: &[PathBuf]) {
+        for path in stale {
+            info!("dry-run: stale '{}' (no matching input)", path.display());
+        }
+
+        info!(
+            "dry-run diff: add={} update={} unchanged={} stale={}",
+            self.added.load(Ordering::Relaxed),
+            self.updated.load(Ordering::Relaxed),
+            self.unchanged.load(Ordering::Relaxed),
+            stale.len(),
+        );
+    }
+}
+
+/// Files under `output_root` whose source no longer exists in `input_root`;
+/// the resized copies of `--derivatives` at `widths` go by their image, and
+/// so do the videos of `--extract-motion-video` when `motion` is set.
+pub fn stale_outputs(
+    input_root: &Path,
+    output_root: &Path,
+    widths: &[u16],
+    motion: bool,
+) -> Vec<PathBuf> {
+    let mut stale = Vec::new();
+
+    let walk = WalkBuilder::new(output_root)
+        .hidden(false)
+        .follow_links(false)
+        .standard_filters(false)
+        .build();
+
+    for result in walk {
+        let entry = match result {
+            Ok(entry) => entry,
+            Err(err) => {
+                warn!("output walk error: {err}");
+                continue;
+            }
+        };
+
+        if !entry.file_type().is_some_and(|ft| ft.is_file()) {
+            continue;
+        }
+
+        let Ok(rel) = entry.path().strip_prefix(output_root) else {
+            continue;
+        };
+
+        let derived = thumb::derivative_source(rel, widths)
+            .is_some_and(|source| input_root.join(source).is_file())
+            || (motion
+                && jpeg::motion_video_sources(rel)
+                    .iter()
+                    .any(|source| input_root.join(source).is_file()));
+        if !derived && !input_root.join(rel).is_file() {
+            stale.push(entry.into_path());
+        }
+    }
+
+    stale
+}
@@ -0,0 +1,77 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Error logging that collapses storms of the same failure.
+//!
+//! Errors are grouped by their root cause (e.g. "Permission denied"). The
+//! first few of each group are logged in full, the rest only at debug
+//! level, and a count per group is logged at the end of the run.
+
+use std::{collections::HashMap, fmt, sync::Mutex};
+
+use log::{debug, error, warn};
+
+/// Errors of one cause that are logged in full before collapsing.
+const FULL_LIMIT: usize = 5;
+
+#[derive(Debug, Default)]
+pub struct ErrorLog {
+    seen: Mutex<HashMap<String, usize>>,
+}
+
+impl ErrorLog {
+    /// Log `msg`, unless `cause` has already been logged often enough.
+    pub fn error(&self, cause: &str, msg: fmt::Arguments<'_>) {
+        let count = {
+            let mut seen = self.seen.lock().unwrap();
+            let count = seen.entry(cause.to_string()).or_default();
+            *count += 1;
+            *count
+        };
+
+        if count <= FULL_LIMIT {
+            error!("{msg}");
+        } else {
+            debug!("{msg}");
+        }
+
+        if count == FULL_LIMIT {
+            warn!("repeated error '{cause}', further ones only with -v");
+        }
+    }
+
+    pub fn log_summary(&self) {
+        let seen = self.seen.lock().unwrap();
+
+        let mut collapsed: Vec<_> =
+            seen.iter().filter(|(_, count)| **count > FULL_LIMIT).collect();
+        collapsed.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+        for (cause, count) in collapsed {
+            error!("'{cause}': {count} errors in total");
+        }
+    }
+}
+
+/// Grouping key of a processing error.
+pub fn cause(err: &anyhow::Error) -> String {
+    err.root_cause().to_string()
+}
+
+/// Grouping key of a walk error, without the path it happened on.
+pub fn walk_cause(err: &ignore::Error) -> String {
+    err.io_error().map_or_else(|| err.to_string(), |err| err.to_string())
+}
@@ -0,0 +1,174 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! `imgst estimate`: header-only analysis of an input tree.
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::bail;
+use ignore::WalkState;
+use log::info;
+
+use crate::{
+    errlog::{self, ErrorLog},
+    format::Format,
+    meta::MetaStats,
+    stamp::Stamp,
+};
+
+#[derive(Debug, Default)]
+struct FormatTotals {
+    files: usize,
+    failed: usize,
+    bytes: u64,
+    metadata_bytes: u64,
+}
+
+#[derive(Debug, Default)]
+struct Totals {
+    /// Supported formats, by name.
+    formats: BTreeMap<&'static str, FormatTotals>,
+    /// Files that would be skipped, by lowercase extension.
+    skipped: BTreeMap<String, usize>,
+    metadata: MetaStats,
+}
+
+pub fn run(
+    input: &Path,
+    num_threads: usize,
+    include_derivatives: bool,
+    stamp: &Stamp,
+) -> anyhow::Result<()> {
+    if !input.is_dir() {
+        bail!("input path '{}' is not directory", input.display());
+    }
+
+    stamp.log_start();
+    info!("input directory: {}", input.display());
+
+    let totals = Arc::new(Mutex::new(Totals::default()));
+    let errors = Arc::new(ErrorLog::default());
+
+    crate::walker(input, num_threads, include_derivatives).run(|| {
+        let totals = Arc::clone(&totals);
+        let errors = Arc::clone(&errors);
+
+        Box::new(move |result| {
+            let entry = match result {
+                Ok(entry) => entry,
+                Err(err) => {
+                    errors.error(
+                        &errlog::walk_cause(&err),
+                        format_args!("walk error: {err}"),
+                    );
+                    return WalkState::Continue;
+                }
+            };
+
+            if !entry.file_type().is_some_and(|ft| ft.is_file()) {
+                return WalkState::Continue;
+            }
+
+            let path = entry.path();
+            // DNG is only cleaned with --raw clean-dng
+            let format = Format::from_path(path).filter(|&f| f != Format::Dng);
+            let Some(format) = format else {
+                let ext = path
+                    .extension()
+                    .map(|e| {
+                        format!(".{}", e.to_string_lossy().to_lowercase())
+                    })
+                    .unwrap_or_else(|| "(none)".into());
+                *totals.lock().unwrap().skipped.entry(ext).or_default() += 1;
+                return WalkState::Continue;
+            };
+
+            let scan = format.scan_file(path);
+
+            let mut totals = totals.lock().unwrap();
+            let format = totals.formats.entry(format.name()).or_default();
+            format.files += 1;
+            match scan {
+                Ok((len, info)) => {
+                    format.bytes += len;
+                    format.metadata_bytes += info.metadata_bytes;
+                    totals.metadata.record_headers(info);
+                }
+                Err(err) => {
+                    format.failed += 1;
+                    errors.error(
+                        &errlog::cause(&err),
+                        format_args!(
+                            "failed to scan '{}': {err:#}",
+                            path.display()
+                        ),
+                    );
+                }
+            }
+
+            WalkState::Continue
+        })
+    });
+
+    errors.log_summary();
+
+    let totals = Arc::into_inner(totals)
+        .expect("walker threads have finished")
+        .into_inner()
+        .unwrap();
+
+    print_totals(&totals, stamp);
+
+    Ok(())
+}
+
+fn print_totals(totals: &Totals, stamp: &Stamp) {
+    let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+
+    let mut bytes = 0;
+    let mut metadata = 0;
+
+    println!();
+    println!("Estimate:");
+    stamp.print();
+    for (name, format) in &totals.formats {
+        println!(
+            "{name}: {} files ({} failed), {:.2} MB, metadata {:.2} MB",
+            format.files,
+            format.failed,
+            mb(format.bytes),
+            mb(format.metadata_bytes)
+        );
+        bytes += format.bytes;
+        metadata += format.metadata_bytes;
+    }
+    for (ext, count) in &totals.skipped {
+        println!("skipped {ext}: {count} files");
+    }
+
+    let pct =
+        if bytes > 0 { metadata as f64 / bytes as f64 * 100.0 } else { 0.0 };
+
+    println!("Source total: {:.2} MB", mb(bytes));
+    println!("Metadata total: {:.2} MB ({pct:.1}%)", mb(metadata));
+    println!("Predicted clean total: {:.2} MB", mb(bytes - metadata));
+    totals.metadata.print();
+    println!();
+}
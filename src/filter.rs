@@ -0,0 +1,496 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Selecting files by their EXIF data, for `--filter`.
+//!
+//! An expression compares fields of the primary image, by their EXIF tag
+//! name, with strings, numbers and dates, and combines the comparisons:
+//!
+//! ```text
+//! Model == "iPhone 15" && has(GPS) && DateTimeOriginal > 2024-01-01
+//! ```
+//!
+//! `==`, `!=`, `<`, `<=`, `>` and `>=` compare, `&&`, `||` and `!` combine,
+//! with parentheses for grouping. `has(Name)` tells whether a field is
+//! there, `has(GPS)` whether any GPS field is. A comparison with a field
+//! that is missing, or that is not of the type of its value, is false,
+//! whichever the operator.
+
+use std::cmp::Ordering;
+
+use exif::{Context, Exif, Field, In, Reader, Value};
+
+/// A parsed `--filter` expression.
+#[derive(Debug, Clone)]
+pub struct Filter(Expr);
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Has(String),
+    Compare(String, Op, Literal),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone)]
+enum Literal {
+    Text(String),
+    Number(f64),
+    /// Year, month, day, hour, minute and second.
+    Date([u32; 6]),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Text(String),
+    /// A number or a date, told apart by the parser.
+    Value(String),
+    Symbol(&'static str),
+}
+
+/// Operators and punctuation, the two-character ones first.
+const SYMBOLS: &[&str] =
+    &["==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "(", ")"];
+
+/// Parse `--filter`.
+pub fn parse_filter(s: &str) -> Result<Filter, String> {
+    let tokens = tokenize(s)?;
+    let mut parser = Parser { tokens: &tokens, pos: 0 };
+    let expr = parser.or()?;
+    match parser.next() {
+        None => Ok(Filter(expr)),
+        Some(token) => Err(format!("unexpected {}", describe(Some(token)))),
+    }
+}
+
+impl Filter {
+    /// Whether an image whose first EXIF block is `tiff` is selected; an
+    /// image without EXIF data, or with a broken block, has no fields.
+    pub fn matches(&self, tiff: Option<Vec<u8>>) -> bool {
+        let exif = tiff.and_then(|tiff| Reader::new().read_raw(tiff).ok());
+        self.0.eval(exif.as_ref())
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        let len = if c == '"' {
+            let Some(end) = rest[1..].find('"') else {
+                return Err("unterminated string".to_string());
+            };
+            tokens.push(Token::Text(rest[1..end + 1].to_string()));
+            end + 2
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..len].to_string()));
+            len
+        } else if c.is_ascii_digit() || c == '-' || c == '.' {
+            let len = rest
+                .find(|c: char| {
+                    !c.is_ascii_alphanumeric() && !matches!(c, '-' | '.' | ':')
+                })
+                .unwrap_or(rest.len());
+            tokens.push(Token::Value(rest[..len].to_string()));
+            len
+        } else if let Some(symbol) =
+            SYMBOLS.iter().find(|s| rest.starts_with(*s))
+        {
+            tokens.push(Token::Symbol(symbol));
+            symbol.len()
+        } else {
+            return Err(format!("unexpected '{c}'"));
+        };
+        rest = rest[len..].trim_start();
+    }
+
+    Ok(tokens)
+}
+
+fn describe(token: Option<&Token>) -> String {
+    match token {
+        None => "end of the expression".to_string(),
+        Some(Token::Name(name)) => format!("'{name}'"),
+        Some(Token::Text(text)) => format!("\"{text}\""),
+        Some(Token::Value(value)) => format!("'{value}'"),
+        Some(Token::Symbol(symbol)) => format!("'{symbol}'"),
+    }
+}
+
+/// Recursive descent, `||` binding looser than `&&`, which binds looser
+/// than `!`.
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: &'static str) -> bool {
+        let found = self.tokens.get(self.pos) == Some(&Token::Symbol(symbol));
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> Result<(), String> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        let found = describe(self.tokens.get(self.pos));
+        Err(format!("expected '{symbol}', found {found}"))
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.eat("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        if self.eat("(") {
+            let expr = self.or()?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+
+        let name = match self.next() {
+            Some(Token::Name(name)) => name.clone(),
+            token => {
+                return Err(format!(
+                    "expected a field name, found {}",
+                    describe(token)
+                ));
+            }
+        };
+        if name == "has" {
+            self.expect("(")?;
+            let name = match self.next() {
+                Some(Token::Name(name)) => name.clone(),
+                token => {
+                    return Err(format!(
+                        "expected a field name in has(), found {}",
+                        describe(token)
+                    ));
+                }
+            };
+            self.expect(")")?;
+            return Ok(Expr::Has(name));
+        }
+
+        let op = match self.next() {
+            Some(Token::Symbol("==")) => Op::Eq,
+            Some(Token::Symbol("!=")) => Op::Ne,
+            Some(Token::Symbol("<")) => Op::Lt,
+            Some(Token::Symbol("<=")) => Op::Le,
+            Some(Token::Symbol(">")) => Op::Gt,
+            Some(Token::Symbol(">=")) => Op::Ge,
+            token => {
+                return Err(format!(
+                    "expected a comparison after '{name}', found {}",
+                    describe(token)
+                ));
+            }
+        };
+        let literal = match self.next() {
+            Some(Token::Text(text)) => Literal::Text(text.clone()),
+            Some(Token::Value(value)) => parse_value(value)?,
+            token => {
+                return Err(format!(
+                    "expected a string, number or date, found {}",
+                    describe(token)
+                ));
+            }
+        };
+        Ok(Expr::Compare(name, op, literal))
+    }
+}
+
+/// A number, or a date such as 2024-01-01 or 2024-01-01T10:20:30.
+fn parse_value(value: &str) -> Result<Literal, String> {
+    if let Ok(number) = value.parse() {
+        return Ok(Literal::Number(number));
+    }
+    parse_date(value, '-')
+        .map(Literal::Date)
+        .ok_or_else(|| format!("invalid number or date '{value}'"))
+}
+
+/// Parse a date whose parts are separated by `sep`, with an optional time
+/// after a space or a `T`; missing time parts are zero.
+fn parse_date(s: &str, sep: char) -> Option<[u32; 6]> {
+    let (date, time) = match s.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (s, None),
+    };
+    let mut parts = [0u32; 6];
+
+    let mut date = date.split(sep);
+    for part in &mut parts[..3] {
+        *part = date.next()?.parse().ok()?;
+    }
+    if date.next().is_some() {
+        return None;
+    }
+    if let Some(time) = time {
+        let mut time = time.split(':');
+        for part in &mut parts[3..] {
+            match time.next() {
+                Some(value) => *part = value.parse().ok()?,
+                None => break,
+            }
+        }
+        if time.next().is_some() {
+            return None;
+        }
+    }
+
+    Some(parts)
+}
+
+impl Expr {
+    fn eval(&self, exif: Option<&Exif>) -> bool {
+        match self {
+            Expr::Or(a, b) => a.eval(exif) || b.eval(exif),
+            Expr::And(a, b) => a.eval(exif) && b.eval(exif),
+            Expr::Not(expr) => !expr.eval(exif),
+            Expr::Has(name) if name == "GPS" => exif.is_some_and(|exif| {
+                exif.fields().any(|f| f.tag.context() == Context::Gps)
+            }),
+            Expr::Has(name) => field(exif, name).is_some(),
+            Expr::Compare(name, op, literal) => field(exif, name)
+                .and_then(|field| compare(field, literal))
+                .is_some_and(|ord| op.holds(ord)),
+        }
+    }
+}
+
+impl Op {
+    fn holds(self, ord: Ordering) -> bool {
+        match self {
+            Op::Eq => ord.is_eq(),
+            Op::Ne => ord.is_ne(),
+            Op::Lt => ord.is_lt(),
+            Op::Le => ord.is_le(),
+            Op::Gt => ord.is_gt(),
+            Op::Ge => ord.is_ge(),
+        }
+    }
+}
+
+/// The field of the primary image named `name`.
+fn field<'a>(exif: Option<&'a Exif>, name: &str) -> Option<&'a Field> {
+    exif?
+        .fields()
+        .find(|f| f.ifd_num == In::PRIMARY && f.tag.to_string() == name)
+}
+
+fn compare(field: &Field, literal: &Literal) -> Option<Ordering> {
+    match literal {
+        Literal::Text(text) => Some(field_text(field).as_str().cmp(text)),
+        Literal::Number(number) => field_number(field)?.partial_cmp(number),
+        Literal::Date(date) => {
+            Some(parse_date(&field_text(field), ':')?.cmp(date))
+        }
+    }
+}
+
+/// Text fields without their padding; other fields as exiftool would
+/// print them.
+fn field_text(field: &Field) -> String {
+    match &field.value {
+        Value::Ascii(values) => values.first().map_or_else(String::new, |v| {
+            String::from_utf8_lossy(v)
+                .trim_end_matches(['\0', ' '])
+                .to_string()
+        }),
+        _ => field.display_value().to_string(),
+    }
+}
+
+fn field_number(field: &Field) -> Option<f64> {
+    match &field.value {
+        Value::Rational(values) => values.first().map(|v| v.to_f64()),
+        Value::SRational(values) => values.first().map(|v| v.to_f64()),
+        Value::SShort(values) => values.first().map(|&v| v as f64),
+        Value::SLong(values) => values.first().map(|&v| v as f64),
+        Value::Float(values) => values.first().map(|&v| v as f64),
+        Value::Double(values) => values.first().copied(),
+        Value::Ascii(_) => field_text(field).parse().ok(),
+        value => value.get_uint(0).map(|v| v as f64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use exif::{Rational, Tag, experimental::Writer};
+
+    use super::*;
+
+    /// A TIFF block with the model, date, aperture and ISO of a photo, and
+    /// its latitude unless `gps` is false.
+    fn tiff(gps: bool) -> Vec<u8> {
+        let field = |tag, value| Field { tag, ifd_num: In::PRIMARY, value };
+        let text = |s: &str| Value::Ascii(vec![s.as_bytes().to_vec()]);
+        let mut fields = vec![
+            field(Tag::Model, text("iPhone 15")),
+            field(Tag::DateTimeOriginal, text("2024:07:15 10:20:30")),
+            field(
+                Tag::FNumber,
+                Value::Rational(vec![Rational::from((18, 10))]),
+            ),
+            field(Tag::PhotographicSensitivity, Value::Short(vec![200])),
+        ];
+        if gps {
+            fields.push(field(Tag::GPSLatitudeRef, text("N")));
+        }
+
+        let mut writer = Writer::new();
+        fields.iter().for_each(|field| writer.push_field(field));
+        let mut out = Cursor::new(Vec::new());
+        writer.write(&mut out, false).unwrap();
+        out.into_inner()
+    }
+
+    fn matches(expr: &str, gps: bool) -> bool {
+        parse_filter(expr).unwrap().matches(Some(tiff(gps)))
+    }
+
+    #[test]
+    fn compares_text_numbers_and_dates() {
+        for expr in [
+            "Model == \"iPhone 15\"",
+            "Model != \"Pixel 8\"",
+            "Model > \"Canon\"",
+            "FNumber < 2",
+            "FNumber >= 1.8",
+            "PhotographicSensitivity == 200",
+            "DateTimeOriginal > 2024-01-01",
+            "DateTimeOriginal <= 2024-07-15T10:20:30",
+            "DateTimeOriginal < 2024-07-15T10:21",
+        ] {
+            assert!(matches(expr, true), "{expr}");
+        }
+        for expr in [
+            "Model == \"iPhone\"",
+            "FNumber > 2",
+            "DateTimeOriginal < 2024-07-15",
+            "DateTimeOriginal > 2024-07-15T10:20:30",
+        ] {
+            assert!(!matches(expr, true), "{expr}");
+        }
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        // a || (b && c), which (a || b) && c is not
+        let expr = "FNumber < 2 || Model == \"Pixel\" && FNumber > 5";
+        assert!(matches(expr, true));
+        assert!(!matches(&format!("({expr}) && FNumber > 5"), true));
+        assert!(!matches("(FNumber < 2 || FNumber > 5) && !has(GPS)", true));
+    }
+
+    #[test]
+    fn negation_is_not_inequality() {
+        assert!(matches("!Model == \"Pixel\"", true));
+        assert!(matches("!(Model == \"Pixel\")", true));
+        assert!(matches("Model != \"Pixel\"", true));
+        assert!(!matches("!Model != \"Pixel\"", true));
+        assert!(matches("!!has(Model)", true));
+    }
+
+    #[test]
+    fn has_looks_for_fields_and_gps() {
+        assert!(matches("has(Model)", true));
+        assert!(!matches("has(Artist)", true));
+        assert!(matches("has(GPS)", true));
+        assert!(!matches("has(GPS)", false));
+        assert!(!parse_filter("has(GPS)").unwrap().matches(None));
+    }
+
+    #[test]
+    fn missing_fields_compare_false() {
+        for expr in ["Artist == \"x\"", "Artist != \"x\"", "Artist < 1"] {
+            assert!(!matches(expr, true), "{expr}");
+            assert!(matches(&format!("!({expr})"), true), "{expr}");
+        }
+        // not a date, whichever the operator
+        assert!(!matches("Model < 2024-01-01", true));
+        assert!(!matches("Model >= 2024-01-01", true));
+        assert!(!parse_filter("Model != \"x\"").unwrap().matches(None));
+        let broken = Some(b"not a tiff block".to_vec());
+        assert!(!parse_filter("has(Model)").unwrap().matches(broken));
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        let error = |expr| parse_filter(expr).unwrap_err();
+
+        assert_eq!(error("Model == \"iPhone"), "unterminated string");
+        assert_eq!(error("has(GPS) has(Model)"), "unexpected 'has'");
+        assert_eq!(error("FNumber < 2 )"), "unexpected ')'");
+        assert_eq!(
+            error("(has(GPS)"),
+            "expected ')', found end of the expression"
+        );
+        assert_eq!(
+            error("Model \"x\""),
+            "expected a comparison after 'Model', found \"x\""
+        );
+        assert_eq!(
+            error("DateTimeOriginal > 2024-13"),
+            "invalid number or date '2024-13'"
+        );
+        assert_eq!(error("Model = \"x\""), "unexpected '='");
+        assert!(parse_filter("").is_err());
+        assert!(parse_filter("has()").is_err());
+    }
+}
@@ -0,0 +1,445 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Supported image formats and dispatch to their cleaners.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, Cursor, Read},
+    path::Path,
+};
+
+use anyhow::Context;
+use clap::ValueEnum;
+
+use crate::{gif, jpeg, jxl, keep::Keep, mp4, pdf, png, svg, tiff, webp};
+
+/// What the headers of an image say about its metadata.
+#[derive(Debug, Default, Clone)]
+pub struct HeaderInfo {
+    /// Number of segments (or chunks) looked at.
+    pub segments: usize,
+    /// Bytes taken by metadata segments, including their framing.
+    pub metadata_bytes: u64,
+    /// TIFF data of the first EXIF block, if any.
+    pub exif: Option<Vec<u8>>,
+    /// Bytes after the end of the image, part of `metadata_bytes`.
+    pub trailer_bytes: u64,
+}
+
+/// How much of the start of a file [`Format::sniff`] looks at.
+pub const SNIFF_LEN: usize = 1024;
+
+/// `ftyp` brands of still images and RAW files, which share the MP4 box
+/// structure but keep the picture in their `meta` box.
+const IMAGE_BRANDS: &[&[u8; 4]] = &[
+    b"avif", b"avis", b"crx ", b"heic", b"heim", b"heis", b"heix", b"hevc",
+    b"hevx", b"mif1", b"msf1",
+];
+
+/// The formats a cleaning run handles, from `--formats`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Selection {
+    #[default]
+    All,
+    Only(Vec<Format>),
+}
+
+impl Selection {
+    pub fn contains(&self, format: Format) -> bool {
+        match self {
+            Selection::All => true,
+            Selection::Only(formats) => formats.contains(&format),
+        }
+    }
+}
+
+/// What happens to the files a run does not clean, from `--others`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Others {
+    /// Leave them out of the output
+    #[default]
+    Skip,
+    /// Copy them to the output unchanged, metadata and all
+    Copy,
+}
+
+/// Parse `--formats`: `all` or a comma-separated list of format names.
+pub fn parse_selection(s: &str) -> Result<Selection, String> {
+    let mut formats = Vec::new();
+
+    for name in s.split(',').map(str::trim) {
+        if name.eq_ignore_ascii_case("all") {
+            return Ok(Selection::All);
+        }
+        let format = Format::ALL
+            .iter()
+            .find(|f| name.eq_ignore_ascii_case(f.name()))
+            .ok_or_else(|| {
+                let names: Vec<_> =
+                    Format::ALL.iter().map(|f| f.name()).collect();
+                format!(
+                    "unknown format '{name}', expected all or some of {}",
+                    names.join(", ")
+                )
+            })?;
+        if !formats.contains(format) {
+            formats.push(*format);
+        }
+    }
+
+    Ok(Selection::Only(formats))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Jpeg,
+    Png,
+    Webp,
+    Tiff,
+    Gif,
+    Jxl,
+    /// Only cleaned with `--raw clean-dng`.
+    Dng,
+    Mp4,
+    Pdf,
+    Svg,
+}
+
+impl Format {
+    /// Every format, in the order they are listed to users.
+    pub const ALL: &[Format] = &[
+        Format::Jpeg,
+        Format::Png,
+        Format::Webp,
+        Format::Tiff,
+        Format::Gif,
+        Format::Jxl,
+        Format::Dng,
+        Format::Mp4,
+        Format::Pdf,
+        Format::Svg,
+    ];
+
+    /// Format of `path`, going by its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "jpg" | "jpeg" => Some(Format::Jpeg),
+            "png" => Some(Format::Png),
+            "webp" => Some(Format::Webp),
+            "tif" | "tiff" => Some(Format::Tiff),
+            "gif" => Some(Format::Gif),
+            "jxl" => Some(Format::Jxl),
+            "dng" => Some(Format::Dng),
+            "mp4" | "m4v" | "mov" => Some(Format::Mp4),
+            "pdf" => Some(Format::Pdf),
+            "svg" => Some(Format::Svg),
+            _ => None,
+        }
+    }
+
+    /// Format of a file going by its first bytes.
+    pub fn sniff(head: &[u8]) -> Option<Self> {
+        let text = head.strip_prefix(b"\xef\xbb\xbf").unwrap_or(head);
+
+        if head.starts_with(b"\xff\xd8\xff") {
+            Some(Format::Jpeg)
+        } else if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Format::Png)
+        } else if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP")
+        {
+            Some(Format::Webp)
+        } else if head.starts_with(b"II*\0") || head.starts_with(b"MM\0*") {
+            Some(Format::Tiff)
+        } else if head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a") {
+            Some(Format::Gif)
+        } else if head.starts_with(b"\xff\x0a")
+            || head.starts_with(b"\0\0\0\x0cJXL \r\n\x87\n")
+        {
+            Some(Format::Jxl)
+        } else if head.get(4..8) == Some(b"ftyp")
+            && head
+                .get(8..12)
+                .is_some_and(|brand| !IMAGE_BRANDS.iter().any(|b| *b == brand))
+            || head.get(4..8) == Some(b"moov")
+        {
+            Some(Format::Mp4)
+        } else if head.starts_with(b"%PDF-") {
+            Some(Format::Pdf)
+        } else if has_svg_root(text) {
+            Some(Format::Svg)
+        } else {
+            None
+        }
+    }
+
+    /// Format of the file at `path` whose first bytes are `head`: what the
+    /// content says, the extension only when it says nothing.
+    pub fn detect(path: &Path, head: &[u8]) -> Option<Self> {
+        let hint = Format::from_path(path);
+        match (Format::sniff(head), hint) {
+            // DNG is a TIFF, told apart by its tags only
+            (Some(Format::Tiff), Some(Format::Dng)) => hint,
+            // markup is only sniffed as SVG, which other documents may be
+            // too; their extension says what they are
+            (Some(Format::Svg), None) if is_document(path) => None,
+            (Some(format), _) => Some(format),
+            (None, _) => hint,
+        }
+    }
+
+    /// Name used in summaries.
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Jpeg => "jpeg",
+            Format::Png => "png",
+            Format::Webp => "webp",
+            Format::Tiff => "tiff",
+            Format::Gif => "gif",
+            Format::Jxl => "jxl",
+            Format::Dng => "dng",
+            Format::Mp4 => "mp4",
+            Format::Pdf => "pdf",
+            Format::Svg => "svg",
+        }
+    }
+
+    /// Name used in summaries for files found in preview caches.
+    pub fn preview_name(self) -> &'static str {
+        match self {
+            Format::Jpeg => "jpeg (preview caches)",
+            Format::Png => "png (preview caches)",
+            Format::Webp => "webp (preview caches)",
+            Format::Tiff => "tiff (preview caches)",
+            Format::Gif => "gif (preview caches)",
+            Format::Jxl => "jxl (preview caches)",
+            Format::Dng => "dng (preview caches)",
+            Format::Mp4 => "mp4 (preview caches)",
+            Format::Pdf => "pdf (preview caches)",
+            Format::Svg => "svg (preview caches)",
+        }
+    }
+
+    pub fn scan(self, data: &[u8]) -> io::Result<HeaderInfo> {
+        match self {
+            Format::Jpeg => jpeg::scan_headers(&mut Cursor::new(data)),
+            Format::Png => png::scan_chunks(&mut Cursor::new(data)),
+            Format::Webp => webp::scan_chunks(&mut Cursor::new(data)),
+            Format::Tiff => tiff::scan_ifds(&mut Cursor::new(data)),
+            Format::Gif => gif::scan_blocks(&mut Cursor::new(data)),
+            Format::Jxl => jxl::scan_boxes(&mut Cursor::new(data)),
+            Format::Dng => tiff::scan_dng(&mut Cursor::new(data)),
+            Format::Mp4 => mp4::scan_boxes(&mut Cursor::new(data)),
+            Format::Pdf => pdf::scan_objects(&mut Cursor::new(data)),
+            Format::Svg => svg::scan_elements(&mut Cursor::new(data)),
+        }
+    }
+
+    /// Scan the headers of the file at `path`, returning its size too.
+    ///
+    /// Image data is skipped over, not read, except in PDFs and SVGs and
+    /// the scans of JPEGs, looked through for the end of the image.
+    pub fn scan_file(self, path: &Path) -> anyhow::Result<(u64, HeaderInfo)> {
+        let file = File::open(path)
+            .with_context(|| format!("failed to open '{}'", path.display()))?;
+        let len = file
+            .metadata()
+            .with_context(|| format!("failed to stat '{}'", path.display()))?
+            .len();
+
+        let mut r = BufReader::new(file);
+        let info = match self {
+            Format::Jpeg => jpeg::scan_headers(&mut r),
+            Format::Png => png::scan_chunks(&mut r),
+            Format::Webp => webp::scan_chunks(&mut r),
+            Format::Tiff => tiff::scan_ifds(&mut r),
+            Format::Gif => gif::scan_blocks(&mut r),
+            Format::Jxl => jxl::scan_boxes(&mut r),
+            Format::Dng => tiff::scan_dng(&mut r),
+            Format::Mp4 => mp4::scan_boxes(&mut r),
+            Format::Pdf => pdf::scan_objects(&mut r),
+            Format::Svg => svg::scan_elements(&mut r),
+        };
+        let info = info.with_context(|| {
+            format!("invalid {} headers in '{}'", self, path.display())
+        })?;
+
+        Ok((len, info))
+    }
+
+    /// Copy `data` without its metadata, but for what `keep` asks for: JPEG
+    /// looks at all of it, PNG and WebP at the colour profile and trailer,
+    /// GIF at the trailer and MP4 at the dates; the others do not.
+    pub fn strip(self, data: &[u8], keep: &Keep) -> io::Result<Vec<u8>> {
+        match self {
+            Format::Jpeg => jpeg::strip_metadata(data, keep),
+            Format::Png => png::strip_metadata(data, keep.icc, keep.trailer),
+            Format::Webp => webp::strip_metadata(data, keep.icc, keep.trailer),
+            Format::Tiff => tiff::strip_metadata(data),
+            Format::Gif => gif::strip_metadata(data, keep.trailer),
+            Format::Jxl => jxl::strip_metadata(data),
+            Format::Dng => tiff::strip_dng(data),
+            Format::Mp4 => mp4::strip_metadata(data, &keep.dates),
+            Format::Pdf => pdf::strip_metadata(data),
+            Format::Svg => svg::strip_metadata(data),
+        }
+    }
+
+    /// Width and height of the image in `data`, for the formats
+    /// `--dimensions` lists.
+    pub fn dimensions(self, data: &[u8]) -> Option<(u32, u32)> {
+        match self {
+            Format::Jpeg => jpeg::dimensions(data),
+            Format::Png => png::dimensions(data),
+            _ => None,
+        }
+    }
+}
+
+/// Extensions of markup documents that are never cleaned as SVG, even
+/// when they hold nothing but an `<svg>` element.
+const DOCUMENTS: &[&str] = &["htm", "html", "xht", "xhtml", "xml"];
+
+fn is_document(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| DOCUMENTS.contains(&&*ext.to_ascii_lowercase()))
+}
+
+/// Whether the root element of `text` is `<svg>`; only the XML
+/// declaration, a DOCTYPE, comments, processing instructions and
+/// whitespace may come before it.
+fn has_svg_root(text: &[u8]) -> bool {
+    let mut rest = text;
+    loop {
+        rest = rest.trim_ascii_start();
+        let close: &[u8] = if rest.starts_with(b"<?") {
+            b"?>"
+        } else if rest.starts_with(b"<!--") {
+            b"-->"
+        } else if rest.starts_with(b"<!DOCTYPE") {
+            // an internal subset may hold '>'
+            match rest.iter().position(|&b| b == b'[' || b == b'>') {
+                Some(at) if rest[at] == b'[' => b"]>",
+                _ => b">",
+            }
+        } else {
+            break;
+        };
+        let Some(end) = rest.windows(close.len()).position(|w| w == close)
+        else {
+            return false;
+        };
+        rest = &rest[end + close.len()..];
+    }
+
+    let name = rest.strip_prefix(b"<svg:svg").or(rest.strip_prefix(b"<svg"));
+    name.and_then(|after| after.first())
+        .is_some_and(|&b| b.is_ascii_whitespace() || b == b'>' || b == b'/')
+}
+
+/// Read the start of the file at `path` for [`Format::detect`]; empty when
+/// it cannot be read, leaving its extension to decide.
+pub fn read_head(path: &Path) -> Vec<u8> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)
+        .and_then(|file| file.take(SNIFF_LEN as u64).read_to_end(&mut head))
+        .map_or_else(|_| Vec::new(), |_| head)
+}
+
+/// Read the next `len` bytes of `r`, or `None` when it ends before them.
+///
+/// Lengths are read from the file, so the buffer grows as the bytes come
+/// instead of being sized up front: a broken or crafted file claiming
+/// gigabytes only costs what it holds.
+pub fn read_len<R: Read>(r: &mut R, len: u64) -> io::Result<Option<Vec<u8>>> {
+    let mut buf = Vec::new();
+    r.by_ref().take(len).read_to_end(&mut buf)?;
+    Ok((buf.len() as u64 == len).then_some(buf))
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Jpeg => "JPEG",
+            Format::Png => "PNG",
+            Format::Webp => "WebP",
+            Format::Tiff => "TIFF",
+            Format::Gif => "GIF",
+            Format::Jxl => "JPEG XL",
+            Format::Dng => "DNG",
+            Format::Mp4 => "MP4",
+            Format::Pdf => "PDF",
+            Format::Svg => "SVG",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SVG: &[u8] = include_bytes!("../tests/fixtures/plain.svg");
+
+    #[test]
+    fn sniffs_svg_by_its_root_element() {
+        assert_eq!(Format::sniff(SVG), Some(Format::Svg));
+        for svg in [
+            &b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>"[..],
+            b"\xef\xbb\xbf  <svg>",
+            b"<?xml version=\"1.0\"?>\n<!-- drawn by hand -->\n<svg>",
+            b"<!DOCTYPE svg [<!ENTITY a \"<b>\">]>\n<svg:svg>",
+        ] {
+            assert_eq!(Format::sniff(svg), Some(Format::Svg), "{svg:?}");
+        }
+        for other in [
+            &b"<!DOCTYPE html>\n<html><body><svg></svg></body></html>"[..],
+            b"<?xml version=\"1.0\"?>\n<feed><svg/></feed>",
+            b"<svgfoo>",
+            b"<!-- <svg> unterminated",
+            b"text then <svg>",
+        ] {
+            assert_eq!(Format::sniff(other), None, "{other:?}");
+        }
+    }
+
+    #[test]
+    fn lengths_from_the_file_are_read_as_they_come() {
+        let mut r = Cursor::new(b"abcdef");
+        assert_eq!(read_len(&mut r, 2).unwrap().as_deref(), Some(&b"ab"[..]));
+        assert_eq!(read_len(&mut r, 0).unwrap().as_deref(), Some(&b""[..]));
+        assert_eq!(read_len(&mut r, 5).unwrap(), None);
+        let mut r = Cursor::new(b"short");
+        assert_eq!(read_len(&mut r, u64::MAX).unwrap(), None);
+    }
+
+    #[test]
+    fn markup_documents_are_not_taken_for_svg() {
+        let svg = b"<svg>";
+        for name in ["page.html", "page.HTM", "feed.xml", "page.xhtml"] {
+            assert_eq!(Format::detect(Path::new(name), svg), None, "{name}");
+        }
+        let detect = |name| Format::detect(Path::new(name), svg);
+        assert_eq!(detect("icon.svg"), Some(Format::Svg));
+        assert_eq!(detect("icon"), Some(Format::Svg));
+        // content wins over other extensions
+        assert_eq!(detect("icon.png"), Some(Format::Svg));
+        let jpeg = Format::detect(Path::new("page.html"), b"\xff\xd8\xff");
+        assert_eq!(jpeg, Some(Format::Jpeg));
+    }
+}
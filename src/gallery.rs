@@ -0,0 +1,184 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Static HTML contact sheet of the cleaned output tree.
+
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+use ignore::WalkBuilder;
+
+use crate::xml::escape;
+
+/// Write `gallery` listing every JPEG and PNG under `output_root`.
+///
+/// Links are relative to the gallery file, so the page keeps working when
+/// the whole tree is moved. Previews from `thumbs_root` are used when they
+/// exist, the cleaned image itself otherwise.
+pub fn write(
+    gallery: &Path,
+    output_root: &Path,
+    thumbs_root: Option<&Path>,
+) -> anyhow::Result<usize> {
+    let gallery_dir = match gallery.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(gallery_dir).with_context(|| {
+        format!("failed to create dir '{}'", gallery_dir.display())
+    })?;
+
+    let base = absolute(gallery_dir)?;
+    let output_root = absolute(output_root)?;
+    let thumbs_root = thumbs_root.map(absolute).transpose()?;
+
+    let mut images = collect_images(&output_root);
+    // previews kept inside the output tree are not images of their own
+    if let Some(thumbs) = &thumbs_root {
+        images.retain(|rel| !output_root.join(rel).starts_with(thumbs));
+    }
+
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>imgst gallery</title>\n<style>\n\
+         body { font-family: sans-serif; }\n\
+         figure { display: inline-block; margin: 4px; text-align: center; }\n\
+         figure img { max-width: 256px; max-height: 256px; }\n\
+         figcaption { font-size: small; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+
+    let mut current_dir = None;
+    for rel in &images {
+        let dir = rel.parent().unwrap_or(Path::new(""));
+        if current_dir != Some(dir) {
+            if current_dir.is_some() {
+                html.push_str("</section>\n");
+            }
+            let title = if dir.as_os_str().is_empty() {
+                ".".into()
+            } else {
+                dir.to_string_lossy()
+            };
+            let _ = writeln!(html, "<section>\n<h2>{}</h2>", escape(&title));
+            current_dir = Some(dir);
+        }
+
+        let image = output_root.join(rel);
+        let preview = thumbs_root
+            .as_ref()
+            .map(|root| root.join(rel))
+            .filter(|thumb| thumb.is_file())
+            .unwrap_or_else(|| image.clone());
+
+        let name = rel.file_name().unwrap_or_default().to_string_lossy();
+        let _ = writeln!(
+            html,
+            "<figure><a href=\"{}\"><img src=\"{}\" alt=\"{}\" \
+             loading=\"lazy\"></a><figcaption>{}</figcaption></figure>",
+            url(&relative(&base, &image)),
+            url(&relative(&base, &preview)),
+            escape(&name),
+            escape(&name),
+        );
+    }
+    if current_dir.is_some() {
+        html.push_str("</section>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+
+    fs::write(gallery, html)
+        .with_context(|| format!("failed to write '{}'", gallery.display()))?;
+
+    Ok(images.len())
+}
+
+fn collect_images(root: &Path) -> Vec<PathBuf> {
+    let walk = WalkBuilder::new(root)
+        .standard_filters(false)
+        .follow_links(false)
+        .build();
+
+    let mut images: Vec<PathBuf> = walk
+        .flatten()
+        .filter(|entry| entry.file_type().is_some_and(|ft| ft.is_file()))
+        .filter(|entry| {
+            let ext = entry
+                .path()
+                .extension()
+                .and_then(|s| s.to_str())
+                .map(|s| s.to_ascii_lowercase());
+            matches!(ext.as_deref(), Some("jpg" | "jpeg" | "png"))
+        })
+        .filter_map(|entry| {
+            entry.path().strip_prefix(root).ok().map(Path::to_path_buf)
+        })
+        .collect();
+
+    images.sort();
+    images
+}
+
+fn absolute(path: &Path) -> anyhow::Result<PathBuf> {
+    fs::canonicalize(path)
+        .with_context(|| format!("failed to resolve '{}'", path.display()))
+}
+
+/// Path of `target` relative to the directory `base`; both are absolute.
+fn relative(base: &Path, target: &Path) -> PathBuf {
+    let base: Vec<Component> = base.components().collect();
+    let target: Vec<Component> = target.components().collect();
+
+    let common = base.iter().zip(&target).take_while(|(a, b)| a == b).count();
+
+    let mut rel = PathBuf::new();
+    for _ in common..base.len() {
+        rel.push("..");
+    }
+    for component in &target[common..] {
+        rel.push(component);
+    }
+    rel
+}
+
+/// Percent-encode a relative path for use in an HTML attribute.
+fn url(path: &Path) -> String {
+    let mut out = String::new();
+    for (i, component) in path.components().enumerate() {
+        if i > 0 {
+            out.push('/');
+        }
+        for byte in component.as_os_str().as_encoded_bytes() {
+            match byte {
+                b'A'..=b'Z'
+                | b'a'..=b'z'
+                | b'0'..=b'9'
+                | b'-'
+                | b'_'
+                | b'.'
+                | b'~' => out.push(*byte as char),
+                _ => {
+                    let _ = write!(out, "%{byte:02X}");
+                }
+            }
+        }
+    }
+    out
+}
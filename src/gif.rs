@@ -0,0 +1,265 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! GIF block stream helpers.
+//!
+//! Cleaning drops comment extensions and the application extensions
+//! carrying XMP or IPTC, and copies every other block whole. Image data,
+//! graphic control extensions (frame delays, disposal, transparency) and
+//! the NETSCAPE loop count are never touched, so animations play the same.
+//! Data after the trailer goes too.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::format::HeaderInfo;
+
+const EXTENSION: u8 = 0x21;
+const IMAGE: u8 = 0x2c;
+const TRAILER: u8 = 0x3b;
+
+const COMMENT: u8 = 0xfe;
+const APPLICATION: u8 = 0xff;
+
+/// Application extensions removed by cleaning, by identifier and
+/// authentication code. The ImageMagick ones hold IPTC and Photoshop
+/// resources.
+const METADATA_APPS: &[&[u8; 11]] =
+    &[b"XMP DataXMP", b"MGKIPTC0000", b"MGK8BIM0000"];
+
+/// Whether the extension with `label` and first sub-block `first` is
+/// metadata.
+pub fn is_metadata(label: u8, first: &[u8]) -> bool {
+    match label {
+        COMMENT => true,
+        APPLICATION => METADATA_APPS.iter().any(|app| first == *app),
+        _ => false,
+    }
+}
+
+/// Walk the blocks up to the trailer without reading the image data.
+pub fn scan_blocks<R: Read + Seek>(r: &mut R) -> io::Result<HeaderInfo> {
+    let mut header = [0u8; 13];
+    r.read_exact(&mut header)?;
+    check_header(&header)?;
+    r.seek(SeekFrom::Current(color_table(header[10]) as i64))?;
+
+    let mut info = HeaderInfo::default();
+
+    loop {
+        match read_u8(r)? {
+            EXTENSION => {
+                let label = read_u8(r)?;
+                let len = read_u8(r)?;
+                let mut first = vec![0u8; len as usize];
+                r.read_exact(&mut first)?;
+
+                let mut size = 3 + len as u64;
+                if len > 0 {
+                    size += skip_sub_blocks(r)?;
+                }
+                if is_metadata(label, &first) {
+                    info.metadata_bytes += size;
+                }
+            }
+            IMAGE => {
+                let mut desc = [0u8; 9];
+                r.read_exact(&mut desc)?;
+                // the byte after the table is the LZW minimum code size
+                r.seek(SeekFrom::Current(color_table(desc[8]) as i64 + 1))?;
+                skip_sub_blocks(r)?;
+            }
+            TRAILER => break,
+            _ => return Err(invalid("unknown block")),
+        }
+
+        info.segments += 1;
+    }
+
+    let end = r.stream_position()?;
+    info.trailer_bytes = r.seek(SeekFrom::End(0))?.saturating_sub(end);
+    info.metadata_bytes += info.trailer_bytes;
+
+    Ok(info)
+}
+
+/// Copy `data` without its comment and metadata application extensions,
+/// and without what follows the trailer unless `trailer` is set.
+pub fn strip_metadata(data: &[u8], trailer: bool) -> io::Result<Vec<u8>> {
+    let Some(header) = data.get(..13) else {
+        return Err(invalid("truncated header"));
+    };
+    check_header(header)?;
+    let start = 13 + color_table(header[10]) as usize;
+    let Some(head) = data.get(..start) else {
+        return Err(invalid("truncated colour table"));
+    };
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(head);
+    let mut pos = start;
+
+    loop {
+        let Some(&introducer) = data.get(pos) else {
+            return Err(invalid("missing trailer"));
+        };
+        let end = match introducer {
+            EXTENSION => {
+                let Some(&label) = data.get(pos + 1) else {
+                    return Err(invalid("truncated extension"));
+                };
+                let end = sub_blocks_end(data, pos + 2)?;
+                let len = data[pos + 2] as usize;
+                if is_metadata(label, &data[pos + 3..pos + 3 + len]) {
+                    pos = end;
+                    continue;
+                }
+                end
+            }
+            IMAGE => {
+                let Some(desc) = data.get(pos + 1..pos + 10) else {
+                    return Err(invalid("truncated image descriptor"));
+                };
+                let table = color_table(desc[8]) as usize;
+                sub_blocks_end(data, pos + 10 + table + 1)?
+            }
+            TRAILER => break,
+            _ => return Err(invalid("unknown block")),
+        };
+
+        out.extend_from_slice(&data[pos..end]);
+        pos = end;
+    }
+
+    let end = if trailer { data.len() } else { pos + 1 };
+    out.extend_from_slice(&data[pos..end]);
+
+    Ok(out)
+}
+
+fn check_header(header: &[u8]) -> io::Result<()> {
+    match &header[..6] {
+        b"GIF87a" | b"GIF89a" => Ok(()),
+        _ => Err(invalid("missing GIF header")),
+    }
+}
+
+/// Size of the colour table announced by a packed fields byte.
+fn color_table(packed: u8) -> u64 {
+    match packed & 0x80 {
+        0 => 0,
+        _ => 3 << ((packed & 0x07) + 1),
+    }
+}
+
+/// Skip data sub-blocks up to and including the terminator, returning the
+/// bytes skipped.
+fn skip_sub_blocks<R: Read + Seek>(r: &mut R) -> io::Result<u64> {
+    let mut skipped = 0;
+    loop {
+        let len = read_u8(r)?;
+        skipped += 1 + len as u64;
+        if len == 0 {
+            return Ok(skipped);
+        }
+        r.seek(SeekFrom::Current(len as i64))?;
+    }
+}
+
+/// Position just after the sub-blocks starting at `pos`.
+fn sub_blocks_end(data: &[u8], mut pos: usize) -> io::Result<usize> {
+    loop {
+        let Some(&len) = data.get(pos) else {
+            return Err(invalid("truncated sub-blocks"));
+        };
+        pos += 1 + len as usize;
+        if len == 0 {
+            return Ok(pos);
+        }
+    }
+}
+
+fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut b = [0u8; 1];
+    r.read_exact(&mut b)?;
+    Ok(b[0])
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const PLAIN: &[u8] = include_bytes!("../tests/fixtures/plain.gif");
+    const META: &[u8] = include_bytes!("../tests/fixtures/meta.gif");
+
+    #[test]
+    fn plain_image_is_unchanged() {
+        assert_eq!(strip_metadata(PLAIN, false).unwrap(), PLAIN);
+        assert_eq!(
+            scan_blocks(&mut Cursor::new(PLAIN)).unwrap().metadata_bytes,
+            0
+        );
+    }
+
+    #[test]
+    fn scan_finds_comments_and_xmp() {
+        let info = scan_blocks(&mut Cursor::new(META)).unwrap();
+
+        assert_eq!(info.segments, 8);
+        assert!(info.metadata_bytes > 0);
+        assert!(info.exif.is_none());
+    }
+
+    #[test]
+    fn frames_and_loop_count_are_kept() {
+        // the metadata fixture is the plain animation with comment and XMP
+        // blocks in between
+        let cleaned = strip_metadata(META, false).unwrap();
+
+        assert_eq!(cleaned, PLAIN);
+        assert_eq!(
+            scan_blocks(&mut Cursor::new(&cleaned)).unwrap().metadata_bytes,
+            0
+        );
+    }
+
+    #[test]
+    fn data_after_the_trailer_goes_unless_kept() {
+        let appended = [PLAIN, b"appended"].concat();
+        let info = scan_blocks(&mut Cursor::new(&appended)).unwrap();
+        assert_eq!(info.trailer_bytes, 8);
+        assert_eq!(info.metadata_bytes, 8);
+
+        assert_eq!(strip_metadata(&appended, false).unwrap(), PLAIN);
+        assert_eq!(strip_metadata(&appended, true).unwrap(), appended);
+        assert_eq!(
+            scan_blocks(&mut Cursor::new(PLAIN)).unwrap().trailer_bytes,
+            0
+        );
+    }
+
+    #[test]
+    fn rejects_broken_streams() {
+        assert!(strip_metadata(b"not a gif file", false).is_err());
+        assert!(strip_metadata(&META[..40], false).is_err());
+        assert!(scan_blocks(&mut Cursor::new(&META[..40])).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Coarser GPS coordinates for `--gps-fuzz`.
+//!
+//! Latitude and longitude are snapped to a grid, either a number of decimal
+//! places of a degree or cells of about the given size in meters, so a
+//! photo still shows the town but no longer the street. The grid is fixed,
+//! so every photo taken in a cell gets the same coordinates and nothing
+//! can be averaged back out of a series.
+
+/// Meters in a degree of latitude, and of longitude at the equator.
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// Most decimal places accepted; the seventh is already about a centimeter.
+const MAX_DECIMALS: u32 = 7;
+
+/// Precision kept by `--gps-fuzz`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fuzz {
+    /// Cells of about this many meters.
+    Meters(u32),
+    /// Decimal places of a degree.
+    Decimals(u32),
+}
+
+/// Parse `--gps-fuzz`: a distance with an `m` or `km` unit, or a bare
+/// number of decimal places.
+pub fn parse_fuzz(s: &str) -> Result<Fuzz, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: u32 =
+        value.parse().map_err(|_| format!("invalid precision '{s}'"))?;
+
+    let fuzz = match unit {
+        "" if value <= MAX_DECIMALS => Fuzz::Decimals(value),
+        "" => return Err(format!("at most {MAX_DECIMALS} decimal places")),
+        "m" => Fuzz::Meters(value),
+        "km" => Fuzz::Meters(
+            value.checked_mul(1000).ok_or("distance is too large")?,
+        ),
+        _ => return Err(format!("unknown unit in precision '{s}'")),
+    };
+    if fuzz == Fuzz::Meters(0) {
+        return Err("distance must be greater than zero".into());
+    }
+
+    Ok(fuzz)
+}
+
+impl Fuzz {
+    /// Snap a latitude and longitude, in degrees, to the grid.
+    pub fn apply(&self, lat: f64, lon: f64) -> (f64, f64) {
+        match *self {
+            Fuzz::Decimals(places) => {
+                let step = 10f64.powi(-(places as i32));
+                (snap(lat, step), snap(lon, step))
+            }
+            Fuzz::Meters(meters) => {
+                let meters = meters as f64;
+                let lat =
+                    snap(lat, meters / METERS_PER_DEGREE).clamp(-90.0, 90.0);
+                // degrees of longitude shrink towards the poles
+                let width = METERS_PER_DEGREE * lat.to_radians().cos();
+                let step = (meters / width.max(1.0)).min(360.0);
+                (lat, snap(lon, step).clamp(-180.0, 180.0))
+            }
+        }
+    }
+}
+
+fn snap(value: f64, step: f64) -> f64 {
+    (value / step).round() * step
+}
+
+/// Degrees from an EXIF degrees, minutes and seconds triple of
+/// rationals, given as numerator and denominator pairs.
+pub fn to_degrees(dms: [(u32, u32); 3]) -> Option<f64> {
+    let mut degrees = 0.0;
+    for ((num, denom), unit) in dms.into_iter().zip([1.0, 60.0, 3600.0]) {
+        if denom == 0 {
+            return None;
+        }
+        degrees += num as f64 / denom as f64 / unit;
+    }
+    Some(degrees)
+}
+
+/// The EXIF triple for `degrees`, with the seconds in hundredths.
+pub fn to_dms(degrees: f64) -> [(u32, u32); 3] {
+    let hundredths = (degrees.abs() * 360_000.0).round() as u32;
+    [
+        (hundredths / 360_000, 1),
+        (hundredths % 360_000 / 6000, 1),
+        (hundredths % 6000, 100),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9
+    }
+
+    #[test]
+    fn precisions_are_places_or_distances() {
+        assert_eq!(parse_fuzz("0"), Ok(Fuzz::Decimals(0)));
+        assert_eq!(parse_fuzz("3"), Ok(Fuzz::Decimals(3)));
+        assert_eq!(parse_fuzz("7"), Ok(Fuzz::Decimals(7)));
+        assert_eq!(parse_fuzz("500m"), Ok(Fuzz::Meters(500)));
+        assert_eq!(parse_fuzz("5km"), Ok(Fuzz::Meters(5000)));
+
+        assert_eq!(parse_fuzz("8"), Err("at most 7 decimal places".into()));
+        for s in ["0m", "0km"] {
+            assert_eq!(
+                parse_fuzz(s),
+                Err("distance must be greater than zero".into())
+            );
+        }
+        assert_eq!(
+            parse_fuzz("5000000km"),
+            Err("distance is too large".into())
+        );
+        for s in ["", "km", "-1", "+1km", " 1km"] {
+            assert_eq!(parse_fuzz(s), Err(format!("invalid precision '{s}'")));
+        }
+        for s in ["1.5km", "5mi", "5 km", "5KM"] {
+            assert_eq!(
+                parse_fuzz(s),
+                Err(format!("unknown unit in precision '{s}'"))
+            );
+        }
+    }
+
+    #[test]
+    fn decimals_snap_to_a_fixed_grid() {
+        let fuzz = Fuzz::Decimals(2);
+        assert!(close(fuzz.apply(52.37123, 13.40456), (52.37, 13.40)));
+        assert!(close(fuzz.apply(-33.86785, -151.20732), (-33.87, -151.21)));
+        // everything in a cell ends up in the same place
+        assert_eq!(fuzz.apply(52.3651, 13.4049), fuzz.apply(52.3749, 13.3951));
+        assert!(close(Fuzz::Decimals(0).apply(52.5, -0.4), (53.0, 0.0)));
+    }
+
+    #[test]
+    fn meters_become_degrees_by_latitude() {
+        // a degree of latitude, and of longitude at the equator
+        let fuzz = Fuzz::Meters(111_320);
+        assert!(close(fuzz.apply(0.4, 13.6), (0.0, 14.0)));
+        assert!(close(fuzz.apply(-0.4, -13.4), (0.0, -13.0)));
+        // a degree of longitude is half as wide at 60 degrees
+        assert!(close(fuzz.apply(60.2, 13.4), (60.0, 14.0)));
+        assert!(close(fuzz.apply(60.2, 12.9), (60.0, 12.0)));
+
+        let (lat, lon) = Fuzz::Meters(1000).apply(52.37123, 13.40456);
+        assert!((lat - 52.37123).abs() < 0.0045);
+        assert!((lon - 13.40456).abs() < 0.0075);
+    }
+
+    #[test]
+    fn coarse_cells_stay_on_the_globe() {
+        for fuzz in [Fuzz::Meters(50_000_000), Fuzz::Meters(1000)] {
+            for (lat, lon) in
+                [(89.999, 179.9), (-89.999, -179.9), (90.0, 180.0)]
+            {
+                let (lat, lon) = fuzz.apply(lat, lon);
+                assert!((-90.0..=90.0).contains(&lat), "{fuzz:?} {lat}");
+                assert!((-180.0..=180.0).contains(&lon), "{fuzz:?} {lon}");
+            }
+        }
+    }
+
+    #[test]
+    fn dms_carry_into_minutes_and_degrees() {
+        assert_eq!(to_dms(52.5), [(52, 1), (30, 1), (0, 100)]);
+        assert_eq!(to_dms(-13.4), [(13, 1), (24, 1), (0, 100)]);
+        // 12.3456 seconds keep their hundredths
+        let degrees = 52.0 + 22.0 / 60.0 + 12.3456 / 3600.0;
+        assert_eq!(to_dms(degrees), [(52, 1), (22, 1), (1235, 100)]);
+        // 59.9999 seconds round up to the next minute, and degree
+        let degrees = 10.0 + 59.0 / 60.0 + 59.9999 / 3600.0;
+        assert_eq!(to_dms(degrees), [(11, 1), (0, 1), (0, 100)]);
+        let degrees = 10.0 + 20.0 / 60.0 + 59.996 / 3600.0;
+        assert_eq!(to_dms(degrees), [(10, 1), (21, 1), (0, 100)]);
+    }
+
+    #[test]
+    fn dms_round_trip() {
+        for degrees in [0.0, 52.3704, 13.4049, 89.99999, 179.123456] {
+            let back = to_degrees(to_dms(degrees)).unwrap();
+            assert!((back - degrees).abs() < 0.005 / 3600.0 + 1e-12);
+        }
+        assert_eq!(to_degrees([(52, 1), (30, 1), (1800, 100)]), Some(52.505));
+        assert_eq!(to_degrees([(52, 1), (30, 0), (0, 100)]), None);
+    }
+}
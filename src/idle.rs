@@ -0,0 +1,113 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Pausing the run while the rest of the system is busy.
+
+use std::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+};
+
+use log::info;
+
+/// How often the load is checked while paused.
+const POLL: Duration = Duration::from_secs(5);
+
+/// Whether the load average can be read on this platform.
+pub const SUPPORTED: bool = cfg!(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+));
+
+#[derive(Debug)]
+pub struct IdleGate {
+    max_load: f64,
+    /// Files being processed right now, i.e. our own share of the load.
+    active: AtomicUsize,
+    paused: AtomicBool,
+}
+
+/// Marks one file as being processed until dropped.
+pub struct Active<'a>(&'a IdleGate);
+
+impl Drop for Active<'_> {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl IdleGate {
+    pub fn new(max_load: f64) -> Self {
+        Self {
+            max_load,
+            active: AtomicUsize::new(0),
+            paused: AtomicBool::new(false),
+        }
+    }
+
+    /// Wait until the load not caused by this run drops below the
+    /// threshold, then count the caller as active.
+    pub fn enter(&self) -> Active<'_> {
+        while let Some(load) = load_average() {
+            let others = load - self.active.load(Ordering::Relaxed) as f64;
+            if others < self.max_load {
+                break;
+            }
+
+            if !self.paused.swap(true, Ordering::Relaxed) {
+                info!("system busy (load {load:.2}), pausing");
+            }
+            thread::sleep(POLL);
+        }
+
+        if self.paused.swap(false, Ordering::Relaxed) {
+            info!("system idle, resuming");
+        }
+
+        self.active.fetch_add(1, Ordering::Relaxed);
+        Active(self)
+    }
+}
+
+/// One-minute load average.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn load_average() -> Option<f64> {
+    let mut load = [0f64; 1];
+    // SAFETY: the buffer holds the one sample asked for
+    let n = unsafe { libc::getloadavg(load.as_mut_ptr(), 1) };
+    (n == 1).then_some(load[0])
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn load_average() -> Option<f64> {
+    None
+}
@@ -0,0 +1,331 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! `imgst import`: clean the photos of a memory card into an archive.
+//!
+//! Images are taken from the card's `DCIM` directory when there is one,
+//! filed under `YYYY/YYYY-MM-DD/` by the date they were taken, and every
+//! written file is read back and compared before it counts as imported.
+
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use exif::{In, Reader, Tag, Value};
+use log::{debug, error, info, warn};
+
+use crate::{format::Format, inspect, jpeg, keep::Keep};
+
+/// Directory for images without a usable capture date.
+const UNDATED: &str = "undated";
+
+pub fn run(from: &Path, to: &Path) -> anyhow::Result<()> {
+    if !from.is_dir() {
+        bail!("card path '{}' is not directory", from.display());
+    }
+
+    let dcim = from.join("DCIM");
+    let source = if dcim.is_dir() { dcim } else { from.to_path_buf() };
+    info!("importing from: {}", source.display());
+    info!("archive directory: {}", to.display());
+
+    let counts = import_all(&source, to);
+    info!(
+        "done: imported={} (already clean={}) skipped={} failed={}",
+        counts.imported, counts.already_clean, counts.skipped, counts.failed
+    );
+    if counts.skipped > 0 {
+        info!(
+            "skipped: {} files that are not JPEG images (RAW, video), the \
+             card still has them",
+            counts.skipped
+        );
+    }
+    if counts.failed > 0 {
+        warn!("some files failed to import, the card still has them");
+    }
+
+    Ok(())
+}
+
+/// What an import did, for its summary.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Counts {
+    imported: usize,
+    already_clean: usize,
+    /// Files that are not JPEG images.
+    skipped: usize,
+    /// Files that failed to import and directories that could not be read.
+    failed: usize,
+}
+
+/// Import every JPEG image under `source` into the archive at `to`.
+fn import_all(source: &Path, to: &Path) -> Counts {
+    let mut files = Vec::new();
+    let walked = inspect::collect_jpegs(source, &mut files);
+    let mut counts = Counts {
+        skipped: walked.others.len(),
+        failed: walked.errors.len(),
+        ..Counts::default()
+    };
+    for err in &walked.errors {
+        error!("failed to read the card: {err}");
+    }
+    for path in &walked.others {
+        debug!("skipped '{}', not a JPEG image", path.display());
+    }
+
+    for file in &files {
+        match import(file, to) {
+            Ok((dst, clean)) => {
+                debug!("imported '{}' -> '{}'", file.display(), dst.display());
+                counts.imported += 1;
+                counts.already_clean += clean as usize;
+            }
+            Err(err) => {
+                error!("failed to import '{}': {err:#}", file.display());
+                counts.failed += 1;
+            }
+        }
+    }
+
+    counts
+}
+
+/// Clean `src` into the archive; returns where it went and whether it was
+/// already clean.
+fn import(src: &Path, to: &Path) -> anyhow::Result<(PathBuf, bool)> {
+    let data = fs::read(src)
+        .with_context(|| format!("failed to read '{}'", src.display()))?;
+
+    let info =
+        jpeg::scan_headers(&mut Cursor::new(&data)).with_context(|| {
+            format!("invalid JPEG headers in '{}'", src.display())
+        })?;
+    let date = info.exif.and_then(capture_date);
+
+    let (cleaned, already_clean) =
+        match crate::clean_img(src, &data, Format::Jpeg, &Keep::default())? {
+            Some(cleaned) => (cleaned, false),
+            None => (data, true),
+        };
+
+    let dir = match &date {
+        Some((year, day)) => to.join(year).join(day),
+        None => to.join(UNDATED),
+    };
+    fs::create_dir_all(&dir).with_context(|| {
+        format!("failed to create dir '{}'", dir.display())
+    })?;
+
+    let name = src.file_name().unwrap_or_default();
+    let Some(dst) = free_path(&dir, Path::new(name), &cleaned)? else {
+        // the same image was imported before
+        return Ok((dir.join(name), already_clean));
+    };
+
+    fs::write(&dst, &cleaned)
+        .with_context(|| format!("failed to write '{}'", dst.display()))?;
+
+    let written = fs::read(&dst)
+        .with_context(|| format!("failed to read back '{}'", dst.display()))?;
+    if written != cleaned {
+        bail!("verification of '{}' failed", dst.display());
+    }
+
+    Ok((dst, already_clean))
+}
+
+/// First name in `dir` based on `name` that is not taken by another file.
+///
+/// Cards restart their numbering, so `IMG_0001.JPG` from two cards becomes
+/// `IMG_0001.JPG` and `IMG_0001-1.JPG`. `None` when a file with the same
+/// contents already exists.
+fn free_path(
+    dir: &Path,
+    name: &Path,
+    data: &[u8],
+) -> anyhow::Result<Option<PathBuf>> {
+    let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = name.extension().map(|e| format!(".{}", e.to_string_lossy()));
+
+    for n in 0.. {
+        let candidate = match n {
+            0 => dir.join(name),
+            n => {
+                dir.join(format!("{stem}-{n}{}", ext.as_deref().unwrap_or("")))
+            }
+        };
+        if !candidate.exists() {
+            return Ok(Some(candidate));
+        }
+
+        let existing = fs::read(&candidate).with_context(|| {
+            format!("failed to read '{}'", candidate.display())
+        })?;
+        if existing == data {
+            return Ok(None);
+        }
+    }
+
+    unreachable!("ran out of file names")
+}
+
+/// `("YYYY", "YYYY-MM-DD")` from the EXIF capture date.
+fn capture_date(tiff: Vec<u8>) -> Option<(String, String)> {
+    let exif = Reader::new().read_raw(tiff).ok()?;
+    let field = [Tag::DateTimeOriginal, Tag::DateTimeDigitized, Tag::DateTime]
+        .into_iter()
+        .find_map(|tag| exif.get_field(tag, In::PRIMARY))?;
+
+    let Value::Ascii(values) = &field.value else {
+        return None;
+    };
+    let date = exif::DateTime::from_ascii(values.first()?).ok()?;
+    if date.year == 0 || date.month == 0 || date.day == 0 {
+        return None;
+    }
+
+    Some((
+        format!("{:04}", date.year),
+        format!("{:04}-{:02}-{:02}", date.year, date.month, date.day),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    const GPS: &[u8] = include_bytes!("../tests/fixtures/gps.jpg");
+    const PLAIN: &[u8] = include_bytes!("../tests/fixtures/plain.jpg");
+
+    /// An empty directory of its own for the test `name`.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("imgst-import-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A big-endian TIFF block with `date` as its DateTime.
+    fn dated_tiff(date: &[u8; 20]) -> Vec<u8> {
+        let mut tiff = b"MM\0\x2a\0\0\0\x08\0\x01".to_vec();
+        tiff.extend_from_slice(&[0x01, 0x32, 0, 2, 0, 0, 0, 20, 0, 0, 0, 26]);
+        tiff.extend_from_slice(&[0; 4]);
+        tiff.extend_from_slice(date);
+        tiff
+    }
+
+    #[test]
+    fn capture_date_files_by_day() {
+        assert_eq!(
+            capture_date(dated_tiff(b"2023:02:28 23:59:59\0")),
+            Some(("2023".into(), "2023-02-28".into()))
+        );
+        assert_eq!(capture_date(dated_tiff(b"0000:00:00 00:00:00\0")), None);
+        assert_eq!(capture_date(dated_tiff(b"    :  :     :  :  \0")), None);
+        assert_eq!(
+            capture_date(b"MM\0\x2a\0\0\0\x08\0\0\0\0\0\0".to_vec()),
+            None
+        );
+        assert_eq!(capture_date(b"garbage".to_vec()), None);
+
+        let tiff = jpeg::scan_headers(&mut Cursor::new(GPS)).unwrap().exif;
+        assert_eq!(
+            tiff.and_then(capture_date),
+            Some(("2024".into(), "2024-07-15".into()))
+        );
+    }
+
+    #[test]
+    fn free_path_numbers_other_images_and_finds_copies() {
+        let dir = temp_dir("free-path");
+        let name = Path::new("IMG_0001.JPG");
+
+        assert_eq!(free_path(&dir, name, b"a").unwrap(), Some(dir.join(name)));
+        fs::write(dir.join(name), b"a").unwrap();
+        assert_eq!(free_path(&dir, name, b"a").unwrap(), None);
+        let second = dir.join("IMG_0001-1.JPG");
+        assert_eq!(free_path(&dir, name, b"b").unwrap(), Some(second.clone()));
+        fs::write(&second, b"b").unwrap();
+        assert_eq!(free_path(&dir, name, b"b").unwrap(), None);
+        assert_eq!(
+            free_path(&dir, name, b"c").unwrap(),
+            Some(dir.join("IMG_0001-2.JPG"))
+        );
+
+        let bare = Path::new("README");
+        fs::write(dir.join(bare), b"a").unwrap();
+        assert_eq!(
+            free_path(&dir, bare, b"b").unwrap(),
+            Some(dir.join("README-1"))
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn imports_jpegs_and_counts_the_rest() {
+        let card = temp_dir("card");
+        let archive = card.join("archive");
+        let dcim = card.join("DCIM/100CANON");
+        fs::create_dir_all(&dcim).unwrap();
+        fs::write(dcim.join("IMG_0001.JPG"), GPS).unwrap();
+        fs::write(dcim.join("IMG_0002.jpg"), PLAIN).unwrap();
+        fs::write(dcim.join("IMG_0003.CR2"), b"raw").unwrap();
+        fs::write(dcim.join("IMG_0004.MOV"), b"video").unwrap();
+
+        let counts =
+            Counts { imported: 2, already_clean: 1, skipped: 2, failed: 0 };
+        assert_eq!(import_all(&card.join("DCIM"), &archive), counts);
+
+        let dated = archive.join("2024/2024-07-15/IMG_0001.JPG");
+        let cleaned = jpeg::strip_metadata(GPS, &Keep::default()).unwrap();
+        assert_eq!(fs::read(dated).unwrap(), cleaned);
+        let undated = archive.join(UNDATED).join("IMG_0002.jpg");
+        assert_eq!(fs::read(undated).unwrap(), PLAIN);
+
+        // a second import of the same card writes nothing new
+        assert_eq!(import_all(&card.join("DCIM"), &archive), counts);
+        assert_eq!(fs::read_dir(archive.join(UNDATED)).unwrap().count(), 1);
+
+        // an archive path that is a file fails every image
+        let file = card.join("file");
+        fs::write(&file, b"").unwrap();
+        let failed = Counts { failed: 2, skipped: 2, ..Counts::default() };
+        assert_eq!(import_all(&card.join("DCIM"), &file), failed);
+
+        // root reads directories whatever their mode
+        if unsafe { libc::geteuid() } != 0 {
+            let locked = card.join("DCIM/101CANON");
+            fs::create_dir(&locked).unwrap();
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o000))
+                .unwrap();
+            let counts = Counts { failed: 1, ..counts };
+            assert_eq!(import_all(&card.join("DCIM"), &archive), counts);
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o755))
+                .unwrap();
+        }
+
+        fs::remove_dir_all(&card).unwrap();
+    }
+}
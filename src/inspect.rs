@@ -0,0 +1,387 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! `imgst inspect`: print the EXIF metadata of images.
+//!
+//! The JSON output follows the layout of `exiftool -j -n` (and of
+//! `exiftool -j -n -G1` with `--groups`), so scripts written against
+//! exiftool can read it unchanged.
+
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use exif::{Context, Field, In, Reader, Tag, Value};
+use ignore::WalkBuilder;
+use log::error;
+
+use crate::jpeg;
+
+/// Tags whose exiftool name differs from the name in the EXIF spec.
+const EXIFTOOL_NAMES: &[(Tag, &str)] = &[
+    (Tag::ExifIFDPointer, "ExifOffset"),
+    (Tag::GPSInfoIFDPointer, "GPSInfo"),
+    (Tag::InteropIFDPointer, "InteropOffset"),
+    (Tag::InteroperabilityIndex, "InteropIndex"),
+    (Tag::JPEGInterchangeFormat, "ThumbnailOffset"),
+    (Tag::JPEGInterchangeFormatLength, "ThumbnailLength"),
+    (Tag::DateTime, "ModifyDate"),
+    (Tag::DateTimeDigitized, "CreateDate"),
+    (Tag::PhotographicSensitivity, "ISO"),
+    (Tag::ExposureBiasValue, "ExposureCompensation"),
+    (Tag::PixelXDimension, "ExifImageWidth"),
+    (Tag::PixelYDimension, "ExifImageHeight"),
+    (Tag::CameraOwnerName, "OwnerName"),
+    (Tag::BodySerialNumber, "SerialNumber"),
+    (Tag::LensSpecification, "LensInfo"),
+];
+
+/// One extracted tag, ready to be printed.
+struct Entry {
+    group: &'static str,
+    name: String,
+    value: JsonValue,
+}
+
+enum JsonValue {
+    Number(String),
+    String(String),
+}
+
+pub fn run(paths: &[PathBuf], json: bool, groups: bool) -> anyhow::Result<()> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            for err in collect_jpegs(path, &mut files).errors {
+                error!("walk error: {err}");
+            }
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    let mut out = String::new();
+    for file in &files {
+        let entries = match read_entries(file) {
+            Ok(entries) => entries,
+            Err(err) => {
+                error!("failed to inspect '{}': {err:#}", file.display());
+                continue;
+            }
+        };
+
+        if json {
+            out.push_str(if out.is_empty() { "[" } else { ",\n" });
+            write_json(&mut out, file, &entries, groups);
+        } else {
+            write_text(&mut out, file, &entries, groups);
+        }
+    }
+    if json {
+        out.push_str(if out.is_empty() { "[]\n" } else { "]\n" });
+    }
+
+    print!("{out}");
+
+    Ok(())
+}
+
+/// What [`collect_jpegs`] came across besides the JPEG images.
+#[derive(Debug, Default)]
+pub struct Walked {
+    /// Directories and files that could not be read.
+    pub errors: Vec<ignore::Error>,
+    /// Files that are not JPEG images, left out.
+    pub others: Vec<PathBuf>,
+}
+
+pub fn collect_jpegs(dir: &Path, files: &mut Vec<PathBuf>) -> Walked {
+    let walk = WalkBuilder::new(dir).hidden(false).follow_links(false).build();
+    let mut walked = Walked::default();
+
+    for entry in walk {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                walked.errors.push(err);
+                continue;
+            }
+        };
+        if !entry.file_type().is_some_and(|ft| ft.is_file()) {
+            continue;
+        }
+
+        let ext = entry
+            .path()
+            .extension()
+            .and_then(|s| s.to_str())
+            .map(|s| s.to_ascii_lowercase());
+        if matches!(ext.as_deref(), Some("jpg" | "jpeg")) {
+            files.push(entry.into_path());
+        } else {
+            walked.others.push(entry.into_path());
+        }
+    }
+
+    files.sort();
+    walked
+}
+
+fn read_entries(path: &Path) -> anyhow::Result<Vec<Entry>> {
+    let file = File::open(path)
+        .with_context(|| format!("failed to open '{}'", path.display()))?;
+
+    let info = jpeg::scan_headers(&mut BufReader::new(file))
+        .context("invalid JPEG headers")?;
+
+    let Some(tiff) = info.exif else {
+        return Ok(Vec::new());
+    };
+
+    let exif = Reader::new().read_raw(tiff).context("invalid EXIF data")?;
+
+    Ok(exif.fields().filter_map(entry).collect())
+}
+
+fn entry(field: &Field) -> Option<Entry> {
+    // exiftool has its own names for tags we do not know about
+    field.tag.description()?;
+
+    let name = EXIFTOOL_NAMES
+        .iter()
+        .find(|(tag, _)| *tag == field.tag)
+        .map_or_else(|| field.tag.to_string(), |(_, name)| name.to_string());
+
+    let group = match field.tag.context() {
+        Context::Gps => "GPS",
+        Context::Interop => "InteropIFD",
+        Context::Exif => "ExifIFD",
+        _ if field.ifd_num == In::THUMBNAIL => "IFD1",
+        _ => "IFD0",
+    };
+
+    let value = match field.tag {
+        Tag::GPSLatitude | Tag::GPSLongitude => degrees(&field.value),
+        _ => None,
+    };
+    let value = value.or_else(|| convert(&field.value))?;
+
+    Some(Entry { group, name, value })
+}
+
+/// Convert a degrees/minutes/seconds triplet to decimal degrees.
+fn degrees(value: &Value) -> Option<JsonValue> {
+    let Value::Rational(dms) = value else {
+        return None;
+    };
+    let [d, m, s] = dms.as_slice() else {
+        return None;
+    };
+
+    let deg = d.to_f64() + m.to_f64() / 60.0 + s.to_f64() / 3600.0;
+    Some(number(deg))
+}
+
+fn convert(value: &Value) -> Option<JsonValue> {
+    fn list<T: ToString>(values: &[T]) -> JsonValue {
+        match values {
+            [v] => JsonValue::Number(v.to_string()),
+            _ => JsonValue::String(join(values.iter().map(T::to_string))),
+        }
+    }
+
+    fn floats(values: impl ExactSizeIterator<Item = f64>) -> JsonValue {
+        if values.len() == 1 {
+            number(values.into_iter().next().unwrap_or(f64::NAN))
+        } else {
+            JsonValue::String(join(values.map(|v| v.to_string())))
+        }
+    }
+
+    Some(match value {
+        Value::Byte(v) => list(v),
+        Value::Short(v) => list(v),
+        Value::Long(v) => list(v),
+        Value::SByte(v) => list(v),
+        Value::SShort(v) => list(v),
+        Value::SLong(v) => list(v),
+        Value::Rational(v) => floats(v.iter().map(|r| r.to_f64())),
+        Value::SRational(v) => floats(v.iter().map(|r| r.to_f64())),
+        Value::Float(v) => floats(v.iter().map(|&f| f as f64)),
+        Value::Double(v) => floats(v.iter().copied()),
+        Value::Ascii(v) => JsonValue::String(join(
+            v.iter()
+                .map(|s| String::from_utf8_lossy(s).trim().to_string())
+                .filter(|s| !s.is_empty()),
+        )),
+        Value::Undefined(bytes, _) => {
+            let printable =
+                bytes.iter().all(|&b| b.is_ascii_graphic() || b == b' ');
+            JsonValue::String(if printable && !bytes.is_empty() {
+                String::from_utf8_lossy(bytes).into_owned()
+            } else {
+                format!(
+                    "(Binary data {} bytes, use -b option to extract)",
+                    bytes.len()
+                )
+            })
+        }
+        Value::Unknown(..) => return None,
+    })
+}
+
+fn number(value: f64) -> JsonValue {
+    if value.is_finite() {
+        JsonValue::Number(value.to_string())
+    } else {
+        JsonValue::String("undef".to_string())
+    }
+}
+
+fn join(values: impl Iterator<Item = String>) -> String {
+    values.collect::<Vec<_>>().join(" ")
+}
+
+fn write_json(out: &mut String, file: &Path, entries: &[Entry], groups: bool) {
+    let mut seen = Vec::new();
+
+    out.push_str("{\n  \"SourceFile\": ");
+    write_json_string(out, &file.to_string_lossy());
+
+    for entry in entries {
+        let key = if groups {
+            format!("{}:{}", entry.group, entry.name)
+        } else {
+            entry.name.clone()
+        };
+
+        // like exiftool, the first (main image) occurrence wins
+        if seen.contains(&key) {
+            continue;
+        }
+
+        out.push_str(",\n  ");
+        write_json_string(out, &key);
+        out.push_str(": ");
+        match &entry.value {
+            JsonValue::Number(n) => out.push_str(n),
+            JsonValue::String(s) => write_json_string(out, s),
+        }
+        seen.push(key);
+    }
+
+    out.push_str("\n}");
+}
+
+pub fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_text(out: &mut String, file: &Path, entries: &[Entry], groups: bool) {
+    let _ = writeln!(out, "======== {}", file.display());
+
+    for entry in entries {
+        let value = match &entry.value {
+            JsonValue::Number(s) | JsonValue::String(s) => s,
+        };
+
+        if groups {
+            let group = format!("[{}]", entry.group);
+            let _ = writeln!(out, "{group:<16}{:<32}: {value}", entry.name);
+        } else {
+            let _ = writeln!(out, "{:<32}: {value}", entry.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GPS: &str =
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/gps.jpg");
+
+    fn json(file: &Path, entries: &[Entry], groups: bool) -> String {
+        let mut out = String::new();
+        write_json(&mut out, file, entries, groups);
+        out
+    }
+
+    #[test]
+    fn json_follows_exiftool() {
+        let entries = read_entries(Path::new(GPS)).unwrap();
+        let out = json(Path::new("gps.jpg"), &entries, false);
+
+        assert_eq!(
+            out,
+            "{\n  \"SourceFile\": \"gps.jpg\",\n  \"Make\": \"Canon\",\n  \
+             \"Model\": \"EOS 5D\",\n  \"Orientation\": 6,\n  \
+             \"ModifyDate\": \"2024:07:15 10:20:30\",\n  \
+             \"Copyright\": \"(c) Alice\",\n  \"GPSLatitudeRef\": \"N\",\n  \
+             \"GPSLatitude\": 52.37009444444445,\n  \
+             \"GPSLongitudeRef\": \"E\",\n  \
+             \"GPSLongitude\": 13.401308333333334,\n  \"GPSAltitude\": 34\n}"
+        );
+
+        let grouped = json(Path::new("gps.jpg"), &entries, true);
+        assert!(grouped.contains("\n  \"IFD0:Make\": \"Canon\",\n"));
+        assert!(grouped.contains("\n  \"GPS:GPSAltitude\": 34\n}"));
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        let entries = [
+            Entry {
+                group: "IFD0",
+                name: "Artist".to_string(),
+                value: JsonValue::String(
+                    "\"Jane\" \\ Doe\n\tx\u{1}é".to_string(),
+                ),
+            },
+            // only the first of a repeated name is printed
+            Entry {
+                group: "IFD1",
+                name: "Artist".to_string(),
+                value: JsonValue::String("thumbnail".to_string()),
+            },
+        ];
+        let out = json(Path::new("a \"b\".jpg"), &entries, false);
+
+        assert_eq!(
+            out,
+            "{\n  \"SourceFile\": \"a \\\"b\\\".jpg\",\n  \
+             \"Artist\": \"\\\"Jane\\\" \\\\ Doe\\n\\tx\\u0001é\"\n}"
+        );
+    }
+}
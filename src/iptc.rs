@@ -0,0 +1,396 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! IPTC datasets in the Photoshop image resources of a JPEG APP13
+//! segment, for the `--strip` modes that keep the block.
+//!
+//! The payload is a list of resources, each with a signature, an id, a
+//! padded Pascal name and a padded length-prefixed body; the IPTC-IIM
+//! datasets are the body of resource 0x0404. Only that body is filtered,
+//! the other resources are copied byte for byte but for the ones that can
+//! hold what is being removed.
+
+use std::io;
+
+use crate::{
+    date::Dates,
+    keep::{Keep, Strip},
+};
+
+const IDENT: &[u8] = b"Photoshop 3.0\0";
+
+/// The IPTC-IIM datasets.
+const IPTC: u16 = 0x0404;
+/// EXIF blocks and an XMP packet, which may carry GPS data.
+const EMBEDDED: &[u16] = &[0x0422, 0x0423, 0x0424];
+/// MD5 of the IPTC datasets, stale once they change.
+const DIGEST: u16 = 0x0425;
+
+/// Application record datasets naming the place: content location code
+/// and name, city, sub-location, province or state, country code and
+/// name.
+const LOCATION: &[u8] = &[26, 27, 90, 92, 95, 100, 101];
+
+/// Date and time dataset pairs: date and time created, digital creation
+/// date and time.
+const DATES: &[(u8, u8)] = &[(55, 60), (62, 63)];
+
+/// Copy an APP13 payload without its location datasets with `--strip gps`,
+/// and with its dates shifted and rounded, or without them, when `keep`
+/// changes them; the EXIF and XMP resources go too, since they are not
+/// filtered. Otherwise it stays as it is.
+pub fn strip(payload: &[u8], keep: &Keep) -> io::Result<Vec<u8>> {
+    let gps = keep.strip == Strip::Gps;
+    if !(gps || keep.dates.is_active()) || !payload.starts_with(IDENT) {
+        return Ok(payload.to_vec());
+    }
+
+    let mut out = IDENT.to_vec();
+    let mut changed = false;
+    let mut digest = None;
+    let mut pos = IDENT.len();
+
+    while pos < payload.len() {
+        let Some(res) = Resource::parse(payload, pos)? else {
+            // padding after the last resource
+            out.extend_from_slice(&payload[pos..]);
+            break;
+        };
+        pos = res.end;
+
+        if res.id == IPTC {
+            let body = rewrite(res.body, gps, &keep.dates)?;
+            changed |= body != res.body;
+            res.write(&mut out, &body)?;
+        } else if EMBEDDED.contains(&res.id) {
+            changed = true;
+        } else if res.id == DIGEST {
+            // put back only when nothing changed, in the same place
+            digest = Some((out.len(), res.raw));
+            out.extend_from_slice(res.raw);
+        } else {
+            out.extend_from_slice(res.raw);
+        }
+    }
+
+    if changed && let Some((at, raw)) = digest {
+        out.drain(at..at + raw.len());
+    }
+    Ok(out)
+}
+
+/// Copy IPTC-IIM datasets without the location ones with `gps`, and with
+/// their dates changed as `dates` says.
+fn rewrite(data: &[u8], gps: bool, dates: &Dates) -> io::Result<Vec<u8>> {
+    let (datasets, padding) = datasets(data)?;
+    let find = |number: u8| {
+        datasets.iter().position(|ds| ds.record == 2 && ds.number == number)
+    };
+
+    let is_date =
+        |number| DATES.iter().any(|&(d, t)| number == d || number == t);
+    let dropped = |ds: &Dataset| {
+        ds.record == 2
+            && (gps && LOCATION.contains(&ds.number)
+                || dates.remove && is_date(ds.number))
+    };
+
+    let mut values: Vec<_> = datasets
+        .iter()
+        .map(|ds| (!dropped(ds)).then(|| ds.value.to_vec()))
+        .collect();
+    if dates.is_active() && !dates.remove {
+        for &(date, time) in DATES {
+            let (date, time) = (find(date), find(time));
+            let value = |at: Option<usize>| at.map(|at| datasets[at].value);
+            let (new_date, new_time) = dates.iptc(value(date), value(time));
+            for (at, new) in [(date, new_date), (time, new_time)] {
+                if let (Some(at), Some(new)) = (at, new) {
+                    values[at] = Some(new);
+                }
+            }
+        }
+    }
+
+    let mut out = Vec::with_capacity(data.len());
+    for (ds, value) in datasets.iter().zip(values) {
+        let Some(value) = value else { continue };
+        if value == ds.value {
+            out.extend_from_slice(ds.raw);
+        } else {
+            // dates are rewritten in as many bytes, the length stays
+            out.extend_from_slice(&ds.raw[..ds.raw.len() - ds.value.len()]);
+            out.extend_from_slice(&value);
+        }
+    }
+    out.extend_from_slice(padding);
+
+    Ok(out)
+}
+
+/// A dataset: tag marker, record and dataset number, length and value.
+struct Dataset<'a> {
+    record: u8,
+    number: u8,
+    value: &'a [u8],
+    raw: &'a [u8],
+}
+
+/// The IPTC-IIM datasets in `data`, and the padding after them.
+fn datasets(data: &[u8]) -> io::Result<(Vec<Dataset<'_>>, &[u8])> {
+    let mut datasets = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        if data[pos] != 0x1C {
+            if data[pos..].iter().all(|&b| b == 0) {
+                return Ok((datasets, &data[pos..]));
+            }
+            return Err(invalid("invalid IPTC dataset"));
+        }
+        let header = data
+            .get(pos..pos + 5)
+            .ok_or_else(|| invalid("truncated IPTC dataset"))?;
+        let (record, number) = (header[1], header[2]);
+        let len = u16::from_be_bytes([header[3], header[4]]);
+
+        // the extended form gives the number of length bytes instead
+        let (start, len) = if len & 0x8000 == 0 {
+            (pos + 5, len as usize)
+        } else {
+            let count = (len & 0x7FFF) as usize;
+            let bytes = data
+                .get(pos + 5..pos + 5 + count)
+                .filter(|_| count <= 4)
+                .ok_or_else(|| invalid("invalid IPTC dataset length"))?;
+            let len = bytes.iter().fold(0, |n, &b| n << 8 | b as usize);
+            (pos + 5 + count, len)
+        };
+        let end = start
+            .checked_add(len)
+            .filter(|&end| end <= data.len())
+            .ok_or_else(|| invalid("truncated IPTC dataset"))?;
+
+        datasets.push(Dataset {
+            record,
+            number,
+            value: &data[start..end],
+            raw: &data[pos..end],
+        });
+        pos = end;
+    }
+
+    Ok((datasets, &[]))
+}
+
+/// An image resource block.
+struct Resource<'a> {
+    id: u16,
+    /// Signature, id and name, up to the length.
+    head: &'a [u8],
+    body: &'a [u8],
+    raw: &'a [u8],
+    end: usize,
+}
+
+impl<'a> Resource<'a> {
+    /// The resource at `pos`, or `None` when only padding is left.
+    fn parse(data: &'a [u8], pos: usize) -> io::Result<Option<Self>> {
+        let truncated = || invalid("truncated image resource");
+        let rest = &data[pos..];
+        if rest.len() < 12 && rest.iter().all(|&b| b == 0) {
+            return Ok(None);
+        }
+        let id = rest.get(4..6).ok_or_else(truncated)?;
+        let id = u16::from_be_bytes([id[0], id[1]]);
+        let name_len = *rest.get(6).ok_or_else(truncated)? as usize;
+        // the name with its length byte is padded to an even size
+        let head_len = 6 + (1 + name_len).next_multiple_of(2);
+        let size = rest.get(head_len..head_len + 4).ok_or_else(truncated)?;
+        let size = u32::from_be_bytes(size.try_into().unwrap()) as usize;
+
+        let body_start = head_len + 4;
+        let body = body_start
+            .checked_add(size)
+            .and_then(|end| rest.get(body_start..end))
+            .ok_or_else(truncated)?;
+        let len = (body_start + size).next_multiple_of(2).min(rest.len());
+
+        Ok(Some(Resource {
+            id,
+            head: &rest[..head_len],
+            body,
+            raw: &rest[..len],
+            end: pos + len,
+        }))
+    }
+
+    /// Append the resource with `body` in place of its own.
+    fn write(&self, out: &mut Vec<u8>, body: &[u8]) -> io::Result<()> {
+        let size = u32::try_from(body.len())
+            .map_err(|_| invalid("image resource too large"))?;
+        out.extend_from_slice(self.head);
+        out.extend_from_slice(&size.to_be_bytes());
+        out.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            out.push(0);
+        }
+        Ok(())
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date::Round;
+
+    fn dataset(record: u8, dataset: u8, value: &[u8]) -> Vec<u8> {
+        let mut out = vec![0x1C, record, dataset];
+        out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        out.extend_from_slice(value);
+        out
+    }
+
+    fn resource(id: u16, body: &[u8]) -> Vec<u8> {
+        let mut out = b"8BIM".to_vec();
+        out.extend_from_slice(&id.to_be_bytes());
+        out.extend_from_slice(&[0, 0]);
+        out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        out.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            out.push(0);
+        }
+        out
+    }
+
+    fn payload(resources: &[Vec<u8>]) -> Vec<u8> {
+        [IDENT.to_vec(), resources.concat()].concat()
+    }
+
+    fn gps() -> Keep {
+        Keep { strip: Strip::Gps, ..Keep::default() }
+    }
+
+    #[test]
+    fn gps_mode_drops_the_place_names() {
+        let title = dataset(2, 5, b"Holiday");
+        let credit = dataset(2, 110, b"Alice");
+        let places = [
+            dataset(2, 90, b"Berlin"),
+            dataset(2, 92, b"Mitte"),
+            dataset(2, 95, b"BE"),
+            dataset(2, 100, b"DEU"),
+            dataset(2, 101, b"Germany"),
+        ];
+        let iptc = [&title[..], &places.concat(), &credit].concat();
+        let other = resource(0x03ED, b"resolution");
+        let data = payload(&[
+            resource(IPTC, &iptc),
+            other.clone(),
+            resource(0x0424, b"<x:xmpmeta/>"),
+            resource(DIGEST, &[7; 16]),
+        ]);
+
+        let kept = [&title[..], &credit].concat();
+        assert_eq!(
+            strip(&data, &gps()).unwrap(),
+            payload(&[resource(IPTC, &kept), other])
+        );
+        for strip_mode in [Strip::Makernotes, Strip::People] {
+            let keep = Keep { strip: strip_mode, ..Keep::default() };
+            assert_eq!(strip(&data, &keep).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn dates_are_shifted_together_or_removed() {
+        let title = dataset(2, 5, b"Holiday");
+        let data = |date: &[u8], time: &[u8], digital: &[u8]| {
+            let iptc = [
+                title.clone(),
+                dataset(2, 55, date),
+                dataset(2, 60, time),
+                dataset(2, 62, digital),
+            ];
+            payload(&[resource(IPTC, &iptc.concat())])
+        };
+        let original = data(b"20241231", b"233000+0100", b"20241231");
+        let with = |dates| Keep { dates, ..Keep::default() };
+
+        let later = Dates { offset: 2 * 60 * 60, ..Dates::default() };
+        assert_eq!(
+            strip(&original, &with(later)).unwrap(),
+            data(b"20250101", b"013000+0100", b"20241231")
+        );
+        let monthly = Dates { round: Some(Round::Month), ..Dates::default() };
+        assert_eq!(
+            strip(&original, &with(monthly)).unwrap(),
+            data(b"20241201", b"000000+0100", b"20241201")
+        );
+        let removed = Dates { remove: true, ..Dates::default() };
+        assert_eq!(
+            strip(&original, &with(removed)).unwrap(),
+            payload(&[resource(IPTC, &title)])
+        );
+
+        // not dates, not changed
+        let odd = data(b"2024", b"noon", b"20241331");
+        assert_eq!(strip(&odd, &with(later)).unwrap(), odd);
+    }
+
+    #[test]
+    fn untouched_blocks_keep_their_digest() {
+        let iptc = dataset(2, 5, b"Holiday");
+        let data =
+            payload(&[resource(IPTC, &iptc), resource(DIGEST, &[7; 16])]);
+        assert_eq!(strip(&data, &gps()).unwrap(), data);
+
+        // not a Photoshop block
+        assert_eq!(
+            strip(b"Adobe_CM\0\x01", &gps()).unwrap(),
+            b"Adobe_CM\0\x01"
+        );
+    }
+
+    #[test]
+    fn reads_extended_lengths_and_padding() {
+        let city = [&[0x1C, 2, 90, 0x80, 2, 0, 6][..], b"Berlin"].concat();
+        let iptc = [&city[..], &dataset(2, 5, b"Hi"), &[0, 0]].concat();
+        let data = [payload(&[resource(IPTC, &iptc)]), vec![0, 0]].concat();
+
+        let kept = [dataset(2, 5, b"Hi"), vec![0, 0]].concat();
+        let expected = [payload(&[resource(IPTC, &kept)]), vec![0, 0]];
+        assert_eq!(strip(&data, &gps()).unwrap(), expected.concat());
+    }
+
+    #[test]
+    fn rejects_broken_blocks() {
+        let truncated = [&dataset(2, 90, b"Berlin")[..8]].concat();
+        let broken = [
+            payload(&[resource(IPTC, &truncated)]),
+            payload(&[resource(IPTC, b"\x1c\x02\x5a\x80\x09xx")]),
+            payload(&[resource(IPTC, b"junk")]),
+            payload(&[resource(0x03ED, b"resolution")[..14].to_vec()]),
+        ];
+        for data in broken {
+            assert!(strip(&data, &gps()).is_err(), "{data:?}");
+        }
+    }
+}
@@ -0,0 +1,1268 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! JPEG marker stream helpers.
+//!
+//! Cleaning works on the marker segments before the first scan: metadata
+//! segments are dropped and everything from the first scan on, i.e. the
+//! entropy-coded data, is copied as is, so the pixels are never re-encoded.
+//!
+//! Whatever follows the EOI marker goes too, unless `--keep-trailer` asks
+//! otherwise: phones append motion photo videos there, and other tools
+//! hide whole files.
+
+use std::{
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    bmff::read_box_header,
+    format::HeaderInfo,
+    iptc,
+    keep::{self, Keep, Strip},
+    tiff,
+    xml::{self, Rules},
+};
+
+pub const SOI: u8 = 0xD8;
+pub const EOI: u8 = 0xD9;
+pub const SOS: u8 = 0xDA;
+pub const APP0: u8 = 0xE0;
+pub const APP1: u8 = 0xE1;
+pub const APP2: u8 = 0xE2;
+pub const APP13: u8 = 0xED;
+pub const APP14: u8 = 0xEE;
+pub const COM: u8 = 0xFE;
+
+/// Identifier of the APP1 segment holding EXIF data.
+pub const EXIF_IDENT: &[u8] = b"Exif\0\0";
+/// Identifier of the APP1 segment holding the XMP packet.
+const XMP_IDENT: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/// Identifier of the APP1 segments holding chunks of an extended XMP
+/// packet, one too large for a single segment.
+const XMP_EXTENSION: &[u8] = b"http://ns.adobe.com/xmp/extension/\0";
+/// Property of the main packet naming the extended packet by its digest.
+const EXTENSION_NOTE: &[u8] = b"xmpNote:HasExtendedXMP";
+/// What Samsung puts between the image and the video of a motion photo.
+const MOTION_PHOTO_DATA: &[u8] = b"MotionPhoto_Data";
+
+/// Identifier of the APP0 segment kept, the JFIF header; the others are
+/// JFXX thumbnails and the like.
+const JFIF_IDENT: &[u8] = b"JFIF\0";
+
+/// Longest segment identifier we look at (`ICC_PROFILE\0`).
+const IDENT_LEN: usize = 12;
+
+/// Whether a segment carries metadata that cleaning removes.
+///
+/// `ident` is the start of the segment payload, used to tell the JFIF
+/// header, ICC profiles and Adobe colour information (which are kept) from
+/// other APPn data.
+pub fn is_metadata(marker: u8, ident: &[u8]) -> bool {
+    match marker {
+        APP0 => !ident.starts_with(JFIF_IDENT),
+        APP2 => !is_icc(marker, ident),
+        APP14 => !ident.starts_with(b"Adobe"),
+        0xE1..=0xEF | COM => true,
+        _ => false,
+    }
+}
+
+/// Walk the marker segments up to the first scan, then look through the
+/// scans for the end of the image; data after it counts as metadata.
+pub fn scan_headers<R: Read + Seek>(r: &mut R) -> io::Result<HeaderInfo> {
+    let mut buf = [0u8; 2];
+    r.read_exact(&mut buf)?;
+    if buf != [0xFF, SOI] {
+        return Err(invalid("missing SOI marker"));
+    }
+
+    let mut info = HeaderInfo::default();
+
+    loop {
+        let marker = read_marker(r)?;
+
+        match marker {
+            SOS => {
+                info.trailer_bytes = scan_trailer(r)?;
+                info.metadata_bytes += info.trailer_bytes;
+                break;
+            }
+            EOI => break,
+            // standalone markers carry no length
+            0x01 | 0xD0..=0xD7 => continue,
+            _ => {}
+        }
+
+        r.read_exact(&mut buf)?;
+        let len = u16::from_be_bytes(buf) as usize;
+        if len < 2 {
+            return Err(invalid("invalid segment length"));
+        }
+
+        let payload = len - 2;
+        let mut ident_buf = [0u8; IDENT_LEN];
+        let ident_len = payload.min(IDENT_LEN);
+        r.read_exact(&mut ident_buf[..ident_len])?;
+        let ident = &ident_buf[..ident_len];
+
+        if marker == APP1
+            && info.exif.is_none()
+            && ident.starts_with(EXIF_IDENT)
+        {
+            let mut exif = ident[EXIF_IDENT.len()..].to_vec();
+            exif.resize(payload - EXIF_IDENT.len(), 0);
+            r.read_exact(&mut exif[ident_len - EXIF_IDENT.len()..])?;
+            info.exif = Some(exif);
+        } else {
+            r.seek(SeekFrom::Current((payload - ident_len) as i64))?;
+        }
+
+        info.segments += 1;
+        if is_metadata(marker, ident) {
+            info.metadata_bytes += len as u64 + 2;
+        }
+    }
+
+    Ok(info)
+}
+
+/// Read the scans, right after the SOS marker, up to the end of the image
+/// and return how many bytes follow it; none when it never ends, as in a
+/// truncated file.
+fn scan_trailer<R: Read + Seek>(r: &mut R) -> io::Result<u64> {
+    let mut image = ImageEnd::default();
+    image.feed(&[0xFF, SOS]);
+
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let fed = image.pos;
+        let n = r.read(&mut buf)?;
+        if n == 0 {
+            return Ok(0);
+        }
+        if let Some(end) = image.feed(&buf[..n]) {
+            let pos = r.stream_position()?;
+            let len = r.seek(SeekFrom::End(0))?;
+            return Ok(len - pos + (fed + n as u64 - end));
+        }
+    }
+}
+
+/// Length of `rest`, from the first scan on, up to the end of the image;
+/// all of it when there is no EOI marker.
+fn image_len(rest: &[u8]) -> usize {
+    ImageEnd::default().feed(rest).map_or(rest.len(), |end| end as usize)
+}
+
+/// The MP4 video Google and Samsung motion photos append after the image,
+/// for `--extract-motion-video`. Its boxes are walked to find where it
+/// ends, since Samsung puts more data after it.
+pub fn motion_video(data: &[u8]) -> Option<&[u8]> {
+    let (_, rest) = segments(data).ok()?;
+    let trailer = &rest[image_len(rest)..];
+    let video = trailer.strip_prefix(MOTION_PHOTO_DATA).unwrap_or(trailer);
+    if video.get(4..8) != Some(b"ftyp") {
+        return None;
+    }
+
+    let mut pos = 0;
+    while let Ok(Some(header)) =
+        read_box_header(&mut Cursor::new(&video[pos..]))
+    {
+        let Some(size) = header.size else {
+            return Some(video);
+        };
+        match usize::try_from(size).ok().and_then(|size| pos.checked_add(size))
+        {
+            Some(end) if size >= header.header && end <= video.len() => {
+                pos = end
+            }
+            _ => break,
+        }
+    }
+    (pos > 0).then(|| &video[..pos])
+}
+
+/// Where `--extract-motion-video` writes the video of the image at `path`:
+/// `photo.jpg` gets `photo.motion.mp4`.
+pub fn motion_video_path(path: &Path) -> PathBuf {
+    path.with_extension("motion.mp4")
+}
+
+/// The images a video at `path` may have come from, when it is named like
+/// [`motion_video_path`] names them.
+pub fn motion_video_sources(path: &Path) -> Vec<PathBuf> {
+    let name = path.file_name().and_then(|name| name.to_str());
+    let Some(stem) = name.and_then(|name| name.strip_suffix(".motion.mp4"))
+    else {
+        return Vec::new();
+    };
+    ["jpg", "jpeg", "JPG", "JPEG"]
+        .iter()
+        .map(|ext| path.with_file_name(format!("{stem}.{ext}")))
+        .collect()
+}
+
+/// Finds the EOI marker in the scans a chunk at a time: entropy-coded data
+/// runs up to the next marker, which is the end of the image, a restart
+/// marker, or a segment such as the next scan of a progressive JPEG.
+#[derive(Default)]
+struct ImageEnd {
+    state: EndState,
+    /// Bytes fed so far.
+    pos: u64,
+}
+
+#[derive(Default, Clone, Copy)]
+enum EndState {
+    #[default]
+    Data,
+    /// After a 0xFF.
+    Marker,
+    /// Reading the length of a segment, the high byte once read.
+    Length(Option<u8>),
+    /// Bytes of a segment left to skip.
+    Skip(usize),
+}
+
+impl ImageEnd {
+    /// Look through the next chunk; the offset right after the EOI marker,
+    /// counted from the first byte fed, once it is in it.
+    fn feed(&mut self, buf: &[u8]) -> Option<u64> {
+        let mut i = 0;
+        while i < buf.len() {
+            match self.state {
+                EndState::Data => {
+                    match buf[i..].iter().position(|&b| b == 0xFF) {
+                        Some(at) => {
+                            i += at + 1;
+                            self.state = EndState::Marker;
+                        }
+                        None => i = buf.len(),
+                    }
+                }
+                EndState::Marker => {
+                    let byte = buf[i];
+                    i += 1;
+                    self.state = match byte {
+                        0xFF => EndState::Marker,
+                        EOI => return Some(self.pos + i as u64),
+                        // stuffed zero, TEM and restart markers
+                        0x00 | 0x01 | 0xD0..=0xD7 => EndState::Data,
+                        _ => EndState::Length(None),
+                    };
+                }
+                EndState::Length(high) => {
+                    let byte = buf[i];
+                    i += 1;
+                    self.state = match high {
+                        None => EndState::Length(Some(byte)),
+                        Some(high) => {
+                            let len = u16::from_be_bytes([high, byte]);
+                            EndState::Skip((len as usize).saturating_sub(2))
+                        }
+                    };
+                }
+                EndState::Skip(left) => {
+                    let step = left.min(buf.len() - i);
+                    i += step;
+                    self.state = match left - step {
+                        0 => EndState::Data,
+                        left => EndState::Skip(left),
+                    };
+                }
+            }
+        }
+        self.pos += buf.len() as u64;
+        None
+    }
+}
+
+/// Copy `data` without its metadata segments.
+///
+/// The EXIF fields in `keep` (by default a non-default orientation, since
+/// viewers would show the image rotated otherwise) go into a minimal EXIF
+/// segment, and the ICC profile is kept unless `keep` leaves it out. With
+/// `--strip gps` or `makernotes` only those go, see [`strip_in_place`].
+/// Cleaning an already clean image returns it unchanged.
+pub fn strip_metadata(data: &[u8], keep: &Keep) -> io::Result<Vec<u8>> {
+    let (segments, rest) = segments(data)?;
+    let rest = if keep.trailer { rest } else { &rest[..image_len(rest)] };
+    if keep.strip != Strip::All {
+        return strip_in_place(&segments, rest, keep);
+    }
+
+    let mut kept: Vec<&[u8]> = Vec::new();
+    let mut exif = None;
+
+    for seg in &segments {
+        let ident = seg.ident();
+        if !is_metadata(seg.marker, ident)
+            && (keep.icc || !is_icc(seg.marker, ident))
+        {
+            kept.push(seg.raw);
+        } else if seg.marker == APP1
+            && exif.is_none()
+            && let Some(tiff) = seg.payload.strip_prefix(EXIF_IDENT)
+        {
+            exif = keep.exif_block(tiff);
+        }
+    }
+
+    // a file without EXIF, or with a broken one, still gets --set
+    let exif = exif.or_else(|| keep.exif_block(tiff::EMPTY));
+
+    // the kept EXIF goes right after the JFIF header, if there is one
+    let jfif = kept.first().is_some_and(|seg| seg.get(1) == Some(&APP0));
+    let (head, tail) = kept.split_at(jfif as usize);
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&[0xFF, SOI]);
+    head.iter().for_each(|seg| out.extend_from_slice(seg));
+    if let Some(exif) = exif {
+        out.extend_from_slice(&exif_segment(&exif)?);
+    }
+    if let Some(packet) = keep.set.xmp() {
+        out.extend_from_slice(&xmp_segment(&packet)?);
+    }
+    tail.iter().for_each(|seg| out.extend_from_slice(seg));
+    out.extend_from_slice(rest);
+
+    Ok(out)
+}
+
+/// Copy every segment, editing the EXIF block in place: `--strip gps`
+/// blanks its GPS IFD, or coarsens it with `--gps-fuzz`, and takes the GPS
+/// and place properties out of the XMP packet and the place names out of
+/// the IPTC block; `--strip makernotes` drops
+/// the maker note; `--strip people` takes the face regions, the people
+/// shown and the keywords out of the XMP packet; `--redact` does all three
+/// and replaces names and serial numbers. Both also drop the IPTC block
+/// (APP13), which holds the by-line, place names and keywords, and the
+/// comments. The dates of the EXIF block, the XMP packet and the IPTC block
+/// are changed when asked to, and the ICC profile goes when `keep` leaves
+/// it out. Thumbnails go in every mode, the EXIF one and the APP0 ones
+/// next to the JFIF header. Everything else stays as it is.
+///
+/// Extended XMP is the exception: its chunks can only be filtered once put
+/// back together, and it mostly holds depth maps and embedded images, so
+/// all of them go along with the note pointing at them. Leaving a chunk
+/// behind would keep part of the packet and its GPS properties.
+fn strip_in_place(
+    segments: &[Segment],
+    rest: &[u8],
+    keep: &Keep,
+) -> io::Result<Vec<u8>> {
+    let mut out = vec![0xFF, SOI];
+
+    let identities = matches!(keep.strip, Strip::People | Strip::Redact);
+
+    for seg in segments {
+        if identities && matches!(seg.marker, APP13 | COM)
+            || seg.marker == APP0 && is_metadata(APP0, seg.ident())
+            || !keep.icc && is_icc(seg.marker, seg.ident())
+        {
+            // dropped, see above
+        } else if seg.marker == APP13 {
+            let payload = iptc::strip(seg.payload, keep)?;
+            out.extend_from_slice(&segment(APP13, &payload)?);
+        } else if seg.marker != APP1 {
+            out.extend_from_slice(seg.raw);
+        } else if seg.payload.starts_with(EXIF_IDENT) {
+            let at = out.len() + seg.raw.len() - seg.payload.len();
+            out.extend_from_slice(seg.raw);
+            let tiff = &mut out[at + EXIF_IDENT.len()..];
+            let len = tiff::strip_thumbnail(tiff)?;
+            match keep.strip {
+                Strip::Gps => tiff::strip_gps(tiff, keep.gps_fuzz)?,
+                Strip::Makernotes => tiff::strip_maker_note(tiff)?,
+                Strip::People => {}
+                Strip::Redact => {
+                    tiff::strip_gps(tiff, keep.gps_fuzz)?;
+                    tiff::strip_maker_note(tiff)?;
+                    tiff::redact(tiff)?;
+                }
+                Strip::All => unreachable!("rebuilt by strip_metadata"),
+            }
+            tiff::rewrite_dates(tiff, &keep.dates)?;
+            // the thumbnail usually comes last and can be cut off
+            out.truncate(at + EXIF_IDENT.len() + len);
+            let seg_len = (out.len() - at + 2) as u16;
+            out[at - 2..at].copy_from_slice(&seg_len.to_be_bytes());
+        } else if seg.payload.starts_with(XMP_EXTENSION) {
+            // dropped whole, see above
+        } else if let Some(packet) = seg.payload.strip_prefix(XMP_IDENT) {
+            let packet = drop_extension_note(keep::strip_xmp(packet, keep)?)?;
+            out.extend_from_slice(&xmp_segment(&packet)?);
+        } else {
+            out.extend_from_slice(seg.raw);
+        }
+    }
+    out.extend_from_slice(rest);
+
+    Ok(out)
+}
+
+/// Take the note naming the extended packet out of the main one, since
+/// [`strip_in_place`] drops the extension.
+fn drop_extension_note(packet: Vec<u8>) -> io::Result<Vec<u8>> {
+    if !packet.windows(EXTENSION_NOTE.len()).any(|w| w == EXTENSION_NOTE) {
+        return Ok(packet);
+    }
+    let is_note = |name: &[u8]| name == EXTENSION_NOTE;
+    let rules =
+        Rules { element: is_note, attribute: is_note, comments: false };
+    Ok(xml::filter(&packet, &rules)?.data)
+}
+
+/// Copy `data` with `thumbnail` as its EXIF thumbnail, for
+/// `--regen-thumbnail`. The EXIF block is created right after the JFIF
+/// header when there is none.
+pub fn set_thumbnail(data: &[u8], thumbnail: &[u8]) -> io::Result<Vec<u8>> {
+    let (segments, rest) = segments(data)?;
+    let is_exif = |seg: &Segment| {
+        seg.marker == APP1 && seg.payload.starts_with(EXIF_IDENT)
+    };
+    let jfif = segments.first().is_some_and(|seg| seg.marker == APP0);
+    let (head, tail) = segments.split_at(jfif as usize);
+
+    let mut out = Vec::with_capacity(data.len() + thumbnail.len());
+    out.extend_from_slice(&[0xFF, SOI]);
+    head.iter().for_each(|seg| out.extend_from_slice(seg.raw));
+    if !segments.iter().any(is_exif) {
+        let tiff = tiff::set_thumbnail(tiff::EMPTY, thumbnail)?;
+        out.extend_from_slice(&exif_segment(&tiff)?);
+    }
+    for seg in tail {
+        if is_exif(seg) {
+            let tiff = &seg.payload[EXIF_IDENT.len()..];
+            out.extend_from_slice(&exif_segment(&tiff::set_thumbnail(
+                tiff, thumbnail,
+            )?)?);
+        } else {
+            out.extend_from_slice(seg.raw);
+        }
+    }
+    out.extend_from_slice(rest);
+
+    Ok(out)
+}
+
+/// Copy `to` with the EXIF and ICC segments of `from` put after its JFIF
+/// header, so a re-encoded copy keeps the orientation and colour profile.
+pub fn carry_segments(from: &[u8], to: &[u8]) -> io::Result<Vec<u8>> {
+    let (from, _) = segments(from)?;
+    let (segments, rest) = segments(to)?;
+    let carried = from.iter().filter(|seg| {
+        (seg.marker == APP1 && seg.payload.starts_with(EXIF_IDENT))
+            || is_icc(seg.marker, seg.ident())
+    });
+
+    let jfif = segments.first().is_some_and(|seg| seg.marker == APP0);
+    let (head, tail) = segments.split_at(jfif as usize);
+
+    let mut out = Vec::with_capacity(to.len());
+    out.extend_from_slice(&[0xFF, SOI]);
+    head.iter().for_each(|seg| out.extend_from_slice(seg.raw));
+    carried.for_each(|seg| out.extend_from_slice(seg.raw));
+    tail.iter().for_each(|seg| out.extend_from_slice(seg.raw));
+    out.extend_from_slice(rest);
+
+    Ok(out)
+}
+
+/// Width and height from the frame header, for `--dimensions`.
+pub fn dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let (segments, _) = segments(data).ok()?;
+    // SOF0 to SOF15, but for DHT, JPG and DAC which share the range
+    let frame = segments.iter().find(|seg| {
+        matches!(seg.marker, 0xC0..=0xCF)
+            && ![0xC4, 0xC8, 0xCC].contains(&seg.marker)
+    })?;
+    let head = frame.payload.get(..5)?;
+    let height = u16::from_be_bytes([head[1], head[2]]);
+    let width = u16::from_be_bytes([head[3], head[4]]);
+    Some((width as u32, height as u32))
+}
+
+/// A marker segment before the first scan.
+struct Segment<'a> {
+    marker: u8,
+    /// The whole segment, from its marker on.
+    raw: &'a [u8],
+    /// What follows the length; empty for standalone markers.
+    payload: &'a [u8],
+}
+
+impl Segment<'_> {
+    fn ident(&self) -> &[u8] {
+        &self.payload[..self.payload.len().min(IDENT_LEN)]
+    }
+}
+
+/// Split `data` into its segments up to the first scan, and the rest.
+fn segments(data: &[u8]) -> io::Result<(Vec<Segment<'_>>, &[u8])> {
+    if !data.starts_with(&[0xFF, SOI]) {
+        return Err(invalid("missing SOI marker"));
+    }
+
+    let mut segments = Vec::new();
+    let mut pos = 2;
+
+    loop {
+        let start = pos;
+        if data.get(pos) != Some(&0xFF) {
+            return Err(invalid("expected marker"));
+        }
+        while data.get(pos) == Some(&0xFF) {
+            pos += 1;
+        }
+        let Some(&marker) = data.get(pos) else {
+            return Err(invalid("truncated marker"));
+        };
+        pos += 1;
+
+        match marker {
+            SOS | EOI => return Ok((segments, &data[start..])),
+            0x01 | 0xD0..=0xD7 => {
+                segments.push(Segment {
+                    marker,
+                    raw: &data[start..pos],
+                    payload: &[],
+                });
+                continue;
+            }
+            _ => {}
+        }
+
+        let Some(len) = data.get(pos..pos + 2) else {
+            return Err(invalid("truncated segment"));
+        };
+        let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+        if len < 2 {
+            return Err(invalid("invalid segment length"));
+        }
+        let Some(payload) = data.get(pos + 2..pos + len) else {
+            return Err(invalid("truncated segment"));
+        };
+        pos += len;
+
+        segments.push(Segment { marker, raw: &data[start..pos], payload });
+    }
+}
+
+/// Whether a segment holds (part of) an ICC profile.
+pub fn is_icc(marker: u8, ident: &[u8]) -> bool {
+    marker == APP2 && ident.starts_with(b"ICC_PROFILE\0")
+}
+
+/// APP1 segment holding the EXIF block `tiff`.
+fn exif_segment(tiff: &[u8]) -> io::Result<Vec<u8>> {
+    let len = u16::try_from(2 + EXIF_IDENT.len() + tiff.len())
+        .map_err(|_| invalid("kept EXIF fields do not fit a segment"))?;
+
+    let mut seg = vec![0xFF, APP1];
+    seg.extend_from_slice(&len.to_be_bytes());
+    seg.extend_from_slice(EXIF_IDENT);
+    seg.extend_from_slice(tiff);
+    Ok(seg)
+}
+
+fn segment(marker: u8, payload: &[u8]) -> io::Result<Vec<u8>> {
+    let len = u16::try_from(2 + payload.len())
+        .map_err(|_| invalid("segment too large"))?;
+
+    let mut seg = vec![0xFF, marker];
+    seg.extend_from_slice(&len.to_be_bytes());
+    seg.extend_from_slice(payload);
+    Ok(seg)
+}
+
+fn xmp_segment(packet: &[u8]) -> io::Result<Vec<u8>> {
+    let len = u16::try_from(2 + XMP_IDENT.len() + packet.len())
+        .map_err(|_| invalid("XMP packet does not fit a segment"))?;
+
+    let mut seg = vec![0xFF, APP1];
+    seg.extend_from_slice(&len.to_be_bytes());
+    seg.extend_from_slice(XMP_IDENT);
+    seg.extend_from_slice(packet);
+    Ok(seg)
+}
+
+/// Read the next marker code, skipping any `0xFF` fill bytes.
+fn read_marker<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut byte = [0u8; 1];
+    r.read_exact(&mut byte)?;
+    if byte[0] != 0xFF {
+        return Err(invalid("expected marker"));
+    }
+
+    loop {
+        r.read_exact(&mut byte)?;
+        if byte[0] != 0xFF {
+            return Ok(byte[0]);
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use exif::{In, Reader, Tag, Value};
+
+    use super::*;
+    use crate::{
+        date::{self, Dates, Round},
+        gps::Fuzz,
+        keep::{Set, parse_keep},
+    };
+
+    const PLAIN: &[u8] = include_bytes!("../tests/fixtures/plain.jpg");
+    const EXIF: &[u8] = include_bytes!("../tests/fixtures/exif.jpg");
+    const XMP: &[u8] = include_bytes!("../tests/fixtures/xmp.jpg");
+    const IPTC: &[u8] = include_bytes!("../tests/fixtures/iptc.jpg");
+    const ALL: &[u8] = include_bytes!("../tests/fixtures/all.jpg");
+    const GPS: &[u8] = include_bytes!("../tests/fixtures/gps.jpg");
+    const MAKER_NOTE: &[u8] =
+        include_bytes!("../tests/fixtures/makernote.jpg");
+    const THUMBNAIL: &[u8] = include_bytes!("../tests/fixtures/thumbnail.jpg");
+    const IDENTITY: &[u8] = include_bytes!("../tests/fixtures/identity.jpg");
+    const EXTENDED: &[u8] = include_bytes!("../tests/fixtures/extended.jpg");
+    const TRAILER: &[u8] = include_bytes!("../tests/fixtures/trailer.jpg");
+    const PEOPLE: &[u8] = include_bytes!("../tests/fixtures/people.jpg");
+
+    fn headers(data: &[u8]) -> HeaderInfo {
+        scan_headers(&mut Cursor::new(data)).unwrap()
+    }
+
+    /// The first scan and everything after it.
+    fn image_data(data: &[u8]) -> &[u8] {
+        let sos = data.windows(2).position(|w| w == [0xFF, SOS]).unwrap();
+        &data[sos..]
+    }
+
+    fn segment_markers(data: &[u8]) -> Vec<u8> {
+        let mut markers = Vec::new();
+        let mut pos = 2;
+        while data[pos + 1] != SOS {
+            markers.push(data[pos + 1]);
+            let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]);
+            pos += 2 + len as usize;
+        }
+        markers
+    }
+
+    #[test]
+    fn plain_image_is_unchanged() {
+        assert_eq!(strip_metadata(PLAIN, &Keep::default()).unwrap(), PLAIN);
+    }
+
+    #[test]
+    fn strips_exif_xmp_and_iptc() {
+        // marker, length, EXIF ident and a 26 byte block with one entry
+        let orientation = 2 + 2 + EXIF_IDENT.len() as u64 + 26;
+
+        for (fixture, left) in
+            [(EXIF, orientation), (XMP, 0), (IPTC, 0), (ALL, orientation)]
+        {
+            assert!(headers(fixture).metadata_bytes > left);
+
+            let cleaned = strip_metadata(fixture, &Keep::default()).unwrap();
+            assert_eq!(headers(&cleaned).metadata_bytes, left);
+
+            for needle in [&b"Canon"[..], b"xmpmeta", b"8BIM", b"secret"] {
+                assert!(!cleaned.windows(needle.len()).any(|w| w == needle));
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_only_the_orientation() {
+        for fixture in [EXIF, ALL] {
+            let cleaned = strip_metadata(fixture, &Keep::default()).unwrap();
+            let tiff = headers(&cleaned).exif.unwrap();
+            let exif = Reader::new().read_raw(tiff).unwrap();
+
+            let tags: Vec<_> = exif.fields().map(|f| f.tag).collect();
+            assert_eq!(tags, [Tag::Orientation]);
+            let field = exif.get_field(Tag::Orientation, In::PRIMARY);
+            assert_eq!(field.unwrap().value.get_uint(0), Some(6));
+        }
+    }
+
+    #[test]
+    fn keeps_the_fields_asked_for() {
+        let keep = Keep { copyright: true, ..Keep::default() };
+        let cleaned = strip_metadata(EXIF, &keep).unwrap();
+        let tiff = headers(&cleaned).exif.unwrap();
+        let exif = Reader::new().read_raw(tiff).unwrap();
+
+        let tags: Vec<_> = exif.fields().map(|f| f.tag).collect();
+        assert_eq!(tags, [Tag::Orientation, Tag::Copyright]);
+        assert!(!cleaned.windows(5).any(|w| w == b"Canon"));
+        assert_eq!(strip_metadata(&cleaned, &keep).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn set_writes_the_attribution() {
+        let keep = Keep {
+            copyright: true,
+            set: Set {
+                artist: Some("Jane Doe".into()),
+                copyright: Some("CC BY 4.0".into()),
+            },
+            ..Keep::default()
+        };
+
+        for fixture in [PLAIN, ALL] {
+            let cleaned = strip_metadata(fixture, &keep).unwrap();
+            let tiff = headers(&cleaned).exif.unwrap();
+            let exif = Reader::new().read_raw(tiff).unwrap();
+            let text = |tag| {
+                let field = exif.get_field(tag, In::PRIMARY).unwrap();
+                field.display_value().to_string()
+            };
+            assert_eq!(text(Tag::Artist), "\"Jane Doe\"");
+            // the one set replaces the kept one
+            assert_eq!(text(Tag::Copyright), "\"CC BY 4.0\"");
+            assert!(!cleaned.windows(5).any(|w| w == b"Alice"));
+            assert!(cleaned.windows(19).any(|w| w == b"<rdf:li>Jane Doe</r"));
+            assert_eq!(strip_metadata(&cleaned, &keep).unwrap(), cleaned);
+        }
+    }
+
+    #[test]
+    fn keeping_nothing_drops_exif_and_icc() {
+        let keep = parse_keep("none").unwrap();
+        let cleaned = strip_metadata(ALL, &keep).unwrap();
+
+        let markers = segment_markers(&cleaned);
+        assert_eq!(markers[0], APP0);
+        assert!(markers[1..].iter().all(|m| !(APP0..=COM).contains(m)));
+        assert_eq!(image_data(&cleaned), image_data(ALL));
+    }
+
+    #[test]
+    fn keeps_jfif_and_icc_segments_in_order() {
+        let cleaned = strip_metadata(ALL, &Keep::default()).unwrap();
+        let markers = segment_markers(&cleaned);
+
+        assert_eq!(&markers[..3], [APP0, APP1, APP2]);
+        assert!(!markers.contains(&APP13));
+        assert!(!markers.contains(&COM));
+        assert!(cleaned.windows(12).any(|w| w == b"ICC_PROFILE\0"));
+    }
+
+    #[test]
+    fn icc_strip_applies_to_every_mode() {
+        let modes = [
+            Strip::All,
+            Strip::Gps,
+            Strip::Makernotes,
+            Strip::People,
+            Strip::Redact,
+        ];
+        for strip in modes {
+            for icc in [true, false] {
+                let keep = Keep { strip, icc, ..Keep::default() };
+                let cleaned = strip_metadata(ALL, &keep).unwrap();
+                let profile =
+                    cleaned.windows(12).any(|w| w == b"ICC_PROFILE\0");
+                assert_eq!(profile, icc, "{strip:?}");
+                assert_eq!(keep.unchanged(0), icc, "{strip:?}");
+                assert_eq!(image_data(&cleaned), image_data(ALL));
+            }
+        }
+    }
+
+    #[test]
+    fn only_the_jfif_header_is_kept_in_app0() {
+        let mut jfxx = b"JFXX\0\x13".to_vec();
+        jfxx.extend_from_slice(&image_data(PLAIN)[..64]);
+        let jfxx = segment(APP0, &jfxx).unwrap();
+        // the JFIF header is the first segment of the fixture
+        let at = 4 + u16::from_be_bytes([PLAIN[4], PLAIN[5]]) as usize;
+        let data = [&PLAIN[..at], &jfxx, &PLAIN[at..]].concat();
+
+        let info = headers(&data);
+        assert_eq!(info.metadata_bytes, jfxx.len() as u64);
+        for strip in [Strip::All, Strip::Gps, Strip::People] {
+            let keep = Keep { strip, ..Keep::default() };
+            let cleaned = strip_metadata(&data, &keep).unwrap();
+            assert_eq!(cleaned, PLAIN, "{strip:?}");
+        }
+    }
+
+    #[test]
+    fn multi_segment_profile_stays_whole() {
+        let segment = |marker: u8, payload: &[u8]| {
+            let len = (payload.len() as u16 + 2).to_be_bytes();
+            [&[0xFF, marker, len[0], len[1]][..], payload].concat()
+        };
+        let chunk = |seq: u8| {
+            segment(
+                APP2,
+                &[&b"ICC_PROFILE\0"[..], &[seq, 2], b"chunk"].concat(),
+            )
+        };
+        let (first, second) = (chunk(1), chunk(2));
+        // the JFIF header is the first segment of the fixture
+        let at = 4 + u16::from_be_bytes([PLAIN[4], PLAIN[5]]) as usize;
+        let split = [
+            &PLAIN[..at],
+            &first,
+            &segment(COM, b"between"),
+            &second,
+            &PLAIN[at..],
+        ]
+        .concat();
+
+        let cleaned = strip_metadata(&split, &Keep::default()).unwrap();
+        let kept = [&PLAIN[..at], &first, &second, &PLAIN[at..]].concat();
+        assert_eq!(cleaned, kept);
+
+        let keep = Keep { icc: false, ..Keep::default() };
+        assert_eq!(strip_metadata(&split, &keep).unwrap(), PLAIN);
+    }
+
+    #[test]
+    fn gps_mode_removes_only_the_location() {
+        let keep = Keep { strip: Strip::Gps, ..Keep::default() };
+        let cleaned = strip_metadata(GPS, &keep).unwrap();
+        let contains =
+            |needle: &[u8]| cleaned.windows(needle.len()).any(|w| w == needle);
+
+        let tiff = headers(&cleaned).exif.unwrap();
+        let exif = Reader::new().read_raw(tiff).unwrap();
+        assert!(exif.fields().all(|f| f.ifd_num == In::PRIMARY));
+        for tag in [Tag::Make, Tag::Model, Tag::Copyright] {
+            assert!(exif.get_field(tag, In::PRIMARY).is_some());
+        }
+        assert!(exif.get_field(Tag::GPSLatitudeRef, In::PRIMARY).is_none());
+
+        let places = [
+            &b"GPSLatitude"[..],
+            b"GPSAltitude",
+            b"Berlin",
+            b"Mitte",
+            b"Germany",
+        ];
+        for needle in places {
+            assert!(!contains(needle), "{needle:?} left");
+        }
+        let others =
+            [&b"ExposureTime"[..], b"FNumber", b"CreatorTool", b"Holiday"];
+        for needle in others {
+            assert!(contains(needle), "{needle:?} lost");
+        }
+        assert_eq!(segment_markers(&cleaned), segment_markers(GPS));
+        assert_eq!(image_data(&cleaned), image_data(GPS));
+        assert_eq!(strip_metadata(&cleaned, &keep).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn gps_fuzz_keeps_a_coarse_position() {
+        let fuzz = Some(Fuzz::Decimals(2));
+        for strip in [Strip::All, Strip::Gps] {
+            let keep = Keep { strip, gps_fuzz: fuzz, ..Keep::default() };
+            let cleaned = strip_metadata(GPS, &keep).unwrap();
+
+            let tiff = headers(&cleaned).exif.unwrap();
+            let exif = Reader::new().read_raw(tiff).unwrap();
+            let degrees = |tag| {
+                let field = exif.get_field(tag, In::PRIMARY).unwrap();
+                let Value::Rational(dms) = &field.value else {
+                    panic!("{tag} is not rational");
+                };
+                dms.iter()
+                    .zip([1.0, 60.0, 3600.0])
+                    .map(|(r, unit)| r.to_f64() / unit)
+                    .sum::<f64>()
+            };
+            assert!((degrees(Tag::GPSLatitude) - 52.37).abs() < 1e-6);
+            assert!((degrees(Tag::GPSLongitude) - 13.40).abs() < 1e-6);
+            for tag in [Tag::GPSLatitudeRef, Tag::GPSLongitudeRef] {
+                assert!(exif.get_field(tag, In::PRIMARY).is_some());
+            }
+            assert!(exif.get_field(Tag::GPSAltitude, In::PRIMARY).is_none());
+            assert_eq!(
+                exif.get_field(Tag::Make, In::PRIMARY).is_some(),
+                strip == Strip::Gps
+            );
+        }
+    }
+
+    #[test]
+    fn makernotes_mode_removes_only_the_maker_note() {
+        let keep = Keep { strip: Strip::Makernotes, ..Keep::default() };
+        let cleaned = strip_metadata(MAKER_NOTE, &keep).unwrap();
+
+        assert_eq!(cleaned.len(), MAKER_NOTE.len());
+        assert!(!cleaned.windows(6).any(|w| w == b"SERIAL"));
+        let tiff = headers(&cleaned).exif.unwrap();
+        let exif = Reader::new().read_raw(tiff).unwrap();
+        let tags: Vec<_> = exif.fields().map(|f| f.tag).collect();
+        assert_eq!(
+            tags,
+            [
+                Tag::Make,
+                Tag::Model,
+                Tag::ExposureTime,
+                Tag::FNumber,
+                Tag::DateTimeOriginal,
+                Tag::LensModel,
+            ]
+        );
+        assert_eq!(strip_metadata(&cleaned, &keep).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn redact_replaces_names_and_serial_numbers() {
+        let keep = Keep { strip: Strip::Redact, ..Keep::default() };
+        let cleaned = strip_metadata(IDENTITY, &keep).unwrap();
+
+        assert_eq!(
+            cleaned.len(),
+            IDENTITY.len() - b" aux:SerialNumber=\"4021337ABC\"".len()
+        );
+        for needle in [&b"Alice"[..], b"4021337", b"SerialNumber"] {
+            assert!(!cleaned.windows(needle.len()).any(|w| w == needle));
+        }
+        let tiff = headers(&cleaned).exif.unwrap();
+        let exif = Reader::new().read_raw(tiff).unwrap();
+        let text = |tag| {
+            let field = exif.get_field(tag, In::PRIMARY).unwrap();
+            match &field.value {
+                Value::Ascii(values) => values[0].clone(),
+                _ => panic!("{tag} is not text"),
+            }
+        };
+        assert_eq!(text(Tag::Artist), b"");
+        assert_eq!(text(Tag::BodySerialNumber), b"REDACTED");
+        // too short for the placeholder, cut to fit
+        assert_eq!(text(Tag::LensSerialNumber), b"RED");
+        assert_eq!(text(Tag::Make), b"Nikon");
+        assert_eq!(text(Tag::DateTimeOriginal), b"2024:07:15 10:20:30");
+        assert!(exif.get_field(Tag::MakerNote, In::PRIMARY).is_none());
+        assert!(cleaned.windows(10).any(|w| w == b"xmp:Rating"));
+        assert_eq!(strip_metadata(&cleaned, &keep).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn people_mode_removes_faces_names_and_keywords() {
+        let names = [&b"Alice"[..], b"Bob", b"Carol", b"Dave", b"People|"];
+        let contains = |data: &[u8], needle: &[u8]| {
+            data.windows(needle.len()).filter(|w| *w == needle).count()
+        };
+
+        for strip in [Strip::People, Strip::Redact] {
+            let keep = Keep { strip, ..Keep::default() };
+            let cleaned = strip_metadata(PEOPLE, &keep).unwrap();
+
+            for needle in
+                names.iter().chain([&&b"Regions"[..], &&b"beach"[..]])
+            {
+                assert_eq!(contains(&cleaned, needle), 0, "{needle:?} left");
+            }
+            assert_eq!(contains(&cleaned, b"xmp:Rating"), 1);
+            assert_eq!(headers(&cleaned).exif, headers(PEOPLE).exif);
+            assert_eq!(strip_metadata(&cleaned, &keep).unwrap(), cleaned);
+        }
+
+        // the names also go from the keyword lists, the other keywords stay
+        let keep =
+            Keep { strip: Strip::People, keywords: true, ..Keep::default() };
+        let cleaned = strip_metadata(PEOPLE, &keep).unwrap();
+        for needle in names {
+            assert_eq!(contains(&cleaned, needle), 0, "{needle:?} left");
+        }
+        assert_eq!(contains(&cleaned, b"<rdf:li>beach</rdf:li>"), 1);
+        assert_eq!(contains(&cleaned, b"<rdf:li>Places|beach</rdf:li>"), 1);
+        assert_eq!(image_data(&cleaned), image_data(PEOPLE));
+        assert_eq!(strip_metadata(&cleaned, &keep).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn iptc_and_comments_go_with_the_people() {
+        let modes =
+            [Strip::Gps, Strip::Makernotes, Strip::People, Strip::Redact];
+        for strip in modes {
+            let keep = Keep { strip, ..Keep::default() };
+            let cleaned = strip_metadata(ALL, &keep).unwrap();
+            let markers = segment_markers(&cleaned);
+
+            // --strip gps keeps the block without its place names
+            let kept = matches!(strip, Strip::Gps | Strip::Makernotes);
+            assert_eq!(markers.contains(&APP13), kept, "{strip:?}");
+            assert_eq!(markers.contains(&COM), kept, "{strip:?}");
+            assert!(markers.contains(&APP1));
+            assert_eq!(image_data(&cleaned), image_data(ALL));
+            assert_eq!(strip_metadata(&cleaned, &keep).unwrap(), cleaned);
+        }
+    }
+
+    #[test]
+    fn extended_xmp_goes_as_a_set() {
+        // four chunks of one extended packet, linked from the main one
+        let chunks = segments(EXTENDED).unwrap().0;
+        let chunks = chunks.iter().filter(|seg| {
+            seg.marker == APP1 && seg.payload.starts_with(XMP_EXTENSION)
+        });
+        assert_eq!(chunks.count(), 4);
+
+        let modes = [
+            Strip::All,
+            Strip::Gps,
+            Strip::Makernotes,
+            Strip::People,
+            Strip::Redact,
+        ];
+        for strip in modes {
+            let keep = Keep { strip, ..Keep::default() };
+            let cleaned = strip_metadata(EXTENDED, &keep).unwrap();
+
+            for needle in [&b"xmp/extension"[..], b"GDepth", b"ExtendedXMP"] {
+                assert!(!cleaned.windows(needle.len()).any(|w| w == needle));
+            }
+            let kept = cleaned.windows(10).any(|w| w == b"xmp:Rating");
+            assert_eq!(kept, strip != Strip::All);
+            assert_eq!(image_data(&cleaned), image_data(EXTENDED));
+            assert_eq!(strip_metadata(&cleaned, &keep).unwrap(), cleaned);
+        }
+    }
+
+    #[test]
+    fn trailer_goes_unless_kept() {
+        // a 44 byte MP4 after the EOI marker, like a motion photo
+        assert_eq!(headers(TRAILER).trailer_bytes, 44);
+        assert_eq!(headers(PLAIN).trailer_bytes, 0);
+
+        let modes = [Strip::All, Strip::Gps, Strip::Makernotes, Strip::Redact];
+        for strip in modes {
+            let keep = Keep { strip, ..Keep::default() };
+            let cleaned = strip_metadata(TRAILER, &keep).unwrap();
+
+            assert!(cleaned.ends_with(&[0xFF, EOI]));
+            assert_eq!(headers(&cleaned).trailer_bytes, 0);
+            assert_eq!(strip_metadata(&cleaned, &keep).unwrap(), cleaned);
+        }
+
+        let keep = Keep { trailer: true, ..Keep::default() };
+        let kept = strip_metadata(TRAILER, &keep).unwrap();
+        assert!(kept.ends_with(b"mdatmotion photo"));
+    }
+
+    #[test]
+    fn motion_video_is_found_after_the_image() {
+        let video = &TRAILER[TRAILER.len() - 44..];
+        assert_eq!(motion_video(TRAILER), Some(video));
+        assert_eq!(motion_video(PLAIN), None);
+
+        // Samsung puts a marker before the video and an index after it
+        let samsung = [PLAIN, MOTION_PHOTO_DATA, video, b"\0\0\0\x10SEFHSEFT"];
+        assert_eq!(motion_video(&samsung.concat()), Some(video));
+
+        let hidden = [PLAIN, b"PK\x03\x04 not a video"].concat();
+        assert_eq!(motion_video(&hidden), None);
+    }
+
+    #[test]
+    fn end_of_image_is_found_across_chunks() {
+        for data in [PLAIN, ALL, TRAILER] {
+            let (_, rest) = segments(data).unwrap();
+            let mut image = ImageEnd::default();
+            let end = rest.chunks(1).find_map(|chunk| image.feed(chunk));
+            assert_eq!(end, Some(image_len(rest) as u64));
+        }
+
+        // stuffed zeros and restart markers are image data
+        let scan = b"\xff\xda\0\x08\x01\x01\0\0\x3f\0\x12\xff\0\x34\xff\xd0\
+            \x56\xff\xd9trailer";
+        assert_eq!(image_len(scan), scan.len() - b"trailer".len());
+        // a truncated scan is kept whole
+        assert_eq!(image_len(&scan[..14]), 14);
+    }
+
+    #[test]
+    fn thumbnail_goes_in_every_mode() {
+        for strip in [Strip::All, Strip::Gps, Strip::Makernotes] {
+            let keep = Keep { strip, ..Keep::default() };
+            let cleaned = strip_metadata(THUMBNAIL, &keep).unwrap();
+
+            assert!(!cleaned.windows(9).any(|w| w == b"uncropped"));
+            let tiff = headers(&cleaned).exif.unwrap();
+            let exif = Reader::new().read_raw(tiff).unwrap();
+            assert!(exif.fields().all(|f| f.ifd_num == In::PRIMARY));
+            assert!(exif.get_field(Tag::Orientation, In::PRIMARY).is_some());
+            assert_eq!(image_data(&cleaned), image_data(THUMBNAIL));
+        }
+
+        // it comes last, so the in-place modes cut it off
+        let keep = Keep { strip: Strip::Gps, ..Keep::default() };
+        let cleaned = strip_metadata(THUMBNAIL, &keep).unwrap();
+        assert!(cleaned.len() < THUMBNAIL.len() - 50);
+        assert_eq!(strip_metadata(&cleaned, &keep).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn set_thumbnail_adds_or_replaces_it() {
+        let thumbnail = |data: &[u8]| {
+            let tiff = headers(data).exif.unwrap();
+            let exif = Reader::new().read_raw(tiff.clone()).unwrap();
+            let field = |tag| {
+                let field = exif.get_field(tag, In::THUMBNAIL).unwrap();
+                field.value.get_uint(0).unwrap() as usize
+            };
+            let start = field(Tag::JPEGInterchangeFormat);
+            let len = field(Tag::JPEGInterchangeFormatLength);
+            tiff[start..start + len].to_vec()
+        };
+        let new = b"\xff\xd8regenerated\xff\xd9";
+
+        let added = set_thumbnail(PLAIN, new).unwrap();
+        assert_eq!(thumbnail(&added), new);
+        assert_eq!(&segment_markers(&added)[..2], [APP0, APP1]);
+        assert_eq!(image_data(&added), image_data(PLAIN));
+
+        let replaced = set_thumbnail(THUMBNAIL, new).unwrap();
+        assert_eq!(thumbnail(&replaced), new);
+        assert!(!replaced.windows(9).any(|w| w == b"uncropped"));
+        let tiff = headers(&replaced).exif.unwrap();
+        let exif = Reader::new().read_raw(tiff).unwrap();
+        assert!(exif.get_field(Tag::Make, In::PRIMARY).is_some());
+        assert_eq!(set_thumbnail(&replaced, new).unwrap(), replaced);
+    }
+
+    #[test]
+    fn resized_copies_carry_the_exif_block() {
+        let carried = carry_segments(EXIF, PLAIN).unwrap();
+
+        assert_eq!(&segment_markers(&carried)[..2], [APP0, APP1]);
+        assert_eq!(headers(&carried).exif, headers(EXIF).exif);
+        assert_eq!(image_data(&carried), image_data(PLAIN));
+        assert_eq!(carry_segments(PLAIN, PLAIN).unwrap(), PLAIN);
+    }
+
+    #[test]
+    fn dates_are_shifted_rounded_or_removed() {
+        let date = |keep: &Keep| {
+            let cleaned = strip_metadata(GPS, keep).unwrap();
+            let tiff = headers(&cleaned).exif.unwrap();
+            let exif = Reader::new().read_raw(tiff).unwrap();
+            let field = exif.get_field(Tag::DateTime, In::PRIMARY).unwrap();
+            let Value::Ascii(values) = &field.value else {
+                panic!("DateTime is not text");
+            };
+            values[0].clone()
+        };
+        let shifted = Dates {
+            offset: date::parse_offset("-3d").unwrap(),
+            round: Some(Round::Day),
+            remove: false,
+        };
+        let removed = Dates { remove: true, ..Dates::default() };
+
+        let keep = Keep { dates: shifted, ..parse_keep("datetime").unwrap() };
+        assert_eq!(date(&keep), b"2024:07:12 00:00:00");
+        let keep = Keep { strip: Strip::Gps, dates: shifted, ..keep };
+        assert_eq!(date(&keep), b"2024:07:12 00:00:00");
+        let keep = Keep { dates: removed, ..keep };
+        assert_eq!(date(&keep), b"    :  :     :  :  ");
+
+        // the XMP and IPTC dates of the modes keeping them too
+        let xmp = |d: &[u8]| [&b"xmp:CreateDate=\""[..], d, b"\""].concat();
+        let iptc = |d: &[u8], t: &[u8]| {
+            [&[0x1C, 2, 55, 0, 8][..], d, &[0x1C, 2, 60, 0, 11], t].concat()
+        };
+        let original = [
+            xmp(b"2024-07-15T10:20:30.25+02:00"),
+            iptc(b"20240715", b"102030+0200"),
+        ];
+        let rewritten = [
+            xmp(b"2024-07-12T00:00:00+02:00"),
+            iptc(b"20240712", b"000000+0200"),
+        ];
+        for strip in [Strip::Gps, Strip::Makernotes] {
+            let cleaned = |dates| {
+                let keep = Keep { strip, dates, ..Keep::default() };
+                strip_metadata(GPS, &keep).unwrap()
+            };
+            let contains = |data: &[u8], needle: &[u8]| {
+                data.windows(needle.len()).any(|w| w == needle)
+            };
+
+            let kept = cleaned(Dates::default());
+            assert!(original.iter().all(|needle| contains(&kept, needle)));
+            let changed = cleaned(shifted);
+            assert!(rewritten.iter().all(|needle| contains(&changed, needle)));
+            let gone = cleaned(removed);
+            for needle in
+                [&b"CreateDate"[..], b"\x1c\x02\x37", b"\x1c\x02\x3c"]
+            {
+                assert!(!contains(&gone, needle), "{strip:?} {needle:?}");
+            }
+            assert!(contains(&gone, b"Holiday"));
+        }
+    }
+
+    #[test]
+    fn image_data_is_bit_identical() {
+        for fixture in [EXIF, XMP, IPTC, ALL] {
+            let cleaned = strip_metadata(fixture, &Keep::default()).unwrap();
+            assert_eq!(image_data(&cleaned), image_data(fixture));
+
+            let decode = |data: &[u8]| {
+                jpeg_decoder::Decoder::new(data).decode().unwrap()
+            };
+            assert_eq!(decode(&cleaned), decode(PLAIN));
+        }
+    }
+
+    #[test]
+    fn cleaning_is_idempotent() {
+        for fixture in [PLAIN, EXIF, XMP, IPTC, ALL] {
+            let cleaned = strip_metadata(fixture, &Keep::default()).unwrap();
+            assert_eq!(
+                strip_metadata(&cleaned, &Keep::default()).unwrap(),
+                cleaned
+            );
+        }
+    }
+
+    #[test]
+    fn dimensions_come_from_the_frame_header() {
+        assert_eq!(dimensions(PLAIN), Some((32, 24)));
+        assert_eq!(dimensions(GPS), Some((8, 8)));
+        assert_eq!(dimensions(b"not a jpeg"), None);
+    }
+
+    #[test]
+    fn rejects_broken_streams() {
+        assert!(strip_metadata(b"not a jpeg", &Keep::default()).is_err());
+        assert!(
+            strip_metadata(&[0xFF, SOI, 0xFF, APP1, 0x00], &Keep::default())
+                .is_err()
+        );
+        assert!(strip_metadata(&EXIF[..40], &Keep::default()).is_err());
+    }
+}
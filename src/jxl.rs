@@ -0,0 +1,224 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! JPEG XL container helpers.
+//!
+//! Metadata only exists in the ISOBMFF container, as Exif, xml and JUMBF
+//! boxes, possibly Brotli-compressed in a brob box. Cleaning drops those
+//! and copies every other box whole. A bare codestream has nowhere to put
+//! metadata and is reported as clean.
+
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+use crate::{
+    bmff::{BoxHeader, read_box_header},
+    format::{HeaderInfo, read_len},
+};
+
+/// Start of a bare codestream.
+const CODESTREAM: &[u8] = b"\xff\x0a";
+/// The signature box every container starts with.
+const SIGNATURE: &[u8] = b"\0\0\0\x0cJXL \r\n\x87\n";
+
+const EXIF: &[u8; 4] = b"Exif";
+const BROB: &[u8; 4] = b"brob";
+
+/// Boxes removed by cleaning.
+const METADATA: &[&[u8; 4]] = &[EXIF, b"xml ", b"jumb"];
+
+/// Whether a box of type `kind` is metadata; `inner` is the start of its
+/// payload, which names the compressed box for brob.
+pub fn is_metadata(kind: &[u8; 4], inner: &[u8]) -> bool {
+    if kind == BROB {
+        return METADATA.iter().any(|m| inner.starts_with(*m));
+    }
+    METADATA.contains(&kind)
+}
+
+/// Walk the boxes of the container without reading the codestream.
+pub fn scan_boxes<R: Read + Seek>(r: &mut R) -> io::Result<HeaderInfo> {
+    let mut info = HeaderInfo::default();
+
+    let mut start = [0u8; 2];
+    r.read_exact(&mut start)?;
+    if start == CODESTREAM {
+        info.segments = 1;
+        return Ok(info);
+    }
+    let mut signature = [0u8; 12];
+    r.rewind()?;
+    r.read_exact(&mut signature)?;
+    if signature != SIGNATURE {
+        return Err(invalid("missing JPEG XL signature"));
+    }
+    info.segments += 1;
+
+    while let Some(BoxHeader { kind, header, size }) = read_box_header(r)? {
+        let len = match size {
+            Some(size) => size
+                .checked_sub(header)
+                .ok_or_else(|| invalid("box smaller than its header"))?,
+            // the last box runs to the end of the file
+            None => {
+                let pos = r.stream_position()?;
+                let end = r.seek(SeekFrom::End(0))?;
+                r.seek(SeekFrom::Start(pos))?;
+                end.saturating_sub(pos)
+            }
+        };
+
+        let mut inner = [0u8; 4];
+        let peek = len.min(4) as usize;
+        r.read_exact(&mut inner[..peek])?;
+
+        if kind == *EXIF && len < 4 {
+            return Err(invalid("truncated Exif box"));
+        }
+        if kind == *EXIF && info.exif.is_none() && len > 4 {
+            let payload = read_len(r, len - 4)?
+                .ok_or_else(|| invalid("truncated box"))?;
+            // the payload starts with the offset of the TIFF header
+            let skip = u32::from_be_bytes(inner) as usize;
+            info.exif = payload.get(skip..).map(<[u8]>::to_vec);
+        } else {
+            r.seek(SeekFrom::Current((len - peek as u64) as i64))?;
+        }
+
+        info.segments += 1;
+        if is_metadata(&kind, &inner[..peek]) {
+            info.metadata_bytes += header + len;
+        }
+    }
+
+    Ok(info)
+}
+
+/// Copy `data` without its metadata boxes.
+pub fn strip_metadata(data: &[u8]) -> io::Result<Vec<u8>> {
+    if data.starts_with(CODESTREAM) {
+        return Ok(data.to_vec());
+    }
+    if !data.starts_with(SIGNATURE) {
+        return Err(invalid("missing JPEG XL signature"));
+    }
+
+    let mut out = Vec::with_capacity(data.len());
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let mut r = Cursor::new(&data[pos..]);
+        let Some(BoxHeader { kind, header, size }) = read_box_header(&mut r)?
+        else {
+            return Err(invalid("truncated box"));
+        };
+        let end = match size {
+            Some(size) => usize::try_from(size)
+                .ok()
+                .and_then(|size| pos.checked_add(size))
+                .filter(|&end| end <= data.len() && size >= header)
+                .ok_or_else(|| invalid("truncated box"))?,
+            None => data.len(),
+        };
+
+        let payload = &data[pos + header as usize..end];
+        if !is_metadata(&kind, payload) {
+            out.extend_from_slice(&data[pos..end]);
+        }
+        pos = end;
+    }
+
+    Ok(out)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: &[u8] = include_bytes!("../tests/fixtures/plain.jxl");
+    const META: &[u8] = include_bytes!("../tests/fixtures/meta.jxl");
+    const BARE: &[u8] = include_bytes!("../tests/fixtures/bare.jxl");
+
+    fn boxes(data: &[u8]) -> Vec<&[u8]> {
+        let mut boxes = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let size =
+                u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap());
+            boxes.push(&data[pos + 4..pos + 8]);
+            pos += size as usize;
+        }
+        boxes
+    }
+
+    #[test]
+    fn plain_image_is_unchanged() {
+        for data in [PLAIN, BARE] {
+            assert_eq!(strip_metadata(data).unwrap(), data);
+            assert_eq!(
+                scan_boxes(&mut Cursor::new(data)).unwrap().metadata_bytes,
+                0
+            );
+        }
+    }
+
+    #[test]
+    fn scan_finds_exif_xmp_and_compressed_boxes() {
+        let info = scan_boxes(&mut Cursor::new(META)).unwrap();
+
+        assert_eq!(info.segments, 7);
+        assert!(info.metadata_bytes > 0);
+        assert!(info.exif.unwrap().starts_with(b"MM\0\x2a"));
+    }
+
+    #[test]
+    fn strips_metadata_boxes() {
+        let cleaned = strip_metadata(META).unwrap();
+
+        assert_eq!(boxes(&cleaned), [&b"JXL "[..], b"ftyp", b"jxlp", b"jxlp"]);
+        assert_eq!(
+            scan_boxes(&mut Cursor::new(&cleaned)).unwrap().metadata_bytes,
+            0
+        );
+        assert_eq!(strip_metadata(&cleaned).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn rejects_oversized_and_undersized_exif_boxes() {
+        // an Exif box with a 64-bit size far past the end of the file
+        let huge = [
+            SIGNATURE,
+            b"\0\0\0\x01Exif",
+            &(1u64 << 44).to_be_bytes(),
+            b"\0\0\0\0MM\0\x2a",
+        ]
+        .concat();
+        assert!(scan_boxes(&mut Cursor::new(&huge)).is_err());
+
+        let short = [SIGNATURE, b"\0\0\0\x0aExif\0\0"].concat();
+        assert!(scan_boxes(&mut Cursor::new(&short)).is_err());
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        assert!(strip_metadata(b"not a jxl file").is_err());
+        assert!(strip_metadata(&META[..40]).is_err());
+        assert!(scan_boxes(&mut Cursor::new(&META[..40])).is_err());
+    }
+}
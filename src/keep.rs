@@ -0,0 +1,586 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Fields kept through cleaning, for `--keep` and `--strip`.
+//!
+//! Instead of dropping the EXIF block whole, a minimal one is rebuilt from
+//! the kept fields of the main image; everything else, the thumbnail
+//! included, is left out. `--strip gps` turns this around and removes the
+//! location alone, though the thumbnail still goes.
+
+use std::io::{self, Cursor};
+
+use clap::ValueEnum;
+use exif::{
+    Exif, Field, In, Rational, Reader, Tag, Value, experimental::Writer,
+};
+
+use crate::{
+    date::Dates,
+    gps::{self, Fuzz},
+    xml::{self, Rules, local_name},
+};
+
+/// What cleaning removes, from `--strip`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Strip {
+    /// All metadata, except what --keep names
+    #[default]
+    All,
+    /// Only the location: GPS data and place names (JPEG only for now)
+    Gps,
+    /// Only the EXIF maker notes, with their serial numbers and private
+    /// camera data (JPEG only for now)
+    Makernotes,
+    /// Only what names the people shown: XMP face regions, the people in
+    /// the image and the keywords (JPEG only for now)
+    People,
+    /// Only the thumbnail, maker notes, IPTC and comments go: the GPS data
+    /// is blanked, identifying text replaced and the people shown removed,
+    /// from `--redact`
+    #[value(skip)]
+    Redact,
+}
+
+/// Bundles of options for a use, from `--preset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// Publishing on the web: everything private goes, the orientation,
+    /// colour profile and colour space stay
+    Web,
+}
+
+impl Preset {
+    /// What this preset makes of `keep`; the copyright and dates still
+    /// follow `--keep`.
+    pub fn apply(self, keep: Keep) -> Keep {
+        match self {
+            Preset::Web => Keep {
+                orientation: true,
+                icc: true,
+                colorspace: true,
+                strip: Strip::All,
+                gps_fuzz: None,
+                ..keep
+            },
+        }
+    }
+}
+
+/// What happens to colour profiles, from `--icc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum IccPolicy {
+    /// Keep the profile, every segment of it in order
+    Keep,
+    /// Remove it; wide-gamut images then show duller colours
+    Strip,
+}
+
+/// XMP properties holding a date.
+const DATES: &[&[u8]] = &[
+    b"xmp:CreateDate",
+    b"xmp:ModifyDate",
+    b"xmp:MetadataDate",
+    b"exif:DateTimeOriginal",
+    b"exif:DateTimeDigitized",
+    b"tiff:DateTime",
+    b"photoshop:DateCreated",
+    b"dc:date",
+    b"stEvt:when",
+];
+
+/// XMP properties naming a person or a device, dropped by `--redact`.
+const IDENTITIES: &[&[u8]] = &[
+    b"dc:creator",
+    b"tiff:Artist",
+    b"aux:SerialNumber",
+    b"aux:LensSerialNumber",
+    b"aux:OwnerName",
+    b"exifEX:BodySerialNumber",
+    b"exifEX:LensSerialNumber",
+    b"exifEX:CameraOwnerName",
+    b"exif:ImageUniqueID",
+    b"exifEX:ImageUniqueID",
+];
+
+/// XMP properties naming a place, on top of the `GPS*` ones.
+const PLACES: &[&[u8]] = &[
+    b"photoshop:City",
+    b"photoshop:State",
+    b"photoshop:Country",
+    b"Iptc4xmpCore:Location",
+    b"Iptc4xmpCore:CountryCode",
+    b"Iptc4xmpExt:LocationCreated",
+    b"Iptc4xmpExt:LocationShown",
+];
+
+/// XMP properties about the people shown: the MWG and Microsoft face
+/// regions and the IPTC people in the image, with their names.
+const PEOPLE: &[&[u8]] = &[
+    b"mwg-rs:Regions",
+    b"MP:RegionInfo",
+    b"Iptc4xmpExt:PersonInImage",
+    b"Iptc4xmpExt:PersonInImageWDetails",
+];
+
+/// XMP keyword lists, flat or hierarchical, where the people tagged in a
+/// photo show up as keywords too.
+const KEYWORDS: &[&[u8]] = &[
+    b"dc:subject",
+    b"lr:hierarchicalSubject",
+    b"digiKam:TagsList",
+    b"MicrosoftPhoto:LastKeywordXMP",
+    b"mediapro:CatalogSets",
+];
+
+/// Top-level keywords the people are tagged under in hierarchies, such as
+/// `People|Friends|Jane Doe` or `Persons/Jane Doe`.
+const PEOPLE_KEYWORDS: &[&[u8]] = &[b"people", b"persons", b"person"];
+
+/// Attribution written into the cleaned files, from `--set`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Set {
+    pub artist: Option<String>,
+    pub copyright: Option<String>,
+}
+
+/// Names accepted by `--set`.
+const SET_NAMES: &[&str] = &["artist", "copyright"];
+
+/// Parse one `--set`: `name=value`.
+pub fn parse_set(s: &str) -> Result<Set, String> {
+    let Some((name, value)) = s.split_once('=') else {
+        return Err(format!("expected name=value, got '{s}'"));
+    };
+    let value = Some(value.to_string());
+    match name.trim().to_ascii_lowercase().as_str() {
+        "artist" => Ok(Set { artist: value, ..Set::default() }),
+        "copyright" => Ok(Set { copyright: value, ..Set::default() }),
+        name => Err(format!(
+            "unknown field '{name}', expected one of {}",
+            SET_NAMES.join(", ")
+        )),
+    }
+}
+
+impl Set {
+    /// `self` with the fields `other` sets; the last `--set` wins.
+    pub fn merge(self, other: Set) -> Set {
+        Set {
+            artist: other.artist.or(self.artist),
+            copyright: other.copyright.or(self.copyright),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.artist.is_none() && self.copyright.is_none()
+    }
+
+    /// The EXIF fields to write.
+    fn fields(&self) -> impl Iterator<Item = Field> {
+        let field = |tag, value: &Option<String>| {
+            value.as_ref().map(|value| Field {
+                tag,
+                ifd_num: In::PRIMARY,
+                value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+            })
+        };
+        [
+            field(Tag::Artist, &self.artist),
+            field(Tag::Copyright, &self.copyright),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// A minimal XMP packet with the Dublin Core creator and rights, or
+    /// `None` when nothing is set.
+    pub fn xmp(&self) -> Option<Vec<u8>> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mut xmp = String::from(XMP_START);
+        if let Some(artist) = &self.artist {
+            xmp.push_str(&format!(
+                "   <dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq>\
+                 </dc:creator>\n",
+                xml::escape(artist)
+            ));
+        }
+        if let Some(copyright) = &self.copyright {
+            xmp.push_str(&format!(
+                "   <dc:rights><rdf:Alt><rdf:li xml:lang=\"x-default\">{}\
+                 </rdf:li></rdf:Alt></dc:rights>\n",
+                xml::escape(copyright)
+            ));
+        }
+        xmp.push_str(XMP_END);
+
+        Some(xmp.into_bytes())
+    }
+}
+
+const XMP_START: &str = "<?xpacket begin=\"\u{feff}\" \
+    id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+    <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+    <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  \
+    <rdf:Description rdf:about=\"\" \
+    xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n";
+const XMP_END: &str = "  </rdf:Description>\n </rdf:RDF>\n</x:xmpmeta>\n\
+    <?xpacket end=\"w\"?>";
+
+/// What survives cleaning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keep {
+    /// A non-default EXIF orientation, so viewers do not show the image
+    /// rotated.
+    pub orientation: bool,
+    /// The colour profile.
+    pub icc: bool,
+    /// The EXIF copyright notice.
+    pub copyright: bool,
+    /// The EXIF dates and times, with their time zone offsets.
+    pub datetime: bool,
+    /// The EXIF colour space, telling sRGB images apart.
+    pub colorspace: bool,
+    /// Whether everything goes or only some of it; the fields above only
+    /// matter for [`Strip::All`], the other modes keep them all.
+    pub strip: Strip,
+    /// Keep the GPS position at this precision instead of removing it.
+    pub gps_fuzz: Option<Fuzz>,
+    /// Changes to the dates that are kept.
+    pub dates: Dates,
+    /// Embed a new EXIF thumbnail made from the cleaned pixels; the old one
+    /// always goes, as it may show the image before it was cropped.
+    pub regen_thumbnail: bool,
+    /// Largest image decoded for the new thumbnail, in pixels.
+    pub max_pixels: Option<u64>,
+    /// Attribution written in place of what was removed.
+    pub set: Set,
+    /// The data appended after the end of a JPEG image.
+    pub trailer: bool,
+    /// The XMP keywords that do not name a person, when the people go.
+    pub keywords: bool,
+}
+
+impl Default for Keep {
+    fn default() -> Self {
+        Keep {
+            orientation: true,
+            icc: true,
+            copyright: false,
+            datetime: false,
+            colorspace: false,
+            strip: Strip::All,
+            gps_fuzz: None,
+            dates: Dates::default(),
+            regen_thumbnail: false,
+            max_pixels: None,
+            set: Set::default(),
+            trailer: false,
+            keywords: false,
+        }
+    }
+}
+
+/// Names accepted by `--keep`.
+const NAMES: &[&str] =
+    &["orientation", "icc", "copyright", "datetime", "colorspace"];
+
+/// Parse `--keep`: `none` or a comma-separated list of field names.
+pub fn parse_keep(s: &str) -> Result<Keep, String> {
+    let mut keep = Keep { orientation: false, icc: false, ..Keep::default() };
+    if s.trim().eq_ignore_ascii_case("none") {
+        return Ok(keep);
+    }
+
+    for name in s.split(',').map(|name| name.trim().to_ascii_lowercase()) {
+        match name.as_str() {
+            "orientation" => keep.orientation = true,
+            "icc" => keep.icc = true,
+            "copyright" => keep.copyright = true,
+            "datetime" => keep.datetime = true,
+            "colorspace" => keep.colorspace = true,
+            _ => {
+                return Err(format!(
+                    "unknown field '{name}', expected none or some of {}",
+                    NAMES.join(", ")
+                ));
+            }
+        }
+    }
+
+    Ok(keep)
+}
+
+impl Keep {
+    /// Whether a file whose headers hold `metadata_bytes` of metadata is
+    /// copied unchanged; colour profiles do not count as metadata, so they
+    /// are only looked for when they go too.
+    pub fn unchanged(&self, metadata_bytes: u64) -> bool {
+        metadata_bytes == 0
+            && self.icc
+            && !self.dates.is_active()
+            && !self.regen_thumbnail
+            && self.set.is_empty()
+    }
+
+    fn keeps(&self, field: &Field) -> bool {
+        match field.tag {
+            Tag::Orientation => {
+                self.orientation
+                    && matches!(field.value.get_uint(0), Some(2..=8))
+            }
+            Tag::Copyright => self.copyright && self.set.copyright.is_none(),
+            Tag::ColorSpace => self.colorspace,
+            Tag::DateTime
+            | Tag::DateTimeOriginal
+            | Tag::DateTimeDigitized
+            | Tag::OffsetTime
+            | Tag::OffsetTimeOriginal
+            | Tag::OffsetTimeDigitized => self.datetime && !self.dates.remove,
+            Tag::SubSecTime
+            | Tag::SubSecTimeOriginal
+            | Tag::SubSecTimeDigitized => {
+                self.datetime && !self.dates.drops_subsec()
+            }
+            _ => false,
+        }
+    }
+
+    /// A big-endian EXIF (TIFF) block with only the kept fields of `tiff`,
+    /// the coarse GPS position with `--gps-fuzz` and the fields of `--set`,
+    /// or `None` when there are none.
+    ///
+    /// Unparseable blocks keep nothing rather than failing the file.
+    pub fn exif_block(&self, tiff: &[u8]) -> Option<Vec<u8>> {
+        let exif = Reader::new().read_raw(tiff.to_vec()).ok()?;
+        let mut fields: Vec<_> = exif
+            .fields()
+            .filter(|f| f.ifd_num == In::PRIMARY && self.keeps(f))
+            .cloned()
+            .collect();
+        for field in &mut fields {
+            if let Value::Ascii(values) = &mut field.value
+                && let Some(value) = values.first_mut()
+                && let Some(date) = self.dates.exif(value)
+            {
+                value[..date.len()].copy_from_slice(&date);
+            }
+        }
+        if let Some(fuzz) = self.gps_fuzz {
+            fields.extend(fuzzed_position(&exif, fuzz).into_iter().flatten());
+        }
+        fields.extend(self.set.fields());
+        if fields.is_empty() {
+            return None;
+        }
+
+        let mut writer = Writer::new();
+        fields.iter().for_each(|field| writer.push_field(field));
+        let mut out = Cursor::new(Vec::new());
+        writer.write(&mut out, false).ok()?;
+
+        Some(out.into_inner())
+    }
+}
+
+/// The GPS references and coordinates of `exif`, snapped to the grid of
+/// `fuzz`; `None` when any of them is missing.
+fn fuzzed_position(exif: &Exif, fuzz: Fuzz) -> Option<[Field; 4]> {
+    let field = |tag| exif.get_field(tag, In::PRIMARY);
+    let degrees = |field: &Field| match &field.value {
+        Value::Rational(dms) if dms.len() == 3 => {
+            gps::to_degrees([0, 1, 2].map(|i| (dms[i].num, dms[i].denom)))
+        }
+        _ => None,
+    };
+    let lat = field(Tag::GPSLatitude)?;
+    let lon = field(Tag::GPSLongitude)?;
+    let (lat_value, lon_value) = fuzz.apply(degrees(lat)?, degrees(lon)?);
+
+    let fuzzed = |field: &Field, degrees| Field {
+        value: Value::Rational(
+            gps::to_dms(degrees)
+                .map(|(num, denom)| Rational { num, denom })
+                .to_vec(),
+        ),
+        ..field.clone()
+    };
+    Some([
+        field(Tag::GPSLatitudeRef)?.clone(),
+        fuzzed(lat, lat_value),
+        field(Tag::GPSLongitudeRef)?.clone(),
+        fuzzed(lon, lon_value),
+    ])
+}
+
+/// Copy an XMP packet without its GPS properties and place names with
+/// `--strip gps`, without the people shown with `--strip people`, without
+/// all of those and the names and serial numbers with `--redact`, and with
+/// its dates shifted and rounded, or without them, when `keep` changes
+/// them.
+pub fn strip_xmp(packet: &[u8], keep: &Keep) -> io::Result<Vec<u8>> {
+    let mut packet = match keep.strip {
+        Strip::People | Strip::Redact => strip_people(packet, keep.keywords)?,
+        _ => packet.to_vec(),
+    };
+    let dates = &keep.dates;
+    if dates.is_active() && !dates.remove {
+        packet = rewrite_dates(&packet, dates)?;
+    }
+
+    let is_dropped: fn(&[u8]) -> bool = match (keep.strip, dates.remove) {
+        (Strip::Gps, true) => |name| is_location(name) || is_date(name),
+        (Strip::Gps, false) => is_location,
+        (Strip::Redact, true) => |name| is_redacted(name) || is_date(name),
+        (Strip::Redact, false) => is_redacted,
+        (_, true) => is_date,
+        (_, false) => return Ok(packet),
+    };
+    let rules =
+        Rules { element: is_dropped, attribute: is_dropped, comments: false };
+    Ok(xml::filter(&packet, &rules)?.data)
+}
+
+/// Copy an XMP packet with the values of its date properties shifted and
+/// rounded; those that are not dates are left as they are, like the EXIF
+/// ones.
+fn rewrite_dates(packet: &[u8], dates: &Dates) -> io::Result<Vec<u8>> {
+    let mut edits: Vec<_> = xml::values(packet)?
+        .iter()
+        .filter(|value| value.path.iter().any(|name| is_date(name)))
+        .filter_map(|value| Some((value.text_span, dates.xmp(value.text)?)))
+        .collect();
+    edits.sort_by_key(|edit| edit.0);
+    Ok(xml::replace(packet, &edits))
+}
+
+/// Copy an XMP packet without its face regions and people in the image,
+/// and without its keywords; with `keywords` only those naming one of
+/// these people, or filed under one of [`PEOPLE_KEYWORDS`], go.
+fn strip_people(packet: &[u8], keywords: bool) -> io::Result<Vec<u8>> {
+    let is_dropped: fn(&[u8]) -> bool = if keywords {
+        |name| PEOPLE.contains(&name)
+    } else {
+        |name| PEOPLE.contains(&name) || KEYWORDS.contains(&name)
+    };
+    let within = |path: &[&[u8]], names: &[&[u8]]| {
+        path.iter().any(|name| names.contains(name))
+    };
+
+    let mut packet = packet.to_vec();
+    if keywords {
+        let values = xml::values(&packet)?;
+        let mut people: Vec<&[u8]> = Vec::new();
+        for value in &values {
+            let name = local_name(value.path.last().unwrap());
+            if within(&value.path, PEOPLE)
+                && matches!(name, b"Name" | b"PersonDisplayName" | b"li")
+            {
+                people.push(value.text.trim_ascii());
+            } else if within(&value.path, KEYWORDS)
+                && let Some(person) = person_keyword(value.text)
+            {
+                people.push(person);
+            }
+        }
+        let spans: Vec<_> = values
+            .iter()
+            .filter(|value| {
+                value.path.last() == Some(&&b"rdf:li"[..])
+                    && within(&value.path, KEYWORDS)
+                    && (person_keyword(value.text).is_some()
+                        || people.contains(&value.text.trim_ascii()))
+            })
+            .map(|value| value.span)
+            .collect();
+        packet = xml::remove(&packet, &spans);
+    }
+
+    let rules =
+        Rules { element: is_dropped, attribute: is_dropped, comments: false };
+    Ok(xml::filter(&packet, &rules)?.data)
+}
+
+/// The name at the end of a hierarchical keyword filed under people.
+fn person_keyword(keyword: &[u8]) -> Option<&[u8]> {
+    let mut levels = keyword.trim_ascii().split(|&b| b == b'|' || b == b'/');
+    let top = levels.next()?.trim_ascii().to_ascii_lowercase();
+    let name = levels.next_back()?.trim_ascii();
+    (PEOPLE_KEYWORDS.contains(&&top[..]) && !name.is_empty()).then_some(name)
+}
+
+fn is_date(name: &[u8]) -> bool {
+    DATES.contains(&name)
+}
+
+fn is_redacted(name: &[u8]) -> bool {
+    is_location(name) || IDENTITIES.contains(&name)
+}
+
+fn is_location(name: &[u8]) -> bool {
+    local_name(name).starts_with(b"GPS") || PLACES.contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_names_fields() {
+        let keep = parse_keep("none").unwrap();
+        assert!(!keep.orientation && !keep.icc && !keep.copyright);
+        assert!(!keep.datetime && !keep.colorspace);
+        assert_eq!(parse_keep(" NONE ").unwrap(), keep);
+
+        let keep = parse_keep("icc, Copyright,datetime").unwrap();
+        assert!(keep.icc && keep.copyright && keep.datetime);
+        assert!(!keep.orientation && !keep.colorspace);
+        assert_eq!(keep.strip, Strip::All);
+
+        let keep = parse_keep("orientation,colorspace").unwrap();
+        assert!(keep.orientation && keep.colorspace);
+        assert!(!keep.icc && !keep.copyright && !keep.datetime);
+    }
+
+    #[test]
+    fn keep_rejects_unknown_names() {
+        for s in ["gps", "icc,gps", "", "icc,", "none,icc"] {
+            let err = parse_keep(s).unwrap_err();
+            assert!(err.starts_with("unknown field"), "{s}: {err}");
+        }
+    }
+
+    #[test]
+    fn web_preset_keeps_the_look_and_the_chosen_fields() {
+        let keep = Keep {
+            strip: Strip::Redact,
+            gps_fuzz: Some(Fuzz::Meters(1000)),
+            ..parse_keep("copyright,datetime").unwrap()
+        };
+        let web = Preset::Web.apply(keep);
+        assert!(web.orientation && web.icc && web.colorspace);
+        assert!(web.copyright && web.datetime);
+        assert_eq!(web.strip, Strip::All);
+        assert_eq!(web.gps_fuzz, None);
+
+        let web = Preset::Web.apply(parse_keep("none").unwrap());
+        assert!(web.orientation && web.icc && web.colorspace);
+        assert!(!web.copyright && !web.datetime);
+    }
+}
@@ -14,20 +14,87 @@
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 //
 
+mod access;
+mod blurhash;
+mod bmff;
+mod cache;
+mod date;
+mod diff;
+mod errlog;
+mod estimate;
+mod filter;
+mod format;
+mod gallery;
+mod gif;
+mod gps;
+mod idle;
+mod import;
+mod inspect;
+mod iptc;
+mod jpeg;
+mod jxl;
+mod keep;
+mod meta;
+mod mp4;
+mod pdf;
+mod png;
+mod raw;
+mod sample;
+mod sandbox;
+mod stamp;
+mod summary;
+mod svg;
+mod thumb;
+mod tiff;
+mod timeout;
+mod user;
+mod webp;
+mod xattr;
+mod xml;
+
 use std::{
-    fs,
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsStr,
+    fmt::Write as _,
+    fs, io,
+    os::unix::fs::MetadataExt,
     path::{Path, PathBuf},
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicUsize, Ordering},
     },
+    time::{Duration, Instant},
 };
 
 use anyhow::{Context, anyhow, bail};
-use clap::{ArgAction, Parser};
-use ignore::{WalkBuilder, WalkState};
+use clap::{
+    ArgAction, Args as ClapArgs, CommandFactory, Parser, Subcommand,
+    ValueEnum, error::ErrorKind,
+};
+use ignore::{
+    DirEntry, WalkBuilder, WalkParallel, WalkState,
+    overrides::{Override, OverrideBuilder},
+};
 use log::{LevelFilter, debug, error, info, warn};
 
+use crate::{
+    access::{Inaccessible, PermissionPolicy},
+    date::{Dates, Round},
+    diff::{Change, DryRunDiff},
+    errlog::ErrorLog,
+    filter::Filter,
+    format::{Format, Others, Selection},
+    gps::Fuzz,
+    idle::IdleGate,
+    keep::{IccPolicy, Keep, Preset, Set, Strip},
+    meta::MetaStats,
+    raw::{RawFiles, RawPolicy},
+    sample::{SampleReport, SampleSpec, Sampler},
+    stamp::Stamp,
+    summary::{Processed, Summary},
+    thumb::{Derivatives, Thumbnails},
+};
+
 const VERSION: &str = concat!(
     env!("CARGO_PKG_VERSION"),
     " (",
@@ -39,7 +106,7 @@ const VERSION: &str = concat!(
 
 /// Simple Image metadata cleaner.
 ///
-/// Recursively walks an input directory, removes metadata from JPEG files
+/// Recursively walks an input directory, removes metadata from image files
 /// and writes the cleaned copies into an output directory, preserving the
 /// directory structure.
 #[derive(Debug, Parser)]
@@ -48,29 +115,271 @@ const VERSION: &str = concat!(
     about = "Simple Image metadata cleaner",
     version = VERSION,
     author,
-    propagate_version = true
+    propagate_version = true,
+    subcommand_negates_reqs = true,
+    args_conflicts_with_subcommands = true
 )]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Input directory containing original images
-    #[arg(short, long)]
-    input: PathBuf,
+    #[arg(short, long, required = true)]
+    input: Option<PathBuf>,
 
     /// Output directory where cleaned images will be written
-    #[arg(short, long)]
-    output: PathBuf,
+    #[arg(short, long, required = true)]
+    output: Option<PathBuf>,
 
     /// Number of worker threads for directory walking (0 = auto)
     #[arg(long, default_value_t = 0)]
     num_threads: usize,
 
-    /// Only print what would be done, do not write files
+    /// Also walk thumbnail and preview caches (@eaDir, .thumbnails,
+    /// Lightroom previews, ...), which are skipped by default
+    #[arg(long)]
+    include_derivatives: bool,
+
+    /// Only print what would be done, do not write files.
+    ///
+    /// Existing outputs are compared with what a real run would write, and
+    /// outputs without a matching input are reported as stale.
     #[arg(long)]
     dry_run: bool,
 
-    /// Show size statistics after finishing
+    /// Keep the run out of the page cache: source and output files are
+    /// dropped from it after processing (outputs are flushed first)
+    #[arg(long)]
+    direct_io: bool,
+
+    /// Pause processing while the system load (not counting this run) is
+    /// at or above --idle-load, and resume once it drops
+    #[arg(long)]
+    only_when_idle: bool,
+
+    /// Load average from which --only-when-idle pauses
+    #[arg(
+        long,
+        value_name = "LOAD",
+        default_value_t = 1.0,
+        requires = "only_when_idle"
+    )]
+    idle_load: f64,
+
+    /// Show size statistics and a metadata histogram after finishing
     #[arg(long)]
     stats: bool,
 
+    /// Also write a small JPEG preview of every cleaned image into this
+    /// directory, mirroring the output tree
+    #[arg(long, value_name = "DIR")]
+    thumbnails: Option<PathBuf>,
+
+    /// Longest side of the previews, in pixels
+    #[arg(
+        long,
+        value_name = "PX",
+        default_value_t = 256,
+        requires = "thumbnails"
+    )]
+    thumb_size: u16,
+
+    /// Also write resized copies of every cleaned JPEG at these widths in
+    /// pixels, e.g. 480,960,1920, next to it as name-480w.jpg and so on for
+    /// srcset; images are never upscaled
+    #[arg(
+        long,
+        value_name = "WIDTHS",
+        value_delimiter = ',',
+        value_parser = thumb::parse_width
+    )]
+    derivatives: Vec<u16>,
+
+    /// Refuse to decode images with more pixels than this (width times
+    /// height), such as decompression bombs
+    #[arg(long, value_name = "N")]
+    max_pixels: Option<u64>,
+
+    /// Do not restrict the process (pledge and unveil on OpenBSD, Landlock
+    /// and seccomp on Linux)
+    #[arg(long)]
+    no_pledge: bool,
+
+    /// When started as root, switch to this user and group (names or ids)
+    /// before reading or writing anything
+    #[arg(long, value_name = "USER:GROUP", value_parser = user::parse_run_as)]
+    run_as: Option<user::RunAs>,
+
+    /// Give up on a file that takes longer than this to process (e.g. 30s,
+    /// 2m); it counts as failed and the run goes on
+    #[arg(long, value_name = "DURATION", value_parser = timeout::parse_duration)]
+    file_timeout: Option<Duration>,
+
+    /// Write a static HTML gallery of the output tree to this file
+    #[arg(long, value_name = "FILE")]
+    gallery: Option<PathBuf>,
+
+    /// Write the width, height and aspect ratio of every cleaned JPEG and
+    /// PNG to this JSON file, keyed by output path, for static site
+    /// generators
+    #[arg(long, value_name = "FILE")]
+    dimensions: Option<PathBuf>,
+
+    /// Add a BlurHash placeholder of every cleaned JPEG to --dimensions
+    #[arg(long, requires = "dimensions")]
+    blurhash: bool,
+
+    /// Process paths matching this glob (relative to the input directory)
+    /// before the rest of the tree; may be repeated
+    #[arg(long, value_name = "GLOB")]
+    priority: Vec<String>,
+
+    /// Write the paths of all files that were skipped or failed to this
+    /// file, one per line, so another tool can pick them up
+    #[arg(long, value_name = "FILE")]
+    unsupported_list: Option<PathBuf>,
+
+    /// What to do with files and directories that cannot be read for lack
+    /// of permission; either way they are summarized at the end
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t)]
+    on_permission_error: PermissionPolicy,
+
+    /// EXIF fields and profiles that survive cleaning: none, or some of
+    /// orientation, icc, copyright, datetime and colorspace (the EXIF
+    /// fields in JPEGs only for now)
+    #[arg(
+        long,
+        value_name = "LIST",
+        default_value = "orientation,icc",
+        value_parser = keep::parse_keep
+    )]
+    keep: Keep,
+
+    /// What to remove: all metadata, only the location (GPS data and place
+    /// names), only the maker notes or only the people shown (face regions,
+    /// names and keywords), keeping the rest of the EXIF data
+    #[arg(long, value_enum, value_name = "WHAT", default_value_t)]
+    strip: Strip,
+
+    /// Options for a use: web removes everything private, GPS and serial
+    /// numbers included, and keeps the orientation, colour profile and
+    /// colour space; --keep adds the copyright or dates
+    #[arg(long, value_enum, value_name = "USE", conflicts_with_all = ["strip", "gps_fuzz"])]
+    preset: Option<Preset>,
+
+    /// What to do with colour profiles in JPEG, PNG and WebP files, over
+    /// --keep and --preset; they are kept by default
+    #[arg(long, value_enum, value_name = "POLICY")]
+    icc: Option<IccPolicy>,
+
+    /// Replace identifying values instead of removing whole blocks, for
+    /// tools that expect the EXIF structure: names go blank, serial numbers
+    /// read REDACTED, the GPS data and maker notes are emptied (JPEG only
+    /// for now)
+    #[arg(long, conflicts_with_all = ["strip", "preset"])]
+    redact: bool,
+
+    /// Keep the XMP keywords that do not name a person with --strip people
+    /// and --redact, which remove them all otherwise
+    #[arg(long)]
+    keep_keywords: bool,
+
+    /// Write this attribution into the cleaned files as EXIF and XMP, e.g.
+    /// artist="Jane Doe" or copyright="CC BY 4.0"; may be repeated (JPEG
+    /// only for now)
+    #[arg(
+        long,
+        value_name = "NAME=VALUE",
+        value_parser = keep::parse_set,
+        conflicts_with_all = ["strip", "redact"]
+    )]
+    set: Vec<Set>,
+
+    /// Keep the GPS position, rounded to this precision, instead of
+    /// removing it: a distance (e.g. 1km, 500m) or a number of decimal
+    /// places of a degree; with --strip all or gps, or --redact (JPEG only
+    /// for now)
+    #[arg(long, value_name = "PRECISION", value_parser = gps::parse_fuzz)]
+    gps_fuzz: Option<Fuzz>,
+
+    /// Shift the dates that are kept by this much, e.g. -3d or +2h; a
+    /// number with an s, m, h, d or w unit
+    #[arg(
+        long,
+        value_name = "OFFSET",
+        allow_hyphen_values = true,
+        value_parser = date::parse_offset
+    )]
+    date_offset: Option<i64>,
+
+    /// Round the dates that are kept down to the start of their hour, day,
+    /// month or year
+    #[arg(long, value_enum, value_name = "UNIT")]
+    date_round: Option<Round>,
+
+    /// Remove the dates that would otherwise be kept: the EXIF dates of
+    /// --keep datetime, the EXIF, XMP and IPTC dates of the --strip modes
+    /// keeping them, and the MP4/MOV header times
+    #[arg(long)]
+    date_remove: bool,
+
+    /// Embed a new EXIF thumbnail made from the cleaned image, for apps
+    /// that need one; the original thumbnail is always removed, as it may
+    /// show the image before it was cropped or edited (JPEG only)
+    #[arg(long)]
+    regen_thumbnail: bool,
+
+    /// Keep the data appended after the end of JPEG, PNG, WebP and GIF
+    /// images, such as motion photo videos, which is removed otherwise
+    #[arg(long)]
+    keep_trailer: bool,
+
+    /// Write the video of Google and Samsung motion photos, with its own
+    /// metadata removed, next to the cleaned image as name.motion.mp4
+    /// before it is stripped from the image
+    #[arg(long)]
+    extract_motion_video: bool,
+
+    /// Only clean these formats (e.g. jpeg,png,webp), skipping the rest;
+    /// `all` for every supported one
+    #[arg(
+        long,
+        value_name = "LIST",
+        default_value = "all",
+        value_parser = format::parse_selection
+    )]
+    formats: Selection,
+
+    /// Only clean the files whose EXIF data matches this expression, e.g.
+    /// 'Model == "iPhone 15" && has(GPS) && DateTimeOriginal > 2024-01-01';
+    /// the others count as skipped
+    #[arg(long, value_name = "EXPR", value_parser = filter::parse_filter)]
+    filter: Option<Filter>,
+
+    /// What to do with camera RAW files (DNG, CR2, NEF, ARW, ...), which
+    /// are not cleaned except for DNG
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t)]
+    raw: RawPolicy,
+
+    /// What to do with the files that are not cleaned, because their
+    /// format is not supported or not picked by --formats
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t)]
+    others: Others,
+
+    /// Clean a random sample of the files in memory (e.g. 1%) and
+    /// extrapolate the totals, without writing anything
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        value_parser = sample::parse_percent,
+        conflicts_with = "sample_count"
+    )]
+    sample: Option<f64>,
+
+    /// Like --sample, but pick a fixed number of files
+    #[arg(long, value_name = "N")]
+    sample_count: Option<usize>,
+
     /// Increase verbosity (use -v, -vv, ...).
     ///
     /// When no RUST_LOG is set, a single -v switches the log level to DEBUG.
@@ -78,129 +387,372 @@ struct Args {
     verbose: u8,
 }
 
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Read image headers only and report metadata and predicted sizes
+    Estimate(EstimateArgs),
+    /// Print the EXIF metadata of images
+    Inspect(InspectArgs),
+    /// Clean the photos of a memory card into an archive sorted by date
+    Import(ImportArgs),
+}
+
+#[derive(Debug, ClapArgs)]
+struct EstimateArgs {
+    /// Input directory containing original images
+    #[arg(short, long)]
+    input: PathBuf,
+
+    /// Number of worker threads for directory walking (0 = auto)
+    #[arg(long, default_value_t = 0)]
+    num_threads: usize,
+
+    /// Also walk thumbnail and preview caches (@eaDir, .thumbnails,
+    /// Lightroom previews, ...), which are skipped by default
+    #[arg(long)]
+    include_derivatives: bool,
+}
+
+#[derive(Debug, ClapArgs)]
+struct InspectArgs {
+    /// Image files, or directories to scan for images
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Print JSON in the layout of `exiftool -j -n`
+    #[arg(long)]
+    json: bool,
+
+    /// Prefix tag names with their group, like `exiftool -G1`
+    #[arg(short = 'G', long)]
+    groups: bool,
+}
+
+#[derive(Debug, ClapArgs)]
+struct ImportArgs {
+    /// Mounted memory card (or any directory with a DCIM folder)
+    #[arg(long)]
+    from: PathBuf,
+
+    /// Archive directory; images go to YYYY/YYYY-MM-DD/ below it
+    #[arg(long)]
+    to: PathBuf,
+}
+
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    // clap conflicts go by argument, these modes leave the GPS data alone
+    if args.gps_fuzz.is_some()
+        && !args.redact
+        && matches!(args.strip, Strip::Makernotes | Strip::People)
+    {
+        let strip = args.strip.to_possible_value().expect("not skipped");
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "the argument '--gps-fuzz <PRECISION>' cannot be used \
+                     with '--strip {}'",
+                    strip.get_name()
+                ),
+            )
+            .exit();
+    }
 
     init_logger(args.verbose);
+    let stamp = Stamp::start(VERSION);
 
-    if !args.input.is_dir() {
-        bail!("input path '{}' is not directory", args.input.display());
+    match &args.command {
+        Some(Command::Estimate(est)) => {
+            return estimate::run(
+                &est.input,
+                est.num_threads,
+                est.include_derivatives,
+                &stamp,
+            );
+        }
+        Some(Command::Inspect(ins)) => {
+            return inspect::run(&ins.paths, ins.json, ins.groups);
+        }
+        Some(Command::Import(imp)) => {
+            return import::run(&imp.from, &imp.to);
+        }
+        None => {}
     }
 
-    if !args.output.exists() {
-        fs::create_dir_all(&args.output).with_context(|| {
-            format!("failed to create output dir '{}'", args.output.display())
+    // both are required by clap when no subcommand is given
+    let input = args.input.expect("input is required");
+    let output = args.output.expect("output is required");
+
+    if let Some(to) = args.run_as {
+        user::drop_to(to).context("failed to drop privileges")?;
+        info!("running as uid {} gid {}", to.uid, to.gid);
+    }
+
+    if !input.is_dir() {
+        bail!("input path '{}' is not directory", input.display());
+    }
+
+    let sample_spec = match (args.sample, args.sample_count) {
+        (Some(ratio), _) => Some(SampleSpec::Ratio(ratio)),
+        (None, Some(count)) => Some(SampleSpec::Count(count)),
+        (None, None) => None,
+    };
+
+    if sample_spec.is_some() {
+        // sampling never writes, so the output dir is left alone
+    } else if !output.exists() {
+        fs::create_dir_all(&output).with_context(|| {
+            format!("failed to create output dir '{}'", output.display())
         })?;
-    } else if !args.output.is_dir() {
+    } else if !output.is_dir() {
         bail!(
             "output path '{}' exists but is not directory",
-            args.output.display()
+            output.display()
         );
     }
 
-    info!("input directory: {}", args.input.display());
-    info!("output directory: {}", args.output.display());
+    stamp.log_start();
+    info!("input directory: {}", input.display());
+    info!("output directory: {}", output.display());
     info!("threads : {}", args.num_threads);
     if args.dry_run {
         info!("running in DRY_RUN mode");
     }
+    if let Some(spec) = sample_spec {
+        info!("running in SAMPLE mode: {spec:?}");
+    }
+    if args.only_when_idle && !idle::SUPPORTED {
+        warn!("--only-when-idle has no effect on this platform");
+    }
+    if args.direct_io && !cache::SUPPORTED {
+        warn!("--direct-io has no effect on this platform");
+    }
 
-    let input_root = Arc::new(args.input);
-    let output_root = Arc::new(args.output);
-    let dry_run = args.dry_run;
+    // dry runs and samples write no images, so no thumbnails, gallery or
+    // dimensions either; only a dry run lists the unsupported files
+    let writes = sample_spec.is_none() && !args.dry_run;
 
-    // counter
-    let processed = Arc::new(AtomicUsize::new(0));
-    let skipped = Arc::new(AtomicUsize::new(0));
-    let failed = Arc::new(AtomicUsize::new(0));
+    // created up front for the overlap check, and because unveil and
+    // Landlock want the directories they are given to exist
+    if let Some(dir) = args.thumbnails.as_ref().filter(|_| writes) {
+        fs::create_dir_all(dir).with_context(|| {
+            format!("failed to create thumbnail dir '{}'", dir.display())
+        })?;
+    }
 
-    // stats
-    let total_before = Arc::new(AtomicUsize::new(0));
-    let total_after = Arc::new(AtomicUsize::new(0));
+    let mut outputs = Vec::new();
+    if sample_spec.is_none() {
+        outputs.push(output.as_path());
+    }
+    outputs.extend(args.thumbnails.as_deref().filter(|dir| dir.exists()));
+    let excluded = excluded_outputs(&input, &outputs)?;
 
-    let walker = WalkBuilder::new(&*input_root)
-        .hidden(false)
-        .follow_links(false)
-        .standard_filters(true)
-        .threads(args.num_threads)
-        .build_parallel();
-
-    walker.run(|| {
-        let input_root = Arc::clone(&input_root);
-        let output_root = Arc::clone(&output_root);
-        let processed = Arc::clone(&processed);
-        let skipped = Arc::clone(&skipped);
-        let failed = Arc::clone(&failed);
-        let total_before = Arc::clone(&total_before);
-        let total_after = Arc::clone(&total_after);
-
-        Box::new(move |result| {
-            match result {
-                Ok(entry) => {
-                    let path = entry.path();
-
-                    // regular file
-                    if !entry
-                        .file_type()
-                        .map(|ft| ft.is_file())
-                        .unwrap_or(false)
-                    {
-                        return WalkState::Continue;
-                    }
+    if sandbox::SUPPORTED && !args.no_pledge {
+        let mut write = outputs.clone();
+        let mut files = Vec::new();
+        if writes {
+            files.extend([&args.gallery, &args.dimensions]);
+        }
+        if sample_spec.is_none() {
+            files.push(&args.unsupported_list);
+        }
+        for file in files.into_iter().flatten() {
+            create_file(file)?;
+            write.push(file);
+        }
 
-                    let ext = path
-                        .extension()
-                        .and_then(|s| s.to_str())
-                        .map(|s| s.to_ascii_lowercase());
+        match sandbox::restrict(&input, &write) {
+            Ok(()) => debug!(
+                "restricted to reading '{}' and writing {} outputs",
+                input.display(),
+                write.len()
+            ),
+            Err(err) if err.kind() == io::ErrorKind::Unsupported => {
+                warn!("{err}, file access is not restricted")
+            }
+            Err(err) => {
+                return Err(err).context("failed to restrict the process");
+            }
+        }
+    }
 
-                    let is_jpeg =
-                        matches!(ext.as_deref(), Some("jpg" | "jpeg"));
+    let keep = Keep {
+        strip: if args.redact { Strip::Redact } else { args.strip },
+        gps_fuzz: args.gps_fuzz,
+        dates: Dates {
+            offset: args.date_offset.unwrap_or(0),
+            round: args.date_round,
+            remove: args.date_remove,
+        },
+        regen_thumbnail: args.regen_thumbnail,
+        max_pixels: args.max_pixels,
+        set: args.set.into_iter().fold(Set::default(), Set::merge),
+        trailer: args.keep_trailer,
+        keywords: args.keep_keywords,
+        ..args.keep
+    };
+    let mut keep = match args.preset {
+        Some(preset) => preset.apply(keep),
+        None => keep,
+    };
+    if let Some(icc) = args.icc {
+        keep.icc = icc == IccPolicy::Keep;
+    }
 
-                    if !is_jpeg {
-                        skipped.fetch_add(1, Ordering::Relaxed);
-                        return WalkState::Continue;
-                    }
+    let run = Arc::new(Run {
+        input_root: input,
+        output_root: output,
+        dry_run: args.dry_run.then(DryRunDiff::default),
+        metadata: args.stats.then(Mutex::default),
+        sampler: sample_spec.map(|spec| Mutex::new(Sampler::new(spec))),
+        thumbnails: args.thumbnails.clone().map(|dir| Thumbnails {
+            dir,
+            size: args.thumb_size,
+            max_pixels: args.max_pixels,
+        }),
+        derivatives: (!args.derivatives.is_empty())
+            .then(|| Derivatives::new(&args.derivatives, args.max_pixels)),
+        unsupported: args.unsupported_list.is_some().then(Mutex::default),
+        dimensions: args.dimensions.is_some().then(Mutex::default),
+        blurhash: args.blurhash,
+        extract_motion_video: args.extract_motion_video,
+        on_permission_error: args.on_permission_error,
+        raw: args.raw,
+        formats: args.formats,
+        filter: args.filter,
+        others: args.others,
+        keep,
+        direct_io: args.direct_io && cache::SUPPORTED,
+        idle: args.only_when_idle.then(|| IdleGate::new(args.idle_load)),
+        file_timeout: args.file_timeout,
+        include_derivatives: args.include_derivatives,
+        excluded,
+        ..Default::default()
+    });
 
-                    match process_img(
-                        &input_root,
-                        &output_root,
-                        path,
-                        dry_run,
-                        &total_before,
-                        &total_after,
-                    ) {
-                        Ok(()) => {
-                            processed.fetch_add(1, Ordering::Relaxed);
-                        }
-                        Err(err) => {
-                            failed.fetch_add(1, Ordering::Relaxed);
-                            error!(
-                                "failed to process '{}': {err:#}",
-                                path.display()
-                            );
-                        }
-                    }
-                }
-                Err(err) => {
-                    failed.fetch_add(1, Ordering::Relaxed);
-                    error!("walk error: {err}");
-                }
+    for overrides in priority_passes(&run.input_root, &args.priority)? {
+        let mut builder = walk_builder(&run.input_root, args.num_threads);
+        if let Some(overrides) = overrides {
+            builder.overrides(overrides);
+        }
+
+        builder.build_parallel().run(|| {
+            let run = Arc::clone(&run);
+            Box::new(move |result| visit(&run, result))
+        });
+    }
+
+    // files that timed out may still hold the run, so it stays shared
+    if run.sampler.is_some() {
+        // sampling processes nothing, the walker threads were the only users
+        let run = Arc::into_inner(run).expect("walker threads have finished");
+        let sampler = run.sampler.expect("sampling was asked for");
+        let threads = match args.num_threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+
+        run_sample(sampler.into_inner().unwrap(), &run.keep)
+            .print(threads, &stamp);
+        return Ok(());
+    }
+
+    if let Some(diff) = &run.dry_run {
+        diff.log_summary(&diff::stale_outputs(
+            &run.input_root,
+            &run.output_root,
+            &args.derivatives,
+            args.extract_motion_video,
+        ));
+    }
+
+    if let Some(gallery) = &args.gallery
+        && run.dry_run.is_none()
+    {
+        let thumbs = run.thumbnails.as_ref().map(|t| t.dir.as_path());
+        match gallery::write(gallery, &run.output_root, thumbs) {
+            Ok(count) => {
+                info!("gallery: {count} images in '{}'", gallery.display())
             }
+            Err(err) => error!("failed to write gallery: {err:#}"),
+        }
+    }
 
-            WalkState::Continue
-        })
-    });
+    if let (Some(list), Some(paths)) =
+        (&args.unsupported_list, &run.unsupported)
+    {
+        let mut paths = std::mem::take(&mut *paths.lock().unwrap());
+        paths.sort();
+        match write_list(list, &paths) {
+            Ok(()) => info!(
+                "unsupported: {} paths in '{}'",
+                paths.len(),
+                list.display()
+            ),
+            Err(err) => error!("failed to write unsupported list: {err:#}"),
+        }
+    }
+
+    if let (Some(file), Some(sizes)) = (&args.dimensions, &run.dimensions)
+        && run.dry_run.is_none()
+    {
+        let sizes = sizes.lock().unwrap();
+        match write_dimensions(file, &sizes) {
+            Ok(()) => info!(
+                "dimensions: {} images in '{}'",
+                sizes.len(),
+                file.display()
+            ),
+            Err(err) => error!("failed to write dimensions: {err:#}"),
+        }
+    }
+
+    run.errors.log_summary();
+    run.inaccessible.log_summary();
+    run.raw_files.log_summary();
+
+    let previews = run.previews_skipped.lock().unwrap().len();
+    if previews > 0 {
+        info!(
+            "preview caches: skipped {previews} directories \
+             (--include-derivatives cleans them too)"
+        );
+    }
+
+    let trailers = run.trailers.load(Ordering::Relaxed);
+    if trailers > 0 {
+        info!(
+            "trailers: {trailers} files had data after the image ({})",
+            if run.keep.trailer { "kept, --keep-trailer" } else { "removed" }
+        );
+    }
 
+    let motion_videos = run.motion_videos.load(Ordering::Relaxed);
+    if motion_videos > 0 {
+        info!("motion photos: extracted {motion_videos} videos");
+    }
+
+    let filtered = run.filtered.load(Ordering::Relaxed);
+    if filtered > 0 {
+        info!("filter: {filtered} files did not match --filter");
+    }
+
+    let failed = run.failed.load(Ordering::Relaxed);
+
+    stamp.log_end();
     info!(
-        "done: processed={} skipped={} failed={}",
-        processed.load(Ordering::Relaxed),
-        skipped.load(Ordering::Relaxed),
-        failed.load(Ordering::Relaxed),
+        "done: processed={} (already clean={}) skipped={} failed={}",
+        run.processed.load(Ordering::Relaxed),
+        run.already_clean.load(Ordering::Relaxed),
+        run.skipped.load(Ordering::Relaxed),
+        failed,
     );
+    run.summary.lock().unwrap().log();
 
-    if total_before.load(Ordering::Relaxed) > 0 && args.stats {
-        let before = total_before.load(Ordering::Relaxed) as f64;
-        let after = total_after.load(Ordering::Relaxed) as f64;
+    if run.total_before.load(Ordering::Relaxed) > 0 && args.stats {
+        let before = run.total_before.load(Ordering::Relaxed) as f64;
+        let after = run.total_after.load(Ordering::Relaxed) as f64;
 
         let saved = before - after;
         let saved_pct =
@@ -208,8 +760,9 @@ fn main() -> anyhow::Result<()> {
 
         println!();
         println!("Stats:");
+        stamp.print();
         println!("Source total: {:.2} MB", before / (1024.0 * 1024.0));
-        if !dry_run {
+        if run.dry_run.is_none() {
             println!("Clean total: {:.2} MB", after / (1024.0 * 1024.0));
             println!(
                 "Saved: {:.2} MB ({:.1}%)",
@@ -219,40 +772,566 @@ fn main() -> anyhow::Result<()> {
         } else {
             println!("Clean total: (DRY-RUN) skipped");
         }
+        if let Some(metadata) = &run.metadata {
+            metadata.lock().unwrap().print();
+        }
         println!();
     }
 
-    if failed.load(Ordering::Relaxed) > 0 {
+    if failed > 0 {
         warn!("some files failed to process");
     }
 
     Ok(())
 }
 
-fn process_img(
-    input_root: &Path,
-    output_root: &Path,
-    src: &Path,
-    dry_run: bool,
-    total_before: &AtomicUsize,
-    total_after: &AtomicUsize,
+/// State shared by the walker threads of a cleaning run.
+#[derive(Debug, Default)]
+struct Run {
+    input_root: PathBuf,
+    output_root: PathBuf,
+    dry_run: Option<DryRunDiff>,
+    metadata: Option<Mutex<MetaStats>>,
+    sampler: Option<Mutex<Sampler>>,
+    thumbnails: Option<Thumbnails>,
+    derivatives: Option<Derivatives>,
+    direct_io: bool,
+    idle: Option<IdleGate>,
+    file_timeout: Option<Duration>,
+    include_derivatives: bool,
+    /// Output directories inside the input tree, left out of the walk.
+    excluded: Vec<PathBuf>,
+    /// Preview cache directories left out of the walk.
+    previews_skipped: Mutex<BTreeSet<PathBuf>>,
+    /// Files that were skipped or failed, for --unsupported-list.
+    unsupported: Option<Mutex<Vec<PathBuf>>>,
+    /// Width and height of the cleaned images, for --dimensions.
+    dimensions: Option<Mutex<BTreeMap<PathBuf, Dimensions>>>,
+    blurhash: bool,
+    extract_motion_video: bool,
+    errors: ErrorLog,
+    on_permission_error: PermissionPolicy,
+    inaccessible: Inaccessible,
+    raw: RawPolicy,
+    /// RAW files reported by --raw report or clean-dng.
+    raw_files: RawFiles,
+    formats: Selection,
+    filter: Option<Filter>,
+    others: Others,
+    keep: Keep,
+
+    // counter
+    processed: AtomicUsize,
+    /// Files left out by --filter, also counted as skipped.
+    filtered: AtomicUsize,
+    /// JPEGs with data after the end of the image.
+    trailers: AtomicUsize,
+    /// Videos written by --extract-motion-video.
+    motion_videos: AtomicUsize,
+    already_clean: AtomicUsize,
+    skipped: AtomicUsize,
+    failed: AtomicUsize,
+
+    // stats
+    total_before: AtomicUsize,
+    total_after: AtomicUsize,
+    summary: Mutex<Summary>,
+}
+
+impl Run {
+    fn in_preview_cache(&self, path: &Path) -> bool {
+        let Ok(rel) = path.strip_prefix(&self.input_root) else {
+            return false;
+        };
+        self.include_derivatives && rel.ancestors().any(is_derivative_dir)
+    }
+
+    /// Record a path that could not be read; true when it is to be skipped
+    /// rather than counted as failed, as it is when an earlier pass over
+    /// the tree counted it already.
+    fn denied(&self, path: &Path) -> bool {
+        !self.inaccessible.record(path)
+            || self.on_permission_error == PermissionPolicy::Skip
+    }
+
+    /// Path of `src` relative to the input, which is also where it goes
+    /// in the output tree.
+    fn rel_path(&self, src: &Path) -> anyhow::Result<PathBuf> {
+        match src.strip_prefix(&self.input_root) {
+            Ok(rel) => Ok(rel.to_path_buf()),
+            Err(_) => src.file_name().map(PathBuf::from).ok_or_else(|| {
+                anyhow!(
+                    "could not compute relative path for '{}'",
+                    src.display()
+                )
+            }),
+        }
+    }
+
+    fn unsupported(&self, path: &Path) {
+        if let Some(paths) = &self.unsupported {
+            paths.lock().unwrap().push(path.to_path_buf());
+        }
+    }
+
+    fn record(&self, format: &'static str, done: &Processed) {
+        self.processed.fetch_add(1, Ordering::Relaxed);
+        if done.already_clean {
+            self.already_clean.fetch_add(1, Ordering::Relaxed);
+        }
+        self.total_before.fetch_add(done.before, Ordering::Relaxed);
+        self.total_after.fetch_add(done.after, Ordering::Relaxed);
+        self.summary.lock().unwrap().record(format, done);
+    }
+}
+
+fn write_list(list: &Path, paths: &[PathBuf]) -> anyhow::Result<()> {
+    let mut out = Vec::new();
+    for path in paths {
+        out.extend_from_slice(path.as_os_str().as_encoded_bytes());
+        out.push(b'\n');
+    }
+
+    fs::write(list, out)
+        .with_context(|| format!("failed to write '{}'", list.display()))
+}
+
+/// What --dimensions records of a cleaned image.
+#[derive(Debug)]
+struct Dimensions {
+    width: u32,
+    height: u32,
+    /// With --blurhash, for JPEGs.
+    blurhash: Option<String>,
+}
+
+/// Write `sizes` as a JSON object of `{"width", "height", "aspect"}` by
+/// path, with a `"blurhash"` where there is one.
+fn write_dimensions(
+    file: &Path,
+    sizes: &BTreeMap<PathBuf, Dimensions>,
 ) -> anyhow::Result<()> {
-    let rel_path = match src.strip_prefix(input_root) {
-        Ok(rel) => rel.to_path_buf(),
-        Err(_) => src.file_name().map(PathBuf::from).ok_or_else(|| {
-            anyhow!("could not compute relative path for '{}'", src.display())
-        })?,
+    let mut out = String::from("{");
+    for (i, (path, size)) in sizes.iter().enumerate() {
+        let Dimensions { width, height, blurhash } = size;
+        out.push_str(if i == 0 { "\n  " } else { ",\n  " });
+        inspect::write_json_string(&mut out, &path.to_string_lossy());
+        let aspect = *width as f64 / (*height).max(1) as f64;
+        let _ = write!(
+            out,
+            ": {{\"width\": {width}, \"height\": {height}, \
+             \"aspect\": {aspect:.4}"
+        );
+        if let Some(blurhash) = blurhash {
+            out.push_str(", \"blurhash\": ");
+            inspect::write_json_string(&mut out, blurhash);
+        }
+        out.push('}');
+    }
+    out.push_str("\n}\n");
+
+    fs::write(file, out)
+        .with_context(|| format!("failed to write '{}'", file.display()))
+}
+
+/// Create the file at `path`, and its parent directory, so that writing it
+/// can be allowed without allowing the directory; an existing file is left
+/// as it is.
+fn create_file(path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent).with_context(|| {
+            format!("failed to create parent dir '{}'", parent.display())
+        })?;
+    }
+    fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .with_context(|| format!("failed to create '{}'", path.display()))?;
+    Ok(())
+}
+
+/// Handle one entry of the input walk.
+fn visit(
+    run: &Arc<Run>,
+    result: Result<DirEntry, ignore::Error>,
+) -> WalkState {
+    match result {
+        Ok(entry) => {
+            let path = entry.path();
+
+            if run.excluded.iter().any(|dir| dir == path) {
+                debug!("skipping output dir '{}'", path.display());
+                return WalkState::Skip;
+            }
+
+            if is_derivative_entry(&entry) {
+                if run.include_derivatives {
+                    debug!("preview cache '{}'", path.display());
+                } else {
+                    debug!("skipping preview cache '{}'", path.display());
+                    run.previews_skipped.lock().unwrap().insert(path.into());
+                    return WalkState::Skip;
+                }
+            }
+
+            // regular file
+            if !entry.file_type().map(|ft| ft.is_file()).unwrap_or(false) {
+                return WalkState::Continue;
+            }
+
+            let ext = path
+                .extension()
+                .and_then(|s| s.to_str())
+                .map(|s| s.to_ascii_lowercase());
+
+            // most RAW formats are TIFF inside, so those go by extension
+            let raw_ext = ext.as_deref().filter(|e| raw::is_raw(e));
+            let format = match raw_ext {
+                Some(ext) if ext != "dng" => None,
+                _ => Format::detect(path, &format::read_head(path)),
+            };
+            let format = format.filter(|&f| {
+                run.formats.contains(f)
+                    && (f != Format::Dng || run.raw == RawPolicy::CleanDng)
+            });
+            let Some(format) = format else {
+                if let Some(ext) = raw_ext
+                    && run.raw != RawPolicy::Skip
+                {
+                    debug!("camera RAW '{}' not cleaned", path.display());
+                    run.raw_files.record(ext);
+                }
+
+                run.skipped.fetch_add(1, Ordering::Relaxed);
+                run.summary.lock().unwrap().record_skipped(ext.as_deref());
+                run.unsupported(path);
+                if run.others == Others::Copy
+                    && run.sampler.is_none()
+                    && let Err(err) = copy_through(run, path)
+                {
+                    run.errors.error(
+                        &errlog::cause(&err),
+                        format_args!(
+                            "failed to copy '{}': {err:#}",
+                            path.display()
+                        ),
+                    );
+                }
+                return WalkState::Continue;
+            };
+
+            if Format::from_path(path) != Some(format) {
+                debug!("'{}' holds {format} data", path.display());
+                let mut summary = run.summary.lock().unwrap();
+                summary.record_mismatch(ext.as_deref(), format);
+            }
+
+            if let Some(filter) = &run.filter
+                && !matches_filter(filter, path, format)
+            {
+                debug!("'{}' does not match --filter", path.display());
+                run.filtered.fetch_add(1, Ordering::Relaxed);
+                run.skipped.fetch_add(1, Ordering::Relaxed);
+                return WalkState::Continue;
+            }
+
+            if let Some(sampler) = &run.sampler {
+                sampler.lock().unwrap().offer(path);
+                return WalkState::Continue;
+            }
+
+            let _active = run.idle.as_ref().map(IdleGate::enter);
+
+            // reported apart, so forgotten full-size previews stand out
+            let name = if run.in_preview_cache(path) {
+                format.preview_name()
+            } else {
+                format.name()
+            };
+
+            match process_timed(run, path, format) {
+                Ok(done) => run.record(name, &done),
+                Err(err) => {
+                    run.unsupported(path);
+                    if access::is_permission_denied(&err) && run.denied(path) {
+                        debug!("skipped '{}': {err:#}", path.display());
+                        return WalkState::Continue;
+                    }
+
+                    run.failed.fetch_add(1, Ordering::Relaxed);
+                    run.summary.lock().unwrap().record_failed(name);
+                    run.errors.error(
+                        &errlog::cause(&err),
+                        format_args!(
+                            "failed to process '{}': {err:#}",
+                            path.display()
+                        ),
+                    );
+                }
+            }
+        }
+        Err(err) => {
+            if let Some(path) = access::walk_denied_path(&err)
+                && run.denied(path)
+            {
+                debug!("skipped: {err}");
+                return WalkState::Continue;
+            }
+
+            run.failed.fetch_add(1, Ordering::Relaxed);
+            run.errors.error(
+                &errlog::walk_cause(&err),
+                format_args!("walk error: {err}"),
+            );
+        }
+    }
+
+    WalkState::Continue
+}
+
+/// Whether the EXIF data of `path` matches `filter`; a file whose headers
+/// cannot be read has none.
+fn matches_filter(filter: &Filter, path: &Path, format: Format) -> bool {
+    match format.scan_file(path) {
+        Ok((_, info)) => filter.matches(info.exif),
+        Err(err) => {
+            debug!("failed to read EXIF of '{}': {err:#}", path.display());
+            filter.matches(None)
+        }
+    }
+}
+
+/// Parallel walker over `root` honouring the usual ignore files.
+fn walker(
+    root: &Path,
+    num_threads: usize,
+    include_derivatives: bool,
+) -> WalkParallel {
+    let mut builder = walk_builder(root, num_threads);
+    if !include_derivatives {
+        builder.filter_entry(|entry| !is_derivative_entry(entry));
+    }
+    builder.build_parallel()
+}
+
+fn walk_builder(root: &Path, num_threads: usize) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .hidden(false)
+        .follow_links(false)
+        .standard_filters(true)
+        .threads(num_threads);
+    builder
+}
+
+/// Directories of previews that NAS indexers, thumbnailers and photo
+/// managers generate next to the images they were made from.
+const DERIVATIVE_DIRS: &[&str] = &[
+    // freedesktop and Android thumbnail caches
+    ".thumbnails",
+    // Synology
+    "@eaDir",
+    // QNAP
+    ".@__thumb",
+    // macOS resource forks on network shares
+    ".AppleDouble",
+];
+
+fn is_derivative_dir(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
     };
+    let parent = path.parent().and_then(Path::file_name);
 
-    let dst = output_root.join(rel_path);
+    DERIVATIVE_DIRS.contains(&name)
+        // Lightroom "<catalog> Previews.lrdata" and smart previews
+        || name.ends_with(".lrdata")
+        // Capture One session and catalog caches
+        || (name == "Cache" && parent == Some(OsStr::new("CaptureOne")))
+}
+
+fn is_derivative_entry(entry: &DirEntry) -> bool {
+    entry.depth() > 0
+        && entry.file_type().is_some_and(|ft| ft.is_dir())
+        && is_derivative_dir(entry.path())
+}
+
+/// Output directories inside `input`, as the walker will come across them,
+/// so a run does not clean what it wrote itself. An output holding the
+/// input is an error, since cleaned files could land on the originals.
+fn excluded_outputs(
+    input: &Path,
+    outputs: &[&Path],
+) -> anyhow::Result<Vec<PathBuf>> {
+    let canonical = |path: &Path| {
+        fs::canonicalize(path)
+            .with_context(|| format!("failed to resolve '{}'", path.display()))
+    };
+    let root = canonical(input)?;
+    let mut excluded = Vec::new();
+
+    for &dir in outputs {
+        let resolved = canonical(dir)?;
+        if root.starts_with(&resolved) {
+            bail!(
+                "output directory '{}' holds the input directory '{}', \
+                 cleaned files could overwrite the originals",
+                dir.display(),
+                input.display()
+            );
+        }
+        if let Ok(rel) = resolved.strip_prefix(&root) {
+            warn!(
+                "output directory '{}' is inside the input, leaving it out",
+                dir.display()
+            );
+            excluded.push(input.join(rel));
+        }
+    }
+
+    Ok(excluded)
+}
+
+/// Filters for each pass over the input tree.
+///
+/// Without priority globs the tree is walked once. Otherwise the matching
+/// paths are walked first and everything else in a second pass.
+fn priority_passes(
+    root: &Path,
+    globs: &[String],
+) -> anyhow::Result<Vec<Option<Override>>> {
+    if globs.is_empty() {
+        return Ok(vec![None]);
+    }
+
+    let build = |negate: bool| {
+        let mut builder = OverrideBuilder::new(root);
+        for glob in globs {
+            let glob = if negate { format!("!{glob}") } else { glob.clone() };
+            builder.add(&glob).with_context(|| {
+                format!("invalid --priority glob '{glob}'")
+            })?;
+        }
+        builder.build().context("invalid --priority globs")
+    };
+
+    Ok(vec![Some(build(false)?), Some(build(true)?)])
+}
+
+/// Clean the video of a motion photo cleaned to `dst` and write it next to
+/// it, for --extract-motion-video.
+fn write_motion_video(
+    dst: &Path,
+    video: &[u8],
+    keep: &Keep,
+    deadline: Option<Instant>,
+) -> anyhow::Result<()> {
+    let video = mp4::strip_metadata(video, &keep.dates)
+        .context("failed to clean metadata of the video")?;
+    timeout::check(deadline)?;
+    let path = jpeg::motion_video_path(dst);
+    fs::write(&path, video)
+        .with_context(|| format!("failed to write '{}'", path.display()))?;
+    debug!("motion video '{}'", path.display());
+    Ok(())
+}
 
-    if dry_run {
-        debug!(
-            "dry-run: would clean '{}' -> '{}'",
+/// `process_img` under `--file-timeout`, if one was given.
+fn process_timed(
+    run: &Arc<Run>,
+    src: &Path,
+    format: Format,
+) -> anyhow::Result<Processed> {
+    let Some(limit) = run.file_timeout else {
+        return process_img(run, src, format, None);
+    };
+
+    let (run, src) = (Arc::clone(run), src.to_path_buf());
+    timeout::run(limit, move |deadline| {
+        process_img(&run, &src, format, Some(deadline))
+    })
+}
+
+/// Copy a file that is not cleaned to the output as it is, for
+/// `--others copy`.
+fn copy_through(run: &Run, src: &Path) -> anyhow::Result<()> {
+    let dst = run.output_root.join(run.rel_path(src)?);
+
+    if run.dry_run.is_some() {
+        debug!("dry-run: would copy '{}' unchanged", src.display());
+        return Ok(());
+    }
+
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent).with_context(|| {
+            format!("failed to create parent dir '{}'", parent.display())
+        })?;
+    }
+    check_not_same(src, &dst)?;
+    fs::copy(src, &dst)
+        .with_context(|| format!("failed to copy to '{}'", dst.display()))?;
+    debug!("copied through '{}' -> '{}'", src.display(), dst.display());
+
+    Ok(())
+}
+
+/// Fail when `dst` is `src` under another name, through a hard link, a
+/// bind mount or overlapping trees: writing it would truncate the source
+/// while it is still needed.
+fn check_not_same(src: &Path, dst: &Path) -> anyhow::Result<()> {
+    let dst_meta = match fs::metadata(dst) {
+        Ok(meta) => meta,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => {
+            return Err(err).with_context(|| {
+                format!("failed to stat '{}'", dst.display())
+            });
+        }
+    };
+    let src_meta = fs::metadata(src)
+        .with_context(|| format!("failed to stat '{}'", src.display()))?;
+
+    if (src_meta.dev(), src_meta.ino()) == (dst_meta.dev(), dst_meta.ino()) {
+        bail!(
+            "'{}' is the same file as the source, not written",
+            dst.display()
+        );
+    }
+    Ok(())
+}
+
+fn process_img(
+    run: &Run,
+    src: &Path,
+    format: Format,
+    deadline: Option<Instant>,
+) -> anyhow::Result<Processed> {
+    let rel_path = run.rel_path(src)?;
+    let dst = run.output_root.join(&rel_path);
+
+    if let Some(diff) = &run.dry_run {
+        let change = diff.check(&dst, || {
+            let data = fs::read(src).with_context(|| {
+                format!("failed to read '{}'", src.display())
+            })?;
+            Ok(clean_img(src, &data, format, &run.keep)?.unwrap_or(data))
+        })?;
+
+        let msg = format!(
+            "dry-run: would {change} '{}' -> '{}'",
             src.display(),
             dst.display()
         );
-        return Ok(());
+        if change == Change::Update {
+            info!("{msg}")
+        } else {
+            debug!("{msg}")
+        }
+
+        return Ok(Processed::default());
     }
 
     if let Some(parent) = dst.parent() {
@@ -261,27 +1340,251 @@ fn process_img(
         })?;
     }
 
-    let data = fs::read(src)
-        .with_context(|| format!("failed to read '{}'", src.display()))?;
+    let (before, info) = format.scan_file(src)?;
+    let has_metadata = !run.keep.unchanged(info.metadata_bytes);
+    if info.trailer_bytes > 0 {
+        run.trailers.fetch_add(1, Ordering::Relaxed);
+    }
 
-    let src_metadata = fs::metadata(src)
-        .with_context(|| format!("failed to stat '{}'", src.display()))?;
+    if let Some(metadata) = &run.metadata {
+        metadata.lock().unwrap().record_headers(info);
+    }
 
-    total_before.fetch_add(src_metadata.len() as usize, Ordering::Relaxed);
+    let data =
+        if has_metadata {
+            Some(fs::read(src).with_context(|| {
+                format!("failed to read '{}'", src.display())
+            })?)
+        } else {
+            None
+        };
+    let cleaned = match &data {
+        Some(data) => strip(src, data, format, &run.keep)?,
+        None => None,
+    };
+    let already_clean = cleaned.is_none();
+    timeout::check(deadline)?;
+    check_not_same(src, &dst)?;
 
-    let cleaned =
-        web_image_meta::jpeg::clean_metadata(&data).with_context(|| {
-            format!("failed to clean metadata for '{}'", src.display())
+    if run.extract_motion_video
+        && format == Format::Jpeg
+        && let Some(video) = data.as_deref().and_then(jpeg::motion_video)
+    {
+        match write_motion_video(&dst, video, &run.keep, deadline) {
+            Ok(()) => {
+                run.motion_videos.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(err) => warn!(
+                "failed to extract motion video of '{}': {err:#}",
+                src.display()
+            ),
+        }
+        // stripping a long video can use up what is left of the budget
+        timeout::check(deadline)?;
+    }
+
+    if let Some(cleaned) = &cleaned {
+        fs::write(&dst, cleaned)
+            .with_context(|| format!("failed to write '{}'", dst.display()))?;
+        debug!("cleaned '{}' -> '{}'", src.display(), dst.display());
+    } else {
+        // fs::copy leaves the copy to the kernel (copy_file_range, which
+        // reflinks on XFS/btrfs) or to clonefile on macOS, and only falls
+        // back to reading and writing in userspace when that fails
+        fs::copy(src, &dst).with_context(|| {
+            format!("failed to copy to '{}'", dst.display())
+        })?;
+        xattr::strip(&dst).with_context(|| {
+            format!(
+                "failed to remove extended attributes of '{}'",
+                dst.display()
+            )
         })?;
+        debug!("already clean '{}' -> '{}'", src.display(), dst.display());
+    }
+    let after = cleaned.as_ref().map_or(before as usize, Vec::len);
+
+    if let Some(sizes) = &run.dimensions {
+        let read;
+        let data = match &cleaned {
+            Some(cleaned) => Some(cleaned.as_slice()),
+            None => {
+                read = fs::read(&dst).ok();
+                read.as_deref()
+            }
+        };
+        if let Some(data) = data
+            && let Some((width, height)) = format.dimensions(data)
+        {
+            let blurhash = (run.blurhash && format == Format::Jpeg)
+                .then(|| {
+                    thumb::decode_scaled(
+                        data,
+                        blurhash::SIDE,
+                        run.keep.max_pixels,
+                    )
+                })
+                .and_then(|image| match image {
+                    Ok(image) => blurhash::encode(&image),
+                    Err(err) => {
+                        warn!(
+                            "failed to compute blurhash of '{}': {err:#}",
+                            src.display()
+                        );
+                        None
+                    }
+                });
+            let size = Dimensions { width, height, blurhash };
+            sizes.lock().unwrap().insert(rel_path.clone(), size);
+        }
+    }
+
+    // previews and resized copies are only made from JPEGs for now
+    if format == Format::Jpeg
+        && (run.thumbnails.is_some() || run.derivatives.is_some())
+    {
+        let jpeg = match cleaned {
+            Some(cleaned) => Ok(cleaned),
+            None => fs::read(&dst).with_context(|| {
+                format!("failed to read '{}'", dst.display())
+            }),
+        };
+        match jpeg {
+            Ok(jpeg) => {
+                if let Some(thumbnails) = &run.thumbnails
+                    && let Err(err) =
+                        thumbnails.write(&rel_path, &jpeg, deadline)
+                {
+                    warn!(
+                        "failed to write preview of '{}': {err:#}",
+                        src.display()
+                    );
+                }
+                if let Some(derivatives) = &run.derivatives
+                    && let Err(err) =
+                        derivatives.write(src, &dst, &jpeg, deadline)
+                {
+                    warn!(
+                        "failed to write resized copies of '{}': {err:#}",
+                        src.display()
+                    );
+                }
+            }
+            Err(err) => {
+                warn!(
+                    "failed to write previews of '{}': {err:#}",
+                    src.display()
+                )
+            }
+        }
+    }
 
-    fs::write(&dst, &cleaned)
-        .with_context(|| format!("failed to write '{}'", dst.display()))?;
+    if run.direct_io {
+        for (path, written) in [(src, false), (dst.as_path(), true)] {
+            if let Err(err) = cache::drop_cached(path, written) {
+                debug!(
+                    "failed to drop '{}' from cache: {err}",
+                    path.display()
+                );
+            }
+        }
+    }
 
-    total_after.fetch_add(cleaned.len(), Ordering::Relaxed);
+    Ok(Processed { before: before as usize, after, already_clean })
+}
 
-    debug!("cleaned '{}' -> '{}'", src.display(), dst.display());
+/// Clean `data`, or return `None` if it has no metadata to remove and can
+/// be copied unchanged.
+fn clean_img(
+    src: &Path,
+    data: &[u8],
+    format: Format,
+    keep: &Keep,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let info = format.scan(data).with_context(|| {
+        format!("invalid {format} headers in '{}'", src.display())
+    })?;
 
-    Ok(())
+    if keep.unchanged(info.metadata_bytes) {
+        return Ok(None);
+    }
+
+    strip(src, data, format, keep)
+}
+
+/// Longest side of the thumbnails embedded by --regen-thumbnail, the
+/// 160x120 the EXIF standard asks for.
+const EXIF_THUMB_SIZE: u16 = 160;
+
+/// Strip the metadata of `data`; `None` when that changes nothing, e.g.
+/// when the only EXIF left is the orientation.
+fn strip(
+    src: &Path,
+    data: &[u8],
+    format: Format,
+    keep: &Keep,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut cleaned = format.strip(data, keep).with_context(|| {
+        format!("failed to clean metadata for '{}'", src.display())
+    })?;
+
+    if keep.regen_thumbnail && format == Format::Jpeg {
+        let thumbnail =
+            thumb::decode_scaled(&cleaned, EXIF_THUMB_SIZE, keep.max_pixels)
+                .and_then(|image| image.encode())
+                .with_context(|| {
+                    format!("failed to make thumbnail of '{}'", src.display())
+                })?;
+        cleaned =
+            jpeg::set_thumbnail(&cleaned, &thumbnail).with_context(|| {
+                format!("failed to embed thumbnail in '{}'", src.display())
+            })?;
+    }
+
+    Ok((cleaned != data).then_some(cleaned))
+}
+
+fn run_sample(sampler: Sampler, keep: &Keep) -> SampleReport {
+    let population = sampler.seen();
+    let picked = sampler.into_picked();
+
+    info!("sampling {} of {} files", picked.len(), population);
+
+    let mut report = SampleReport {
+        population,
+        sampled: picked.len(),
+        ..Default::default()
+    };
+
+    let start = Instant::now();
+    for path in &picked {
+        let cleaned = fs::read(path)
+            .with_context(|| format!("failed to read '{}'", path.display()))
+            .and_then(|data| {
+                let format = Format::detect(path, &data).ok_or_else(|| {
+                    anyhow!("unsupported image format '{}'", path.display())
+                })?;
+                Ok((data.len(), clean_img(path, &data, format, keep)?))
+            });
+
+        match cleaned {
+            Ok((before, cleaned)) => {
+                let after = cleaned.map_or(before, |c| c.len());
+                if after < before {
+                    report.with_metadata += 1;
+                }
+                report.bytes_before += before as u64;
+                report.bytes_after += after as u64;
+            }
+            Err(err) => {
+                report.failed += 1;
+                error!("failed to process '{}': {err:#}", path.display());
+            }
+        }
+    }
+    report.elapsed = start.elapsed();
+
+    report
 }
 
 fn init_logger(verbose: u8) {
@@ -0,0 +1,110 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Per-file metadata statistics: size histogram and sensitive fields.
+
+use exif::{Context, In, Reader, Tag};
+
+use crate::format::HeaderInfo;
+
+/// Upper bounds (exclusive) of the histogram buckets, in bytes.
+const BUCKETS: [(u64, &str); 7] = [
+    (1, "none"),
+    (1024, "< 1 KB"),
+    (4 * 1024, "1-4 KB"),
+    (16 * 1024, "4-16 KB"),
+    (64 * 1024, "16-64 KB"),
+    (256 * 1024, "64-256 KB"),
+    (1024 * 1024, "256 KB-1 MB"),
+];
+
+/// Sensitive fields found in an EXIF block.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ExifFlags {
+    pub gps: bool,
+    pub serial: bool,
+    pub thumbnail: bool,
+}
+
+impl ExifFlags {
+    /// Inspect a raw TIFF/EXIF block (without the `Exif\0\0` header).
+    ///
+    /// Unparseable blocks report nothing rather than failing the file.
+    pub fn from_tiff(tiff: Vec<u8>) -> Self {
+        let Ok(exif) = Reader::new().read_raw(tiff) else {
+            return Self::default();
+        };
+
+        let mut flags = Self::default();
+        for field in exif.fields() {
+            flags.gps |= field.tag.context() == Context::Gps;
+            flags.serial |= matches!(
+                field.tag,
+                Tag::BodySerialNumber
+                    | Tag::LensSerialNumber
+                    | Tag::CameraOwnerName
+            );
+            flags.thumbnail |= field.ifd_num == In::THUMBNAIL
+                && matches!(
+                    field.tag,
+                    Tag::JPEGInterchangeFormat | Tag::StripOffsets
+                );
+        }
+
+        flags
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MetaStats {
+    buckets: [usize; BUCKETS.len() + 1],
+    gps: usize,
+    serial: usize,
+    thumbnail: usize,
+}
+
+impl MetaStats {
+    /// Record one file from its scanned JPEG headers.
+    pub fn record_headers(&mut self, info: HeaderInfo) {
+        let flags = info.exif.map(ExifFlags::from_tiff).unwrap_or_default();
+        self.record(info.metadata_bytes, flags);
+    }
+
+    fn record(&mut self, metadata_bytes: u64, flags: ExifFlags) {
+        let bucket = BUCKETS
+            .iter()
+            .position(|&(limit, _)| metadata_bytes < limit)
+            .unwrap_or(BUCKETS.len());
+
+        self.buckets[bucket] += 1;
+        self.gps += flags.gps as usize;
+        self.serial += flags.serial as usize;
+        self.thumbnail += flags.thumbnail as usize;
+    }
+
+    pub fn print(&self) {
+        let labels =
+            BUCKETS.iter().map(|&(_, label)| label).chain([">= 1 MB"]);
+
+        println!("Metadata sizes:");
+        for (label, count) in labels.zip(self.buckets) {
+            println!("  {label:<12}: {count}");
+        }
+        println!("With GPS: {}", self.gps);
+        println!("With serial numbers: {}", self.serial);
+        println!("With thumbnails: {}", self.thumbnail);
+    }
+}
@@ -0,0 +1,309 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! MP4/MOV (ISO BMFF) helpers.
+//!
+//! The user data (`udta`, with the `©xyz` location and `©day` date), the
+//! QuickTime metadata (`meta`, where phones put the location) and XMP
+//! `uuid` boxes are blanked in place: each becomes a `free` box of the same
+//! size filled with zeros. Nothing moves, so the chunk offsets in `stco`
+//! and `co64` stay valid without rewriting the sample tables, and the media
+//! data is not touched.
+//!
+//! The creation and modification times of the movie, its tracks and their
+//! media stay, since players show them; `--date-offset`, `--date-round`
+//! and `--date-remove` rewrite them in place.
+
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+use crate::{bmff::read_box_header, date::Dates, format::HeaderInfo};
+
+const UUID: &[u8; 4] = b"uuid";
+const FREE: &[u8; 4] = b"free";
+
+/// Extended type of the XMP `uuid` box.
+const XMP_UUID: [u8; 16] = [
+    0xbe, 0x7a, 0xcf, 0xcb, 0x97, 0xa9, 0x42, 0xe8, 0x9c, 0x71, 0x99, 0x94,
+    0x91, 0xe3, 0xaf, 0xac,
+];
+
+/// Boxes blanked by cleaning, at the top level, in `moov` or in a `trak`.
+const METADATA: &[&[u8; 4]] = &[b"udta", b"meta"];
+
+/// Headers holding creation and modification times: movie, track, media.
+const TIMES: &[&[u8; 4]] = &[b"mvhd", b"tkhd", b"mdhd"];
+
+/// Boxes a file may start with; QuickTime files predate `ftyp`.
+const FIRST: &[&[u8; 4]] =
+    &[b"ftyp", b"moov", b"mdat", b"wide", b"free", b"skip", b"pnot"];
+
+/// Whether a box of type `kind` is metadata; `payload` is enough of its
+/// start to tell a `uuid` box apart.
+pub fn is_metadata(kind: &[u8; 4], payload: &[u8]) -> bool {
+    METADATA.contains(&kind)
+        || (kind == UUID && payload.starts_with(&XMP_UUID))
+}
+
+/// Boxes looked into: `moov` at the top level and the tracks in it.
+fn is_container(kind: &[u8; 4], depth: usize) -> bool {
+    matches!((depth, kind), (0, b"moov") | (1, b"trak"))
+}
+
+/// Walk the box tree down to the tracks without reading the media data.
+pub fn scan_boxes<R: Read + Seek>(r: &mut R) -> io::Result<HeaderInfo> {
+    let len = r.seek(SeekFrom::End(0))?;
+    r.rewind()?;
+
+    let mut info = HeaderInfo::default();
+    scan_range(r, 0, len, 0, &mut info)?;
+
+    Ok(info)
+}
+
+fn scan_range<R: Read + Seek>(
+    r: &mut R,
+    start: u64,
+    end: u64,
+    depth: usize,
+    info: &mut HeaderInfo,
+) -> io::Result<()> {
+    let mut pos = start;
+
+    while pos < end {
+        r.seek(SeekFrom::Start(pos))?;
+        let Some(header) = read_box_header(&mut r.take(end - pos))? else {
+            return Err(invalid("truncated box"));
+        };
+        if pos == 0 && !FIRST.contains(&&header.kind) {
+            return Err(invalid("missing ftyp or moov box"));
+        }
+
+        let size = header.size.unwrap_or(end - pos);
+        if size < header.header || size > end - pos {
+            return Err(invalid("truncated box"));
+        }
+        let body = pos + header.header;
+
+        let mut payload = [0u8; 16];
+        let peek = (size - header.header).min(16) as usize;
+        if &header.kind == UUID {
+            r.read_exact(&mut payload[..peek])?;
+        }
+
+        info.segments += 1;
+        if is_metadata(&header.kind, &payload[..peek]) {
+            info.metadata_bytes += size;
+        } else if is_container(&header.kind, depth) {
+            scan_range(r, body, pos + size, depth + 1, info)?;
+        }
+        pos += size;
+    }
+
+    Ok(())
+}
+
+/// Copy `data` with its metadata boxes blanked and its times rewritten as
+/// `dates` asks.
+pub fn strip_metadata(data: &[u8], dates: &Dates) -> io::Result<Vec<u8>> {
+    let mut out = data.to_vec();
+    blank_range(&mut out, 0, data.len(), 0, dates)?;
+    Ok(out)
+}
+
+fn blank_range(
+    data: &mut [u8],
+    start: usize,
+    end: usize,
+    depth: usize,
+    dates: &Dates,
+) -> io::Result<()> {
+    let mut pos = start;
+
+    while pos < end {
+        let Some(header) = read_box_header(&mut Cursor::new(&data[pos..end]))?
+        else {
+            return Err(invalid("truncated box"));
+        };
+        if pos == 0 && !FIRST.contains(&&header.kind) {
+            return Err(invalid("missing ftyp or moov box"));
+        }
+
+        let box_end = match header.size {
+            Some(size) => usize::try_from(size)
+                .ok()
+                .and_then(|size| pos.checked_add(size))
+                .filter(|&box_end| box_end <= end)
+                .ok_or_else(|| invalid("truncated box"))?,
+            None => end,
+        };
+        let body = pos + header.header as usize;
+        if body > box_end {
+            return Err(invalid("truncated box"));
+        }
+
+        if is_metadata(&header.kind, &data[body..box_end]) {
+            // the type sits right after the 32-bit size in both header forms
+            data[pos + 4..pos + 8].copy_from_slice(FREE);
+            data[body..box_end].fill(0);
+        } else if TIMES.contains(&&header.kind) {
+            rewrite_times(&mut data[body..box_end], dates)?;
+        } else if is_container(&header.kind, depth)
+            || (dates.is_active() && (depth, &header.kind) == (2, b"mdia"))
+        {
+            blank_range(data, body, box_end, depth + 1, dates)?;
+        }
+        pos = box_end;
+    }
+
+    Ok(())
+}
+
+/// Rewrite the creation and modification times at the start of a full box
+/// body, 32 or 64 bits each depending on its version.
+fn rewrite_times(body: &mut [u8], dates: &Dates) -> io::Result<()> {
+    if !dates.is_active() {
+        return Ok(());
+    }
+    let width = match body.first() {
+        Some(0) => 4,
+        Some(1) => 8,
+        _ => return Err(invalid("unknown header version")),
+    };
+    let Some(times) = body.get_mut(4..4 + 2 * width) else {
+        return Err(invalid("truncated header"));
+    };
+
+    for time in times.chunks_exact_mut(width) {
+        if width == 4 {
+            let secs = u32::from_be_bytes(time.try_into().unwrap());
+            let secs = u32::try_from(dates.quicktime(secs as u64))
+                .unwrap_or(u32::MAX);
+            time.copy_from_slice(&secs.to_be_bytes());
+        } else {
+            let secs = u64::from_be_bytes(time.try_into().unwrap());
+            time.copy_from_slice(&dates.quicktime(secs).to_be_bytes());
+        }
+    }
+
+    Ok(())
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date::Round;
+
+    const PLAIN: &[u8] = include_bytes!("../tests/fixtures/plain.mp4");
+    const META: &[u8] = include_bytes!("../tests/fixtures/meta.mp4");
+
+    fn contains(data: &[u8], needle: &[u8]) -> bool {
+        data.windows(needle.len()).any(|w| w == needle)
+    }
+
+    fn mdat(data: &[u8]) -> (usize, &[u8]) {
+        let at = data.windows(4).position(|w| w == b"mdat").unwrap();
+        (at, &data[at + 4..])
+    }
+
+    #[test]
+    fn plain_video_is_unchanged() {
+        assert_eq!(strip_metadata(PLAIN, &Dates::default()).unwrap(), PLAIN);
+        assert_eq!(
+            scan_boxes(&mut Cursor::new(PLAIN)).unwrap().metadata_bytes,
+            0
+        );
+    }
+
+    #[test]
+    fn scan_finds_user_data_meta_and_xmp() {
+        let info = scan_boxes(&mut Cursor::new(META)).unwrap();
+
+        // ftyp, moov, mvhd, trak, tkhd, mdia, udta, udta, meta, uuid, mdat
+        assert_eq!(info.segments, 11);
+        assert!(info.metadata_bytes > 0);
+        assert!(info.exif.is_none());
+    }
+
+    #[test]
+    fn blanks_metadata_in_place() {
+        let cleaned = strip_metadata(META, &Dates::default()).unwrap();
+
+        assert_eq!(cleaned.len(), META.len());
+        for needle in [&b"\xa9xyz"[..], b"+52.5200", b"clip1", &XMP_UUID] {
+            assert!(!contains(&cleaned, needle));
+        }
+        assert_eq!(
+            scan_boxes(&mut Cursor::new(&cleaned)).unwrap().metadata_bytes,
+            0
+        );
+        assert_eq!(
+            strip_metadata(&cleaned, &Dates::default()).unwrap(),
+            cleaned
+        );
+    }
+
+    #[test]
+    fn media_data_does_not_move() {
+        let cleaned = strip_metadata(META, &Dates::default()).unwrap();
+
+        assert_eq!(mdat(&cleaned), mdat(META));
+        // the sample table is copied, so the chunk offsets still match
+        let stco = |data: &[u8]| {
+            let at = data.windows(4).position(|w| w == b"stco").unwrap();
+            data[at..at + 16].to_vec()
+        };
+        assert_eq!(stco(&cleaned), stco(META));
+    }
+
+    #[test]
+    fn header_times_are_rewritten_in_place() {
+        let times = |data: &[u8]| {
+            TIMES
+                .iter()
+                .map(|kind| {
+                    let at = data.windows(4).position(|w| w == *kind).unwrap();
+                    let raw = &data[at + 8..at + 12];
+                    u32::from_be_bytes(raw.try_into().unwrap())
+                })
+                .collect::<Vec<_>>()
+        };
+        // 2024-07-15 10:20:30 in the fixture
+        assert_eq!(times(META), [3_803_883_630; 3]);
+
+        let round = Dates { round: Some(Round::Year), ..Dates::default() };
+        let cleaned = strip_metadata(META, &round).unwrap();
+        assert_eq!(cleaned.len(), META.len());
+        // 2024-01-01 00:00:00
+        assert_eq!(times(&cleaned), [3_786_912_000; 3]);
+
+        let remove = Dates { remove: true, ..Dates::default() };
+        assert_eq!(times(&strip_metadata(META, &remove).unwrap()), [0; 3]);
+        assert_eq!(mdat(&strip_metadata(META, &remove).unwrap()), mdat(META));
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        assert!(
+            strip_metadata(b"not a video file", &Dates::default()).is_err()
+        );
+        assert!(strip_metadata(&META[..40], &Dates::default()).is_err());
+        assert!(scan_boxes(&mut Cursor::new(&META[..40])).is_err());
+    }
+}
@@ -0,0 +1,617 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! PDF helpers.
+//!
+//! The document information dictionary (title, author, creation tool,
+//! dates) and XMP metadata streams are blanked in place: the dictionary is
+//! emptied and the stream data overwritten with spaces, so every object
+//! keeps its offset. The cross-reference tables of the original file and of
+//! each incremental update stay valid, and the pages are not touched. The
+//! copies left behind by earlier revisions are blanked too, since an update
+//! only appends to the file.
+//!
+//! An information dictionary inside a compressed object stream cannot be
+//! blanked without rewriting the stream, so such files are refused.
+
+use std::{
+    collections::HashSet,
+    io::{self, Read},
+    ops::Range,
+};
+
+use crate::format::HeaderInfo;
+
+/// Where the header may start; readers accept some junk before it.
+const HEADER_WITHIN: usize = 1024;
+
+/// How deep arrays and dictionaries may nest.
+const MAX_DEPTH: usize = 64;
+
+/// Stream dictionary entries blanked with the data they describe.
+const FILTERS: &[&[u8]] = &[b"Filter", b"DecodeParms"];
+
+type Id = (u32, u16);
+
+#[derive(Debug)]
+enum Value {
+    /// Entries with the range from the key to the end of the value.
+    Dict(Vec<(Vec<u8>, Value, Range<usize>)>),
+    Ref(Id),
+    Name(Vec<u8>),
+    Int(i64),
+    /// Strings, arrays, reals, booleans and null.
+    Other,
+}
+
+impl Value {
+    fn get(&self, key: &[u8]) -> Option<&Value> {
+        match self {
+            Value::Dict(entries) => entries
+                .iter()
+                .find(|(k, _, _)| k == key)
+                .map(|(_, value, _)| value),
+            _ => None,
+        }
+    }
+
+    fn is_name(&self, key: &[u8], name: &[u8]) -> bool {
+        matches!(self.get(key), Some(Value::Name(n)) if n == name)
+    }
+}
+
+#[derive(Debug)]
+struct Object {
+    id: Id,
+    value: Value,
+    range: Range<usize>,
+    stream: Option<Range<usize>>,
+}
+
+/// A byte range to blank, and whether it is replaced with `null` rather
+/// than spaces.
+type Blank = (Range<usize>, bool);
+
+/// Read the whole file and find its metadata; objects are not indexed by
+/// offset, so nothing can be skipped over.
+pub fn scan_objects<R: Read>(r: &mut R) -> io::Result<HeaderInfo> {
+    let mut data = Vec::new();
+    r.read_to_end(&mut data)?;
+
+    let (objects, trailers) = parse(&data)?;
+    let blanks = metadata(&data, &objects, &trailers)?;
+
+    Ok(HeaderInfo {
+        segments: objects.len(),
+        metadata_bytes: blanks.iter().map(|(r, _)| r.len() as u64).sum(),
+        ..HeaderInfo::default()
+    })
+}
+
+/// Copy `data` with its information dictionaries and XMP streams blanked.
+pub fn strip_metadata(data: &[u8]) -> io::Result<Vec<u8>> {
+    let (objects, trailers) = parse(data)?;
+    let blanks = metadata(data, &objects, &trailers)?;
+
+    let mut out = data.to_vec();
+    for (range, null) in blanks {
+        let start = range.start;
+        let len = range.len();
+        out[range].fill(b' ');
+        // a shorter value is left blank, an empty object reads as null too
+        if null && len >= 4 {
+            out[start..start + 4].copy_from_slice(b"null");
+        }
+    }
+
+    Ok(out)
+}
+
+/// The ranges holding metadata that is still there.
+fn metadata(
+    data: &[u8],
+    objects: &[Object],
+    trailers: &[Value],
+) -> io::Result<Vec<Blank>> {
+    let info: HashSet<Id> = trailers
+        .iter()
+        .filter_map(|trailer| match trailer.get(b"Info") {
+            Some(Value::Ref(id)) => Some(*id),
+            _ => None,
+        })
+        .collect();
+    for id in &info {
+        if !objects.iter().any(|object| object.id == *id) {
+            return Err(invalid(
+                "document information is in a compressed object stream",
+            ));
+        }
+    }
+
+    let mut blanks = Vec::new();
+    let mut strings = HashSet::new();
+    for object in objects {
+        if info.contains(&object.id)
+            && let Value::Dict(entries) = &object.value
+            && !entries.is_empty()
+        {
+            // keep the << and >> so an empty dictionary is left
+            let range = &object.range;
+            blanks.push((range.start + 2..range.end - 2, false));
+            strings.extend(entries.iter().filter_map(|(_, value, _)| {
+                match value {
+                    Value::Ref(id) => Some(*id),
+                    _ => None,
+                }
+            }));
+        }
+
+        let xmp = object.value.is_name(b"Type", b"Metadata")
+            || object.value.is_name(b"Subtype", b"XML");
+        if let (true, Some(stream), Value::Dict(entries)) =
+            (xmp, &object.stream, &object.value)
+        {
+            if data[stream.clone()].iter().any(|&b| !is_white(b)) {
+                blanks.push((stream.clone(), false));
+            }
+            blanks.extend(
+                entries
+                    .iter()
+                    .filter(|(key, _, _)| FILTERS.contains(&key.as_slice()))
+                    .map(|(_, _, range)| (range.clone(), false)),
+            );
+        }
+    }
+
+    // values the information dictionaries point to, such as a title kept
+    // in a string object of its own
+    blanks.extend(
+        objects
+            .iter()
+            .filter(|object| {
+                strings.contains(&object.id)
+                    && object.stream.is_none()
+                    && !matches!(
+                        data[object.range.clone()].trim_ascii(),
+                        b"" | b"null"
+                    )
+            })
+            .map(|object| (object.range.clone(), true)),
+    );
+
+    Ok(blanks)
+}
+
+/// Walk the objects, cross-reference tables and trailers of every revision.
+fn parse(data: &[u8]) -> io::Result<(Vec<Object>, Vec<Value>)> {
+    let header = data[..data.len().min(HEADER_WITHIN)]
+        .windows(5)
+        .position(|w| w == b"%PDF-")
+        .ok_or_else(|| invalid("missing PDF header"))?;
+
+    let mut p = Parser { data, pos: header };
+    let mut objects = Vec::new();
+    let mut trailers = Vec::new();
+
+    loop {
+        p.skip_white();
+        if p.pos >= data.len() {
+            break;
+        }
+
+        if let Some(id) = p.object_header() {
+            let object = p.object(id)?;
+            if object.value.is_name(b"Type", b"XRef") {
+                // cross-reference streams carry the trailer entries
+                trailers.push(p.reparse(&object.range)?);
+            }
+            objects.push(object);
+            continue;
+        }
+
+        match p.keyword() {
+            b"xref" => loop {
+                p.skip_white();
+                let at = p.pos;
+                if p.int().is_none() && !matches!(p.keyword(), b"n" | b"f") {
+                    p.pos = at;
+                    break;
+                }
+            },
+            b"trailer" => {
+                let (trailer, _) = p.value(0)?;
+                trailers.push(trailer);
+            }
+            b"startxref" => {
+                p.skip_white();
+                p.int().ok_or_else(|| invalid("invalid startxref"))?;
+            }
+            _ => return Err(invalid("unexpected data between objects")),
+        }
+    }
+
+    Ok((objects, trailers))
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_white(&mut self) {
+        while let Some(b) = self.peek() {
+            if b == b'%' {
+                while self.peek().is_some_and(|b| b != b'\n' && b != b'\r') {
+                    self.pos += 1;
+                }
+            } else if is_white(b) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// A run of regular characters: a keyword, a number or a name's body.
+    fn keyword(&mut self) -> &[u8] {
+        let start = self.pos;
+        while self.peek().is_some_and(|b| !is_white(b) && !is_delim(b)) {
+            self.pos += 1;
+        }
+        &self.data[start..self.pos]
+    }
+
+    /// An unsigned integer, leaving the position alone if there is none.
+    fn int(&mut self) -> Option<u64> {
+        let start = self.pos;
+        let value = std::str::from_utf8(self.keyword()).ok()?.parse().ok();
+        if value.is_none() {
+            self.pos = start;
+        }
+        value
+    }
+
+    /// `num gen obj`, leaving the position alone if it is not there.
+    fn object_header(&mut self) -> Option<Id> {
+        let start = self.pos;
+        let id = self.id(b"obj");
+        if id.is_none() {
+            self.pos = start;
+        }
+        id
+    }
+
+    /// `num gen` followed by `keyword`.
+    fn id(&mut self, keyword: &[u8]) -> Option<Id> {
+        let num = self.int()?.try_into().ok()?;
+        self.skip_white();
+        let generation = self.int()?.try_into().ok()?;
+        self.skip_white();
+        (self.keyword() == keyword).then_some((num, generation))
+    }
+
+    fn object(&mut self, id: Id) -> io::Result<Object> {
+        self.skip_white();
+        let start = self.pos;
+        // an empty object, left by blanking a short value, reads as null
+        if self.keyword() == b"endobj" {
+            let range = start..start;
+            return Ok(Object {
+                id,
+                value: Value::Other,
+                range,
+                stream: None,
+            });
+        }
+        self.pos = start;
+        let (value, range) = self.value(0)?;
+
+        self.skip_white();
+        let start = self.pos;
+        let stream = if self.keyword() == b"stream" {
+            Some(self.stream(&value)?)
+        } else {
+            self.pos = start;
+            None
+        };
+
+        self.skip_white();
+        if self.keyword() != b"endobj" {
+            return Err(invalid("missing endobj"));
+        }
+
+        Ok(Object { id, value, range, stream })
+    }
+
+    /// The data of a stream, after its `stream` keyword.
+    fn stream(&mut self, dict: &Value) -> io::Result<Range<usize>> {
+        let data = self.data;
+        if data[self.pos..].starts_with(b"\r\n") {
+            self.pos += 2;
+        } else if matches!(self.peek(), Some(b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+        let start = self.pos;
+
+        // trust a direct /Length when the stream really ends there
+        if let Some(Value::Int(len)) = dict.get(b"Length")
+            && let Some(end) = usize::try_from(*len)
+                .ok()
+                .and_then(|len| start.checked_add(len))
+                .filter(|&end| end <= data.len())
+        {
+            self.pos = end;
+            self.skip_white();
+            if self.keyword() == b"endstream" {
+                return Ok(start..end);
+            }
+        }
+
+        let at = data[start..]
+            .windows(9)
+            .position(|w| w == b"endstream")
+            .ok_or_else(|| invalid("missing endstream"))?;
+        self.pos = start + at + 9;
+        let mut end = start + at;
+        if data[..end].ends_with(b"\r\n") {
+            end -= 2;
+        } else if end > start && matches!(data[end - 1], b'\n' | b'\r') {
+            end -= 1;
+        }
+        Ok(start..end)
+    }
+
+    /// Parse the value at `range` again, for trailers of xref streams.
+    fn reparse(&self, range: &Range<usize>) -> io::Result<Value> {
+        let mut p = Parser { data: &self.data[..range.end], pos: range.start };
+        Ok(p.value(0)?.0)
+    }
+
+    fn value(&mut self, depth: usize) -> io::Result<(Value, Range<usize>)> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("objects nested too deeply"));
+        }
+        self.skip_white();
+        let data = self.data;
+        let start = self.pos;
+
+        let value = match self.peek() {
+            None => return Err(invalid("truncated object")),
+            Some(b'<') if data[start..].starts_with(b"<<") => {
+                self.pos += 2;
+                self.dict(depth)?
+            }
+            Some(b'<') => {
+                let len = data[start..]
+                    .iter()
+                    .position(|&b| b == b'>')
+                    .ok_or_else(|| invalid("unterminated hex string"))?;
+                self.pos += len + 1;
+                Value::Other
+            }
+            Some(b'(') => {
+                self.literal()?;
+                Value::Other
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                loop {
+                    self.skip_white();
+                    if self.peek() == Some(b']') {
+                        self.pos += 1;
+                        break;
+                    }
+                    self.value(depth + 1)?;
+                }
+                Value::Other
+            }
+            Some(b'/') => {
+                self.pos += 1;
+                Value::Name(self.keyword().to_vec())
+            }
+            Some(_) => {
+                if let Some(id) = self.reference() {
+                    Value::Ref(id)
+                } else {
+                    let word = self.keyword();
+                    if word.is_empty() {
+                        return Err(invalid("unexpected delimiter"));
+                    }
+                    match std::str::from_utf8(word)
+                        .ok()
+                        .and_then(|s| s.parse().ok())
+                    {
+                        Some(n) => Value::Int(n),
+                        None => Value::Other,
+                    }
+                }
+            }
+        };
+
+        Ok((value, start..self.pos))
+    }
+
+    /// `num gen R`, leaving the position alone if it is not there.
+    fn reference(&mut self) -> Option<Id> {
+        let start = self.pos;
+        let id = self.id(b"R");
+        if id.is_none() {
+            self.pos = start;
+        }
+        id
+    }
+
+    fn dict(&mut self, depth: usize) -> io::Result<Value> {
+        let mut entries = Vec::new();
+
+        loop {
+            self.skip_white();
+            let start = self.pos;
+            if self.data[start..].starts_with(b">>") {
+                self.pos += 2;
+                return Ok(Value::Dict(entries));
+            }
+            if self.peek() != Some(b'/') {
+                return Err(invalid("dictionary key is not a name"));
+            }
+            self.pos += 1;
+            let key = self.keyword().to_vec();
+            let (value, range) = self.value(depth + 1)?;
+            entries.push((key, value, start..range.end));
+        }
+    }
+
+    /// A literal string, with nested parentheses and escapes.
+    fn literal(&mut self) -> io::Result<()> {
+        let mut depth = 0usize;
+
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            match b {
+                b'\\' => self.pos += 1,
+                b'(' => depth += 1,
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Err(invalid("unterminated string"))
+    }
+}
+
+fn is_white(b: u8) -> bool {
+    matches!(b, b'\0' | b'\t' | b'\n' | b'\x0c' | b'\r' | b' ')
+}
+
+fn is_delim(b: u8) -> bool {
+    matches!(
+        b,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const PLAIN: &[u8] = include_bytes!("../tests/fixtures/plain.pdf");
+    const META: &[u8] = include_bytes!("../tests/fixtures/meta.pdf");
+
+    fn contains(data: &[u8], needle: &[u8]) -> bool {
+        data.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn plain_document_is_unchanged() {
+        assert_eq!(strip_metadata(PLAIN).unwrap(), PLAIN);
+        assert_eq!(
+            scan_objects(&mut Cursor::new(PLAIN)).unwrap().metadata_bytes,
+            0
+        );
+    }
+
+    #[test]
+    fn scan_finds_info_and_xmp_of_every_revision() {
+        let info = scan_objects(&mut Cursor::new(META)).unwrap();
+
+        assert_eq!(info.segments, 8);
+        assert!(info.metadata_bytes > 0);
+        assert!(info.exif.is_none());
+    }
+
+    #[test]
+    fn blanks_metadata_in_place() {
+        let cleaned = strip_metadata(META).unwrap();
+
+        assert_eq!(cleaned.len(), META.len());
+        for needle in [
+            &b"Jane Roe"[..],
+            b"Scanner 3000",
+            b"D:2024",
+            b"Final report",
+            b"4a6f686e",
+            b"FlateDecode",
+        ] {
+            assert!(!contains(&cleaned, needle));
+        }
+        assert!(contains(&cleaned, b"8 0 obj\nnull"));
+        assert!(contains(&cleaned, b"(Hello) Tj"));
+        assert_eq!(
+            scan_objects(&mut Cursor::new(&cleaned)).unwrap().metadata_bytes,
+            0
+        );
+        assert_eq!(strip_metadata(&cleaned).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn short_values_are_left_blank() {
+        // the same length, so the cross-references still hold
+        let value = b"(Final report)\nendobj";
+        let at = META.windows(value.len()).position(|w| w == value).unwrap();
+        let short = [&META[..at], b"           (x) endobj", &META[at + 21..]];
+        let short = short.concat();
+        let cleaned = strip_metadata(&short).unwrap();
+
+        assert_eq!(cleaned.len(), short.len());
+        assert!(contains(&cleaned, b"8 0 obj\n               endobj"));
+        assert_eq!(
+            scan_objects(&mut Cursor::new(&cleaned)).unwrap().metadata_bytes,
+            0
+        );
+        assert_eq!(strip_metadata(&cleaned).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn cross_references_stay_valid() {
+        let cleaned = strip_metadata(META).unwrap();
+
+        // every in-use entry of both xref tables still points at its object
+        let text = String::from_utf8_lossy(&cleaned);
+        let offsets: Vec<usize> = text
+            .lines()
+            .filter(|line| line.ends_with(" 00000 n "))
+            .map(|line| line[..10].parse().unwrap())
+            .collect();
+        assert_eq!(offsets.len(), 8);
+        for offset in offsets {
+            let mut p = Parser { data: &cleaned, pos: offset };
+            assert!(p.object_header().is_some(), "no object at {offset}");
+        }
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        assert!(strip_metadata(b"not a pdf file").is_err());
+        assert!(strip_metadata(&META[..200]).is_err());
+        assert!(scan_objects(&mut Cursor::new(&META[..200])).is_err());
+    }
+}
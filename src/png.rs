@@ -0,0 +1,259 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! PNG chunk stream helpers.
+//!
+//! Cleaning drops the metadata chunks and copies every other chunk whole,
+//! CRC included, so IDAT and the pixels it encodes are never touched and no
+//! CRC has to be recomputed. Data after IEND goes too: a screenshot cropped
+//! in place by a buggy editor keeps the rest of the original there.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::format::{HeaderInfo, read_len};
+
+pub const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+const IEND: &[u8; 4] = b"IEND";
+const EXIF: &[u8; 4] = b"eXIf";
+const ICCP: &[u8; 4] = b"iCCP";
+
+/// Chunks removed by cleaning.
+///
+/// Text chunks hold anything from the software used to window titles and
+/// user names, eXIf is a full EXIF block and tIME the last edit time. The
+/// colour profile (iCCP) is kept, like ICC profiles in JPEG, unless
+/// `--icc strip` asks otherwise.
+const METADATA: &[&[u8; 4]] = &[b"tEXt", b"zTXt", b"iTXt", EXIF, b"tIME"];
+
+pub fn is_metadata(kind: &[u8; 4]) -> bool {
+    METADATA.contains(&kind)
+}
+
+/// Walk the chunks up to IEND without reading the image data.
+pub fn scan_chunks<R: Read + Seek>(r: &mut R) -> io::Result<HeaderInfo> {
+    let mut signature = [0u8; 8];
+    r.read_exact(&mut signature)?;
+    if signature != SIGNATURE {
+        return Err(invalid("missing PNG signature"));
+    }
+
+    let mut info = HeaderInfo::default();
+
+    loop {
+        let mut header = [0u8; 8];
+        r.read_exact(&mut header)?;
+        let len =
+            u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let kind = [header[4], header[5], header[6], header[7]];
+
+        if &kind == EXIF && info.exif.is_none() {
+            let exif = read_len(r, len as u64)?
+                .ok_or_else(|| invalid("truncated chunk"))?;
+            r.seek(SeekFrom::Current(4))?;
+            info.exif = Some(exif);
+        } else {
+            r.seek(SeekFrom::Current(len as i64 + 4))?;
+        }
+
+        info.segments += 1;
+        if is_metadata(&kind) {
+            info.metadata_bytes += len as u64 + 12;
+        }
+
+        if &kind == IEND {
+            break;
+        }
+    }
+
+    let end = r.stream_position()?;
+    info.trailer_bytes = r.seek(SeekFrom::End(0))?.saturating_sub(end);
+    info.metadata_bytes += info.trailer_bytes;
+
+    Ok(info)
+}
+
+/// Width and height from the IHDR chunk, which comes first.
+pub fn dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    // the chunk length, its type and the two sizes
+    let ihdr = data.strip_prefix(SIGNATURE)?.get(4..16)?;
+    if &ihdr[..4] != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(ihdr[4..8].try_into().unwrap());
+    let height = u32::from_be_bytes(ihdr[8..12].try_into().unwrap());
+    Some((width, height))
+}
+
+/// Copy `data` without its metadata chunks, without its colour profile
+/// unless `icc` is set and without what follows IEND unless `trailer` is.
+pub fn strip_metadata(
+    data: &[u8],
+    icc: bool,
+    trailer: bool,
+) -> io::Result<Vec<u8>> {
+    if !data.starts_with(SIGNATURE) {
+        return Err(invalid("missing PNG signature"));
+    }
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(SIGNATURE);
+    let mut pos = SIGNATURE.len();
+
+    loop {
+        let Some(header) = data.get(pos..pos + 8) else {
+            return Err(invalid("truncated chunk"));
+        };
+        let len =
+            u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let kind = [header[4], header[5], header[6], header[7]];
+
+        let end = pos + 12 + len as usize;
+        let Some(chunk) = data.get(pos..end) else {
+            return Err(invalid("truncated chunk"));
+        };
+        if !is_metadata(&kind) && (icc || &kind != ICCP) {
+            out.extend_from_slice(chunk);
+        }
+        pos = end;
+
+        if &kind == IEND {
+            break;
+        }
+    }
+
+    if trailer {
+        out.extend_from_slice(&data[pos..]);
+    }
+
+    Ok(out)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const PLAIN: &[u8] = include_bytes!("../tests/fixtures/plain.png");
+    const META: &[u8] = include_bytes!("../tests/fixtures/meta.png");
+
+    fn chunks(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+        let mut chunks = Vec::new();
+        let mut pos = SIGNATURE.len();
+        while pos < data.len() {
+            let len =
+                u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap());
+            let end = pos + 12 + len as usize;
+            chunks.push((&data[pos + 4..pos + 8], &data[pos..end]));
+            pos = end;
+        }
+        chunks
+    }
+
+    #[test]
+    fn plain_image_is_unchanged() {
+        assert_eq!(strip_metadata(PLAIN, true, false).unwrap(), PLAIN);
+        assert_eq!(
+            scan_chunks(&mut Cursor::new(PLAIN)).unwrap().metadata_bytes,
+            0
+        );
+    }
+
+    #[test]
+    fn scan_finds_metadata_chunks() {
+        let info = scan_chunks(&mut Cursor::new(META)).unwrap();
+
+        assert_eq!(info.segments, 9);
+        assert!(info.metadata_bytes > 0);
+        assert!(info.exif.unwrap().starts_with(b"MM\0\x2a"));
+    }
+
+    #[test]
+    fn strips_metadata_chunks() {
+        let cleaned = strip_metadata(META, true, false).unwrap();
+        let kinds: Vec<_> =
+            chunks(&cleaned).into_iter().map(|(k, _)| k).collect();
+
+        assert_eq!(kinds, [&b"IHDR"[..], b"iCCP", b"IDAT", b"IEND"]);
+        assert_eq!(
+            scan_chunks(&mut Cursor::new(&cleaned)).unwrap().metadata_bytes,
+            0
+        );
+    }
+
+    #[test]
+    fn profile_goes_when_asked() {
+        let cleaned = strip_metadata(META, false, false).unwrap();
+        let kinds: Vec<_> =
+            chunks(&cleaned).into_iter().map(|(k, _)| k).collect();
+
+        assert_eq!(kinds, [&b"IHDR"[..], b"IDAT", b"IEND"]);
+        assert_eq!(strip_metadata(&cleaned, false, false).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn kept_chunks_are_bit_identical() {
+        let cleaned = strip_metadata(META, true, false).unwrap();
+        let original = chunks(META);
+
+        for (kind, chunk) in chunks(&cleaned) {
+            assert!(original.contains(&(kind, chunk)));
+        }
+        assert_eq!(strip_metadata(&cleaned, true, false).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn data_after_iend_goes_unless_kept() {
+        // what a cropped screenshot can leave behind
+        let leak = [PLAIN, b"\0\0\0\x0dIDATuncropped"].concat();
+        let info = scan_chunks(&mut Cursor::new(&leak)).unwrap();
+        assert_eq!(info.trailer_bytes, 17);
+        assert_eq!(info.metadata_bytes, 17);
+
+        assert_eq!(strip_metadata(&leak, true, false).unwrap(), PLAIN);
+        assert_eq!(strip_metadata(&leak, true, true).unwrap(), leak);
+        assert_eq!(
+            scan_chunks(&mut Cursor::new(PLAIN)).unwrap().trailer_bytes,
+            0
+        );
+    }
+
+    #[test]
+    fn dimensions_come_from_the_header() {
+        assert_eq!(dimensions(PLAIN), Some((4, 3)));
+        assert_eq!(
+            dimensions(&strip_metadata(META, true, false).unwrap()),
+            Some((4, 3))
+        );
+        assert_eq!(dimensions(&META[..20]), None);
+    }
+
+    #[test]
+    fn rejects_broken_streams() {
+        assert!(strip_metadata(b"not a png", true, false).is_err());
+        assert!(strip_metadata(&META[..40], true, false).is_err());
+        assert!(scan_chunks(&mut Cursor::new(&META[..40])).is_err());
+
+        // an eXIf chunk claiming nearly 4 GiB right after IHDR
+        let huge = [&PLAIN[..33], b"\xff\xff\xff\xf0eXIfMM\0\x2a"].concat();
+        assert!(scan_chunks(&mut Cursor::new(&huge)).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Camera RAW files.
+//!
+//! RAW files always carry the full camera metadata, serial numbers and
+//! maker notes included, so skipping them quietly would leave the most
+//! revealing files of a tree behind without a word. Only DNG, which is
+//! TIFF based and documented, can be cleaned.
+
+use std::{collections::BTreeMap, sync::Mutex};
+
+use clap::ValueEnum;
+use log::{info, warn};
+
+/// Extensions of the common RAW formats.
+const EXTENSIONS: &[&str] = &[
+    "3fr", "arw", "cr2", "cr3", "crw", "dcr", "dng", "erf", "iiq", "kdc",
+    "mef", "mos", "mrw", "nef", "nrw", "orf", "pef", "raf", "rw2", "rwl",
+    "sr2", "srf", "srw", "x3f",
+];
+
+/// What to do with camera RAW files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum RawPolicy {
+    /// Skip them like any other unsupported file
+    Skip,
+    /// Skip them, but report them as holding metadata that was not removed
+    #[default]
+    Report,
+    /// Clean DNG files and report the other RAW formats
+    CleanDng,
+}
+
+/// Whether `ext` (lowercase, without the dot) is a RAW extension.
+pub fn is_raw(ext: &str) -> bool {
+    EXTENSIONS.contains(&ext)
+}
+
+/// RAW files left uncleaned, by extension.
+#[derive(Debug, Default)]
+pub struct RawFiles {
+    by_ext: Mutex<BTreeMap<String, usize>>,
+}
+
+impl RawFiles {
+    pub fn record(&self, ext: &str) {
+        *self.by_ext.lock().unwrap().entry(ext.to_string()).or_default() += 1;
+    }
+
+    pub fn log_summary(&self) {
+        let by_ext = self.by_ext.lock().unwrap();
+        if by_ext.is_empty() {
+            return;
+        }
+
+        let mut counts: Vec<_> = by_ext.iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let list: Vec<_> =
+            counts.iter().map(|(ext, n)| format!(".{ext}={n}")).collect();
+
+        warn!(
+            "raw: {} files contain metadata and were not cleaned ({})",
+            by_ext.values().sum::<usize>(),
+            list.join(", ")
+        );
+        if by_ext.contains_key("dng") {
+            info!("raw: --raw clean-dng cleans the DNG files");
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Statistical sampling of the input tree.
+//!
+//! A sampling run walks the whole tree but only cleans a random subset of
+//! the candidate files, in memory, and extrapolates the totals from it.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::stamp::{Stamp, format_duration};
+
+/// How many candidate files should be sampled.
+#[derive(Debug, Clone, Copy)]
+pub enum SampleSpec {
+    /// Pick every file with the given probability (`0.0..=1.0`).
+    Ratio(f64),
+    /// Pick exactly this many files (or all of them, if there are fewer).
+    Count(usize),
+}
+
+/// Parse a sampling percentage such as `1%`, `0.5%` or `10`.
+pub fn parse_percent(s: &str) -> Result<f64, String> {
+    let value: f64 = s
+        .trim()
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("invalid percentage '{s}'"))?;
+
+    if !(value > 0.0 && value <= 100.0) {
+        return Err(format!("percentage must be in (0, 100], got '{s}'"));
+    }
+
+    Ok(value / 100.0)
+}
+
+/// Selects the sampled files while the tree is being walked.
+///
+/// Ratio sampling is a plain Bernoulli draw per file; count sampling uses
+/// reservoir sampling so the total number of files does not need to be
+/// known up front.
+#[derive(Debug)]
+pub struct Sampler {
+    spec: SampleSpec,
+    seen: usize,
+    picked: Vec<PathBuf>,
+    rng: Rng,
+}
+
+impl Sampler {
+    pub fn new(spec: SampleSpec) -> Self {
+        Self { spec, seen: 0, picked: Vec::new(), rng: Rng::from_time() }
+    }
+
+    /// Offer a candidate file to the sampler.
+    pub fn offer(&mut self, path: &Path) {
+        self.seen += 1;
+
+        match self.spec {
+            SampleSpec::Ratio(ratio) => {
+                if self.rng.next_f64() < ratio {
+                    self.picked.push(path.to_path_buf());
+                }
+            }
+            SampleSpec::Count(count) => {
+                if self.picked.len() < count {
+                    self.picked.push(path.to_path_buf());
+                } else {
+                    let slot = self.rng.below(self.seen);
+                    if slot < count {
+                        self.picked[slot] = path.to_path_buf();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Number of candidate files offered so far.
+    pub fn seen(&self) -> usize {
+        self.seen
+    }
+
+    pub fn into_picked(self) -> Vec<PathBuf> {
+        self.picked
+    }
+}
+
+/// Aggregated result of cleaning the sampled files.
+#[derive(Debug, Default)]
+pub struct SampleReport {
+    pub population: usize,
+    pub sampled: usize,
+    pub failed: usize,
+    pub with_metadata: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub elapsed: Duration,
+}
+
+impl SampleReport {
+    /// Print the sample results and the extrapolated totals.
+    ///
+    /// `threads` is the number of workers a full run would use, and is
+    /// only used to scale the runtime estimate.
+    pub fn print(&self, threads: usize, stamp: &Stamp) {
+        let ok = self.sampled - self.failed;
+
+        println!();
+        println!("Sample:");
+        stamp.print();
+        println!(
+            "Files sampled: {} of {} ({:.2}%)",
+            self.sampled,
+            self.population,
+            pct(self.sampled as f64, self.population as f64)
+        );
+        if self.failed > 0 {
+            println!("Failed: {}", self.failed);
+        }
+
+        if ok == 0 {
+            println!("Nothing to extrapolate from");
+            println!();
+            return;
+        }
+
+        let scale = self.population as f64 / ok as f64;
+        let saved = self.bytes_before.saturating_sub(self.bytes_after) as f64;
+        let per_file = self.elapsed.as_secs_f64() / ok as f64;
+        let runtime =
+            per_file * self.population as f64 / threads.max(1) as f64;
+
+        println!(
+            "With metadata: {:.1}%",
+            pct(self.with_metadata as f64, ok as f64)
+        );
+        println!(
+            "Est. source total: {:.2} MB",
+            self.bytes_before as f64 * scale / (1024.0 * 1024.0)
+        );
+        println!(
+            "Est. saved: {:.2} MB ({:.1}%)",
+            saved * scale / (1024.0 * 1024.0),
+            pct(saved, self.bytes_before as f64)
+        );
+        println!(
+            "Est. runtime: {} ({} threads)",
+            format_duration(Duration::from_secs_f64(runtime)),
+            threads
+        );
+        println!();
+    }
+}
+
+fn pct(part: f64, whole: f64) -> f64 {
+    if whole > 0.0 { part / whole * 100.0 } else { 0.0 }
+}
+
+/// Small xorshift64* generator; sampling does not need anything stronger.
+#[derive(Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
+        // xorshift must never be seeded with zero
+        Self(nanos ^ 0x9e37_79b9_7f4a_7c15 | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
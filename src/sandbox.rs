@@ -0,0 +1,365 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Restricting the process once the arguments are checked.
+//!
+//! The filesystem is cut down to the input tree (read only) and the paths
+//! the run writes to, so a parser bug exploited by a crafted image cannot
+//! reach the rest of the system. OpenBSD uses pledge(2) and unveil(2).
+//! Linux uses Landlock for the filesystem and a seccomp filter that refuses
+//! the syscalls a cleaning run never makes (exec, ptrace, sockets, mounts).
+//! Both are inherited by the walker threads spawned afterwards.
+
+use std::{io, path::Path};
+
+/// Whether the process can be restricted on this platform.
+pub const SUPPORTED: bool = cfg!(any(
+    target_os = "openbsd",
+    all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )
+));
+
+/// Promises of a cleaning run: file IO, creating outputs and copying file
+/// modes over.
+#[cfg(target_os = "openbsd")]
+const PROMISES: &str = "stdio rpath wpath cpath fattr";
+
+/// Allow reading `read` and writing `write` only, then pledge.
+#[cfg(target_os = "openbsd")]
+pub fn restrict(read: &Path, write: &[&Path]) -> io::Result<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let cstr = |s: &[u8]| CString::new(s).map_err(io::Error::from);
+    let check = |ret: libc::c_int| match ret {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    };
+
+    let paths = std::iter::once((read, "r"))
+        .chain(write.iter().map(|path| (*path, "rwc")));
+    for (path, perms) in paths {
+        let path = cstr(path.as_os_str().as_bytes())?;
+        let perms = cstr(perms.as_bytes())?;
+        // SAFETY: both strings are nul-terminated and outlive the call
+        check(unsafe { libc::unveil(path.as_ptr(), perms.as_ptr()) })?;
+    }
+    // SAFETY: null arguments lock the unveiled paths
+    check(unsafe { libc::unveil(std::ptr::null(), std::ptr::null()) })?;
+
+    let promises = cstr(PROMISES.as_bytes())?;
+    // SAFETY: the promises are nul-terminated, null keeps exec promises
+    check(unsafe { libc::pledge(promises.as_ptr(), std::ptr::null()) })
+}
+
+/// Refuse the syscalls a cleaning run never makes, then allow reading
+/// `read` and writing `write` only; the files in `write` must exist.
+///
+/// Fails with [`io::ErrorKind::Unsupported`] when the kernel has no
+/// Landlock; the seccomp filter is in place by then.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn restrict(read: &Path, write: &[&Path]) -> io::Result<()> {
+    // SAFETY: plain integer arguments; required to install both filters
+    // without privileges
+    let ret = unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    seccomp::install()?;
+    landlock::restrict(read, write)
+}
+
+#[cfg(not(any(
+    target_os = "openbsd",
+    all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )
+)))]
+pub fn restrict(_read: &Path, _write: &[&Path]) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod landlock {
+    use std::{
+        fs::OpenOptions,
+        io,
+        os::fd::{AsRawFd, FromRawFd, OwnedFd},
+        os::unix::fs::OpenOptionsExt,
+        path::Path,
+    };
+
+    const CREATE_RULESET_VERSION: u32 = 1 << 0;
+    const RULE_PATH_BENEATH: libc::c_long = 1;
+
+    const WRITE_FILE: u64 = 1 << 1;
+    const READ_FILE: u64 = 1 << 2;
+    const READ_DIR: u64 = 1 << 3;
+    const REMOVE_DIR: u64 = 1 << 4;
+    const REMOVE_FILE: u64 = 1 << 5;
+    const MAKE_DIR: u64 = 1 << 7;
+    const MAKE_REG: u64 = 1 << 8;
+    /// Moving files between directories, from ABI 2.
+    const REFER: u64 = 1 << 13;
+    /// Truncating files, from ABI 3.
+    const TRUNCATE: u64 = 1 << 14;
+
+    /// The rights ABI 1 knows about, from executing to making symlinks.
+    const ABI_1: u64 = (1 << 13) - 1;
+
+    const READ: u64 = READ_FILE | READ_DIR;
+    const WRITE: u64 = READ
+        | WRITE_FILE
+        | REMOVE_DIR
+        | REMOVE_FILE
+        | MAKE_DIR
+        | MAKE_REG
+        | REFER
+        | TRUNCATE;
+    /// The rights that apply to a file rather than a directory.
+    const FILE: u64 = READ_FILE | WRITE_FILE | TRUNCATE;
+
+    /// Read by getloadavg(3) for `--only-when-idle`.
+    const LOADAVG: &str = "/proc/loadavg";
+
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+    }
+
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: libc::c_int,
+    }
+
+    pub fn restrict(read: &Path, write: &[&Path]) -> io::Result<()> {
+        let handled = handled_access()?;
+
+        let attr = RulesetAttr { handled_access_fs: handled };
+        // SAFETY: attr is a valid ruleset_attr of the given size
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                size_of::<RulesetAttr>(),
+                0,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the kernel just handed out this descriptor
+        let ruleset = unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) };
+
+        add_rule(&ruleset, read, READ & handled)?;
+        if Path::new(LOADAVG).exists() {
+            add_rule(&ruleset, Path::new(LOADAVG), READ_FILE)?;
+        }
+        for path in write {
+            // a file not there yet could only be allowed by its parent
+            // directory, which may hold the input tree, so the caller
+            // creates it first
+            let access = if path.is_dir() { WRITE } else { FILE };
+            add_rule(&ruleset, path, access & handled)?;
+        }
+
+        // SAFETY: ruleset is an open Landlock ruleset descriptor
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_restrict_self,
+                ruleset.as_raw_fd(),
+                0,
+            )
+        };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// The rights the running kernel can restrict.
+    fn handled_access() -> io::Result<u64> {
+        // SAFETY: a null attribute with the version flag only queries
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<RulesetAttr>(),
+                0,
+                CREATE_RULESET_VERSION,
+            )
+        };
+        if abi < 0 {
+            let err = io::Error::last_os_error();
+            return match err.raw_os_error() {
+                Some(libc::ENOSYS | libc::EOPNOTSUPP) => Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "landlock is not available on this kernel",
+                )),
+                _ => Err(err),
+            };
+        }
+
+        let mut handled = ABI_1;
+        if abi >= 2 {
+            handled |= REFER;
+        }
+        if abi >= 3 {
+            handled |= TRUNCATE;
+        }
+        Ok(handled)
+    }
+
+    fn add_rule(
+        ruleset: &OwnedFd,
+        path: &Path,
+        access: u64,
+    ) -> io::Result<()> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+            .open(path)?;
+        let attr = PathBeneathAttr {
+            allowed_access: access,
+            parent_fd: file.as_raw_fd(),
+        };
+
+        // SAFETY: attr is a valid path_beneath_attr, file outlives the call
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset.as_raw_fd(),
+                RULE_PATH_BENEATH,
+                &attr as *const PathBeneathAttr,
+                0,
+            )
+        };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod seccomp {
+    use std::{io, mem::offset_of};
+
+    #[cfg(target_arch = "x86_64")]
+    const ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const ARCH: u32 = 0xc000_00b7;
+
+    /// Syscalls refused with EPERM: running programs, looking into other
+    /// processes, the network, namespaces and mounts, and kernel loading.
+    const DENIED: &[libc::c_long] = &[
+        libc::SYS_execve,
+        libc::SYS_execveat,
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_socket,
+        libc::SYS_socketpair,
+        libc::SYS_connect,
+        libc::SYS_bind,
+        libc::SYS_listen,
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_pivot_root,
+        libc::SYS_chroot,
+        libc::SYS_unshare,
+        libc::SYS_setns,
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_kexec_load,
+        libc::SYS_bpf,
+        libc::SYS_perf_event_open,
+        libc::SYS_keyctl,
+        libc::SYS_add_key,
+        libc::SYS_request_key,
+        libc::SYS_userfaultfd,
+    ];
+
+    const LD_W_ABS: u16 = (libc::BPF_LD | libc::BPF_W | libc::BPF_ABS) as u16;
+    const JEQ_K: u16 = (libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16;
+    #[cfg(target_arch = "x86_64")]
+    const JGE_K: u16 = (libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K) as u16;
+    const RET_K: u16 = (libc::BPF_RET | libc::BPF_K) as u16;
+
+    fn stmt(code: u16, k: u32) -> libc::sock_filter {
+        libc::sock_filter { code, jt: 0, jf: 0, k }
+    }
+
+    fn jump(code: u16, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+        libc::sock_filter { code, jt, jf, k }
+    }
+
+    /// Install the filter for every thread of the process.
+    pub fn install() -> io::Result<()> {
+        let arch = offset_of!(libc::seccomp_data, arch) as u32;
+        let nr = offset_of!(libc::seccomp_data, nr) as u32;
+        let deny = libc::SECCOMP_RET_ERRNO | libc::EPERM as u32;
+
+        let mut filter = vec![
+            stmt(LD_W_ABS, arch),
+            jump(JEQ_K, ARCH, 1, 0),
+            stmt(RET_K, libc::SECCOMP_RET_KILL_PROCESS),
+            stmt(LD_W_ABS, nr),
+        ];
+        // x32 syscalls share the x86_64 arch value and set this bit instead
+        #[cfg(target_arch = "x86_64")]
+        filter.extend([jump(JGE_K, 0x4000_0000, 0, 1), stmt(RET_K, deny)]);
+        for &sys in DENIED {
+            filter.push(jump(JEQ_K, sys as u32, 0, 1));
+            filter.push(stmt(RET_K, deny));
+        }
+        filter.push(stmt(RET_K, libc::SECCOMP_RET_ALLOW));
+
+        let prog = libc::sock_fprog {
+            len: filter.len() as u16,
+            filter: filter.as_mut_ptr(),
+        };
+        // SAFETY: prog points to a filter that outlives the call
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_seccomp,
+                libc::SECCOMP_SET_MODE_FILTER,
+                libc::SECCOMP_FILTER_FLAG_TSYNC,
+                &prog as *const libc::sock_fprog,
+            )
+        };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! When, where and by what a run was made, for its reports.
+//!
+//! Reports get archived and compared long after the run, so each one says
+//! when it started and finished, on which host and with which imgst. Times
+//! are local with their UTC offset, in RFC 3339, so they sort as text and
+//! stay unambiguous across time zones.
+
+use std::{
+    ffi::CStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::info;
+
+use crate::date;
+
+#[derive(Debug)]
+pub struct Stamp {
+    started: SystemTime,
+    clock: Instant,
+    host: String,
+    version: &'static str,
+}
+
+impl Stamp {
+    /// Start the clock of a run of imgst `version`.
+    pub fn start(version: &'static str) -> Self {
+        Stamp {
+            started: SystemTime::now(),
+            clock: Instant::now(),
+            host: hostname().unwrap_or_else(|| "unknown".into()),
+            version,
+        }
+    }
+
+    pub fn log_start(&self) {
+        info!(
+            "started: {} on {} (imgst {})",
+            timestamp(self.started),
+            self.host,
+            self.version
+        );
+    }
+
+    pub fn log_end(&self) {
+        info!(
+            "finished: {} after {}",
+            timestamp(SystemTime::now()),
+            format_duration(self.clock.elapsed())
+        );
+    }
+
+    /// Print the lines heading a report, when asked for the end of the run.
+    pub fn print(&self) {
+        println!("Started: {}", timestamp(self.started));
+        println!("Finished: {}", timestamp(SystemTime::now()));
+        println!("Duration: {}", format_duration(self.clock.elapsed()));
+        println!("Host: {}", self.host);
+        println!("Version: imgst {}", self.version);
+    }
+}
+
+/// A duration rounded for people: seconds, then minutes, then hours.
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    let (h, m, s) = (secs / 3600, (secs / 60) % 60, secs % 60);
+
+    if h > 0 {
+        format!("{h}h {m:02}m")
+    } else if m > 0 {
+        format!("{m}m {s:02}s")
+    } else {
+        format!("{:.1}s", d.as_secs_f64())
+    }
+}
+
+/// `time` in the local time zone.
+fn timestamp(time: SystemTime) -> String {
+    let unix =
+        time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64);
+    date::rfc3339(unix, utc_offset(unix))
+}
+
+/// Seconds east of UTC of the local time zone at `unix`.
+fn utc_offset(unix: i64) -> i64 {
+    let t = unix as libc::time_t;
+    // SAFETY: localtime_r only writes to the tm it is given
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+        return 0;
+    }
+    // a c_long, 32 bits on some targets
+    #[allow(clippy::unnecessary_cast)]
+    let offset = tm.tm_gmtoff as i64;
+    offset
+}
+
+fn hostname() -> Option<String> {
+    let mut buf = [0 as libc::c_char; 256];
+    // SAFETY: the length passed is the one of the buffer
+    if unsafe { libc::gethostname(buf.as_mut_ptr(), buf.len()) } != 0 {
+        return None;
+    }
+    // names that fill the buffer may not be terminated
+    buf[buf.len() - 1] = 0;
+    // SAFETY: the buffer is NUL terminated
+    let name = unsafe { CStr::from_ptr(buf.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
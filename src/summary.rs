@@ -0,0 +1,114 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Per-format breakdown of a cleaning run.
+
+use std::collections::BTreeMap;
+
+use log::info;
+
+use crate::format::Format;
+
+/// What processing one file did.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Processed {
+    pub before: usize,
+    pub after: usize,
+    pub already_clean: bool,
+}
+
+#[derive(Debug, Default)]
+struct FormatSummary {
+    processed: usize,
+    already_clean: usize,
+    failed: usize,
+    bytes_before: usize,
+    bytes_after: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    formats: BTreeMap<&'static str, FormatSummary>,
+    /// Files that were not images, by lowercase extension.
+    skipped: BTreeMap<String, usize>,
+    /// Files whose content is not what their extension says, by extension
+    /// and the format they turned out to be.
+    mismatched: BTreeMap<String, usize>,
+}
+
+impl Summary {
+    pub fn record(&mut self, format: &'static str, processed: &Processed) {
+        let summary = self.formats.entry(format).or_default();
+        summary.processed += 1;
+        summary.already_clean += processed.already_clean as usize;
+        summary.bytes_before += processed.before;
+        summary.bytes_after += processed.after;
+    }
+
+    pub fn record_failed(&mut self, format: &'static str) {
+        self.formats.entry(format).or_default().failed += 1;
+    }
+
+    pub fn record_skipped(&mut self, ext: Option<&str>) {
+        *self.skipped.entry(ext_key(ext)).or_default() += 1;
+    }
+
+    pub fn record_mismatch(&mut self, ext: Option<&str>, format: Format) {
+        let key = format!("{} as {}", ext_key(ext), format.name());
+        *self.mismatched.entry(key).or_default() += 1;
+    }
+
+    pub fn log(&self) {
+        for (format, summary) in &self.formats {
+            let saved =
+                summary.bytes_before.saturating_sub(summary.bytes_after);
+            info!(
+                "{format}: processed={} (already clean={}) failed={} \
+                 saved={:.2} MB",
+                summary.processed,
+                summary.already_clean,
+                summary.failed,
+                saved as f64 / (1024.0 * 1024.0),
+            );
+        }
+
+        if !self.mismatched.is_empty() {
+            info!(
+                "extension did not match the content: {}",
+                counts(&self.mismatched)
+            );
+        }
+
+        // most common first, so the formats worth supporting stand out
+        if !self.skipped.is_empty() {
+            info!("skipped: {}", counts(&self.skipped));
+        }
+    }
+}
+
+fn ext_key(ext: Option<&str>) -> String {
+    ext.map_or_else(|| "(none)".into(), |e| format!(".{e}"))
+}
+
+/// `key=count` pairs, most common first.
+fn counts(map: &BTreeMap<String, usize>) -> String {
+    let mut counts: Vec<_> = map.iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+    let list: Vec<_> =
+        counts.iter().map(|(key, count)| format!("{key}={count}")).collect();
+    list.join(", ")
+}
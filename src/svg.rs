@@ -0,0 +1,157 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! SVG helpers.
+//!
+//! Design tools leave the author, the file name and machine paths in an
+//! SVG: `<metadata>` with its RDF, comments, and the `inkscape:` and
+//! `sodipodi:` attributes and elements holding the editor state. Cleaning
+//! drops those and copies everything else byte for byte, so the drawing is
+//! not touched.
+
+use std::io::{self, Read};
+
+use crate::{
+    format::HeaderInfo,
+    xml::{self, Rules, local_name},
+};
+
+/// Dropped with its content, in any namespace.
+const METADATA: &[u8] = b"metadata";
+/// Dropped with its content where it sits outside `<metadata>`.
+const RDF: &[u8] = b"rdf:RDF";
+
+/// Prefixes of the editor namespaces whose elements and attributes are
+/// dropped, along with their `xmlns:` declarations.
+const EDITORS: &[&[u8]] = &[b"inkscape", b"sodipodi"];
+
+/// Read the whole file and find its metadata; markup has no lengths to
+/// skip over.
+pub fn scan_elements<R: Read>(r: &mut R) -> io::Result<HeaderInfo> {
+    let mut data = Vec::new();
+    r.read_to_end(&mut data)?;
+
+    Ok(clean(&data)?.1)
+}
+
+/// Copy `data` without its metadata.
+pub fn strip_metadata(data: &[u8]) -> io::Result<Vec<u8>> {
+    Ok(clean(data)?.0)
+}
+
+fn clean(data: &[u8]) -> io::Result<(Vec<u8>, HeaderInfo)> {
+    let rules =
+        Rules { element: is_dropped, attribute: is_editor, comments: true };
+    let filtered = xml::filter(data, &rules)?;
+    if !filtered.names.iter().any(|name| local_name(name) == b"svg") {
+        return Err(invalid("missing svg element"));
+    }
+
+    Ok((filtered.data, filtered.info))
+}
+
+fn is_dropped(name: &[u8]) -> bool {
+    local_name(name) == METADATA || name == RDF || is_editor(name)
+}
+
+/// Whether a qualified name is in an editor namespace or declares one.
+fn is_editor(name: &[u8]) -> bool {
+    let prefix = match name.strip_prefix(b"xmlns:") {
+        Some(declared) => declared,
+        None => match name.iter().position(|&b| b == b':') {
+            Some(colon) => &name[..colon],
+            None => return false,
+        },
+    };
+    EDITORS.contains(&prefix)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const PLAIN: &[u8] = include_bytes!("../tests/fixtures/plain.svg");
+    const META: &[u8] = include_bytes!("../tests/fixtures/meta.svg");
+
+    fn contains(data: &[u8], needle: &[u8]) -> bool {
+        data.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn plain_drawing_is_unchanged() {
+        assert_eq!(strip_metadata(PLAIN).unwrap(), PLAIN);
+        assert_eq!(
+            scan_elements(&mut Cursor::new(PLAIN)).unwrap().metadata_bytes,
+            0
+        );
+    }
+
+    #[test]
+    fn strips_metadata_comments_and_editor_state() {
+        let cleaned = strip_metadata(META).unwrap();
+
+        for needle in [
+            &b"Jane Roe"[..],
+            b"Roe Design",
+            b"jroe",
+            b"<!--",
+            b"inkscape",
+            b"sodipodi",
+            b"rdf:",
+        ] {
+            assert!(!contains(&cleaned, needle), "{needle:?} left");
+        }
+        assert_eq!(
+            scan_elements(&mut Cursor::new(&cleaned)).unwrap().metadata_bytes,
+            0
+        );
+        assert_eq!(strip_metadata(&cleaned).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn drawing_is_copied_as_is() {
+        let cleaned = strip_metadata(META).unwrap();
+
+        for needle in [
+            &b"<?xml version=\"1.0\""[..],
+            b"<title>Logo</title>",
+            b"<g id=\"layer1\">",
+            b"<rect x=\"8\" y=\"8\" width=\"48\" height=\"48\" fill=\"#c33\"/>",
+            b"<path d=\"M16 48 L32 16 L48 48 Z\" fill=\"#fff\"/>",
+            b"<![CDATA[ rect { stroke: <none>; } ]]>",
+            b"xmlns:dc=",
+        ] {
+            assert!(contains(&cleaned, needle), "{needle:?} lost");
+        }
+        // dropped elements take their line with them
+        assert!(!contains(&cleaned, b"\n  \n"));
+        assert!(!contains(&cleaned, b"\n\n"));
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        assert!(strip_metadata(b"not an svg file").is_err());
+        assert!(strip_metadata(b"<html></html>").is_err());
+        assert!(strip_metadata(&META[..400]).is_err());
+        assert!(scan_elements(&mut Cursor::new(&META[..400])).is_err());
+    }
+}
@@ -0,0 +1,403 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Downscaled previews of cleaned images, and resized copies of them for
+//! `srcset`.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::{Context, bail};
+use jpeg_decoder::{Decoder, PixelFormat};
+use jpeg_encoder::{ColorType, Encoder};
+use log::warn;
+
+use crate::timeout;
+
+/// JPEG quality used for generated previews.
+const QUALITY: u8 = 80;
+
+/// Decoded 8-bit pixels.
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub pixels: Vec<u8>,
+    pub width: u16,
+    pub height: u16,
+    pub color: ColorType,
+}
+
+impl Image {
+    fn channels(&self) -> usize {
+        // decode_scaled only produces these three
+        match self.color {
+            ColorType::Luma => 1,
+            ColorType::Rgb => 3,
+            _ => 4,
+        }
+    }
+
+    /// Encode the pixels as a baseline JPEG.
+    pub fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        Encoder::new(&mut out, QUALITY)
+            .encode(&self.pixels, self.width, self.height, self.color)
+            .context("failed to encode JPEG")?;
+        Ok(out)
+    }
+}
+
+/// Where and how large previews should be written.
+#[derive(Debug)]
+pub struct Thumbnails {
+    pub dir: PathBuf,
+    pub size: u16,
+    /// Largest image decoded, in pixels.
+    pub max_pixels: Option<u64>,
+}
+
+impl Thumbnails {
+    /// Write the preview of `jpeg` at `rel_path` under the thumbnail dir,
+    /// unless `deadline` passes before it is made.
+    pub fn write(
+        &self,
+        rel_path: &Path,
+        jpeg: &[u8],
+        deadline: Option<Instant>,
+    ) -> anyhow::Result<()> {
+        let dst = self.dir.join(rel_path);
+
+        if let Some(parent) = dst.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("failed to create parent dir '{}'", parent.display())
+            })?;
+        }
+
+        let thumb =
+            decode_scaled(jpeg, self.size, self.max_pixels)?.encode()?;
+        timeout::check(deadline)?;
+
+        fs::write(&dst, thumb)
+            .with_context(|| format!("failed to write '{}'", dst.display()))
+    }
+}
+
+/// Resized copies of the cleaned images, from `--derivatives`.
+#[derive(Debug)]
+pub struct Derivatives {
+    /// Widths asked for, largest first.
+    pub widths: Vec<u16>,
+    /// Largest image decoded, in pixels.
+    pub max_pixels: Option<u64>,
+}
+
+/// Parse one width of `--derivatives`, in pixels.
+pub fn parse_width(s: &str) -> Result<u16, String> {
+    match s.trim().parse() {
+        Ok(0) | Err(_) => Err(format!("invalid width '{s}'")),
+        Ok(width) => Ok(width),
+    }
+}
+
+impl Derivatives {
+    pub fn new(widths: &[u16], max_pixels: Option<u64>) -> Self {
+        let mut widths = widths.to_vec();
+        widths.sort_unstable_by(|a, b| b.cmp(a));
+        widths.dedup();
+        Derivatives { widths, max_pixels }
+    }
+
+    /// Write the copies of `jpeg`, cleaned from `src` and written to `dst`,
+    /// next to it as `name-480w.jpg` and so on; widths the image does not
+    /// exceed are left to the image itself, and so are copies named like
+    /// another source next to `src`, whose cleaned copy goes there. Returns
+    /// how many were written; none are when `deadline` passes before all of
+    /// them are made.
+    ///
+    /// The image is decoded once, for the largest copy, and the smaller
+    /// ones are filtered down from it. They keep the EXIF block and colour
+    /// profile the cleaned image kept, for the orientation and colours.
+    pub fn write(
+        &self,
+        src: &Path,
+        dst: &Path,
+        jpeg: &[u8],
+        deadline: Option<Instant>,
+    ) -> anyhow::Result<usize> {
+        let Some((width, height)) = crate::jpeg::dimensions(jpeg) else {
+            bail!("JPEG has no frame header");
+        };
+        let mut widths: Vec<_> =
+            self.widths.iter().filter(|&&w| (w as u32) < width).collect();
+        widths.retain(|&&w| {
+            let source = derivative_path(src, w);
+            let taken = source.exists();
+            if taken {
+                warn!(
+                    "not writing the {w}w copy of '{}' over the source '{}'",
+                    src.display(),
+                    source.display()
+                );
+            }
+            !taken
+        });
+        let Some(&&largest) = widths.first() else {
+            return Ok(0);
+        };
+
+        // the longest side that makes the image `w` pixels wide
+        let longest = width.max(height);
+        let side = |w: u16| {
+            ((w as u32 * longest + width / 2) / width).min(u16::MAX as u32)
+                as u16
+        };
+        let image = decode_scaled(jpeg, side(largest), self.max_pixels)?;
+
+        let mut copies = Vec::with_capacity(widths.len());
+        for &&w in &widths {
+            let (tw, th) = fit(width as u16, height as u16, side(w));
+            let copy = resize(image.clone(), tw, th).encode()?;
+            let copy = crate::jpeg::carry_segments(jpeg, &copy)
+                .context("failed to copy the kept metadata")?;
+            copies.push((derivative_path(dst, w), copy));
+        }
+        timeout::check(deadline)?;
+
+        for (path, copy) in copies {
+            fs::write(&path, copy).with_context(|| {
+                format!("failed to write '{}'", path.display())
+            })?;
+        }
+
+        Ok(widths.len())
+    }
+}
+
+/// Where the copy of `path` `width` pixels wide goes.
+fn derivative_path(path: &Path, width: u16) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-{width}w.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{width}w"),
+    };
+    path.with_file_name(name)
+}
+
+/// Whether `path` is the copy of its source at one of `widths`, so that
+/// source is what to look for.
+pub fn derivative_source(path: &Path, widths: &[u16]) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_str()?;
+    let (base, digits) = stem.rsplit_once('-')?;
+    let digits = digits.strip_suffix('w')?;
+    let width: u16 = digits.parse().ok()?;
+    // only the names derivative_path writes
+    if base.is_empty()
+        || width.to_string() != digits
+        || !widths.contains(&width)
+    {
+        return None;
+    }
+    let name = match path.extension() {
+        Some(ext) => format!("{base}.{}", ext.to_string_lossy()),
+        None => base.to_string(),
+    };
+    Some(path.with_file_name(name))
+}
+
+/// Decode `jpeg` so that its longest side is at most `max_side` pixels.
+///
+/// The decoder does the coarse 1/2, 1/4 or 1/8 scaling in the DCT domain,
+/// the rest is done with a box filter. Images are never upscaled.
+///
+/// Images with more than `max_pixels` pixels are refused before decoding.
+pub fn decode_scaled(
+    jpeg: &[u8],
+    max_side: u16,
+    max_pixels: Option<u64>,
+) -> anyhow::Result<Image> {
+    let mut decoder = Decoder::new(jpeg);
+    decoder.read_info().context("failed to read JPEG header")?;
+
+    let Some(info) = decoder.info() else {
+        bail!("JPEG has no frame header");
+    };
+    let area = info.width as u64 * info.height as u64;
+    if let Some(max) = max_pixels
+        && area > max
+    {
+        bail!(
+            "image is {}x{} ({area} pixels), over the limit of {max}",
+            info.width,
+            info.height
+        );
+    }
+
+    let (tw, th) = fit(info.width, info.height, max_side);
+    let (width, height) =
+        decoder.scale(tw, th).context("failed to scale JPEG")?;
+    let pixels = decoder.decode().context("failed to decode JPEG")?;
+
+    let (pixels, color) = match info.pixel_format {
+        PixelFormat::L8 => (pixels, ColorType::Luma),
+        // keep the most significant byte of each sample
+        PixelFormat::L16 => {
+            (pixels.chunks_exact(2).map(|p| p[0]).collect(), ColorType::Luma)
+        }
+        PixelFormat::RGB24 => (pixels, ColorType::Rgb),
+        PixelFormat::CMYK32 => (pixels, ColorType::Cmyk),
+    };
+
+    Ok(resize(Image { pixels, width, height, color }, tw, th))
+}
+
+/// Largest size with the same aspect ratio that fits in `max_side`.
+pub fn fit(width: u16, height: u16, max_side: u16) -> (u16, u16) {
+    let longest = width.max(height);
+    if longest <= max_side {
+        return (width, height);
+    }
+
+    let scale = |side: u16| {
+        ((side as u32 * max_side as u32 + longest as u32 / 2) / longest as u32)
+            .max(1) as u16
+    };
+
+    (scale(width), scale(height))
+}
+
+/// Box-filter downscale to exactly `width` x `height`.
+fn resize(image: Image, width: u16, height: u16) -> Image {
+    if (image.width, image.height) == (width, height) {
+        return image;
+    }
+
+    let channels = image.channels();
+    let (sw, sh) = (image.width as usize, image.height as usize);
+    let (dw, dh) = (width as usize, height as usize);
+    let mut pixels = Vec::with_capacity(dw * dh * channels);
+
+    for y in 0..dh {
+        let (y0, y1) = (y * sh / dh, ((y + 1) * sh / dh).max(y * sh / dh + 1));
+        for x in 0..dw {
+            let (x0, x1) =
+                (x * sw / dw, ((x + 1) * sw / dw).max(x * sw / dw + 1));
+            let count = ((y1 - y0) * (x1 - x0)) as u32;
+
+            for c in 0..channels {
+                let mut sum = 0u32;
+                for sy in y0..y1 {
+                    let row = sy * sw;
+                    for sx in x0..x1 {
+                        sum += image.pixels[(row + sx) * channels + c] as u32;
+                    }
+                }
+                pixels.push(((sum + count / 2) / count) as u8);
+            }
+        }
+    }
+
+    Image { pixels, width, height, color: image.color }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: &[u8] = include_bytes!("../tests/fixtures/plain.jpg");
+
+    /// An empty directory of its own for the test `name`.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("imgst-thumb-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn fit_keeps_the_aspect_ratio() {
+        assert_eq!(fit(3000, 2000, 1000), (1000, 667));
+        assert_eq!(fit(2000, 3000, 480), (320, 480));
+        assert_eq!(fit(1001, 501, 500), (500, 250));
+        assert_eq!(fit(999, 333, 100), (100, 33));
+        // never upscaled, never below a pixel
+        assert_eq!(fit(320, 240, 480), (320, 240));
+        assert_eq!(fit(1, 5000, 480), (1, 480));
+        assert_eq!(fit(100, 100, 0), (1, 1));
+        assert_eq!(fit(0, 0, 480), (0, 0));
+    }
+
+    #[test]
+    fn derivatives_name_their_source() {
+        let widths = [480, 960];
+        for (path, width) in [
+            ("a/photo.jpg", 480),
+            ("a/photo.JPEG", 960),
+            ("a/photo", 480),
+            ("a/my-trip.jpg", 960),
+            ("a/photo-480w.jpg", 480),
+        ] {
+            let path = Path::new(path);
+            let derived = derivative_path(path, width);
+            assert_eq!(
+                derivative_source(&derived, &widths).as_deref(),
+                Some(path),
+                "{}",
+                derived.display()
+            );
+        }
+        assert_eq!(
+            derivative_path(Path::new("a/photo-480w.jpg"), 480),
+            Path::new("a/photo-480w-480w.jpg")
+        );
+
+        for path in [
+            "a/photo.jpg",
+            "a/my-trip.jpg",
+            "a/photo-1920w.jpg",
+            "a/photo-w.jpg",
+            "a/photo-0480w.jpg",
+            "a/photo-+480w.jpg",
+            "a/photo-480.jpg",
+            "a/-480w.jpg",
+        ] {
+            assert_eq!(derivative_source(Path::new(path), &widths), None);
+        }
+    }
+
+    #[test]
+    fn copies_do_not_overwrite_sources() {
+        let (src, out) = (temp_dir("src"), temp_dir("out"));
+        fs::write(src.join("photo.jpg"), PLAIN).unwrap();
+        fs::write(src.join("photo-16w.jpg"), PLAIN).unwrap();
+
+        // plain.jpg is 32 pixels wide
+        let derivatives = Derivatives::new(&[8, 16, 32], None);
+        let written = derivatives
+            .write(&src.join("photo.jpg"), &out.join("photo.jpg"), PLAIN, None)
+            .unwrap();
+        assert_eq!(written, 1);
+        let copy = fs::read(out.join("photo-8w.jpg")).unwrap();
+        assert_eq!(crate::jpeg::dimensions(&copy), Some((8, 6)));
+        assert!(!out.join("photo-16w.jpg").exists());
+        assert!(!out.join("photo-32w.jpg").exists());
+
+        fs::remove_dir_all(src).unwrap();
+        fs::remove_dir_all(out).unwrap();
+    }
+}
@@ -0,0 +1,1157 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! TIFF rewriting.
+//!
+//! Metadata tags live in the same IFDs as the offsets of the image data, so
+//! a TIFF cannot be cleaned by dropping byte ranges like the chunked
+//! formats. The file is rewritten instead: every IFD of the chain keeps
+//! only the tags describing its image, and the strips or tiles they point
+//! to are copied byte for byte to their new offsets. BigTIFF is not
+//! supported.
+//!
+//! DNG is TIFF with the raw image in a SubIFD and a set of tags needed to
+//! develop it; those are kept too when cleaning DNG.
+
+use std::{
+    collections::HashSet,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+};
+
+use crate::{
+    date::Dates,
+    format::{HeaderInfo, read_len},
+    gps::{self, Fuzz},
+};
+
+const STRIP_OFFSETS: u16 = 0x0111;
+const STRIP_BYTE_COUNTS: u16 = 0x0117;
+const TILE_OFFSETS: u16 = 0x0144;
+const TILE_BYTE_COUNTS: u16 = 0x0145;
+const SUB_IFDS: u16 = 0x014a;
+const COMPRESSION: u16 = 0x0103;
+const JPEG_IF: u16 = 0x0201;
+const JPEG_IF_LENGTH: u16 = 0x0202;
+
+/// Tags pointing at IFDs of their own, dropped along with them.
+const EXIF_IFD: u16 = 0x8769;
+const GPS_IFD: u16 = 0x8825;
+const INTEROP_IFD: u16 = 0xa005;
+
+const MAKER_NOTE: u16 = 0x927c;
+
+/// Compression of a JPEG thumbnail.
+const OLD_JPEG: u16 = 6;
+
+/// An EXIF block with an empty IFD0, for a thumbnail with nothing else.
+pub const EMPTY: &[u8] = b"MM\0\x2a\0\0\0\x08\0\0\0\0\0\0";
+
+/// Dates and their sub-second parts and time zone offsets, in IFD0 and
+/// the EXIF IFD.
+const DATE_TIMES: &[u16] = &[0x0132, 0x9003, 0x9004];
+const SUBSEC_TIMES: &[u16] = &[0x9290, 0x9291, 0x9292];
+const OFFSET_TIMES: &[u16] = &[0x9010, 0x9011, 0x9012];
+
+/// Identifying text in IFD0 and the EXIF IFD, and what `--redact` puts
+/// in its place.
+const REDACTED: &[(u16, &[u8])] = &[
+    (0x013b, b""),         // Artist
+    (0x013c, b""),         // HostComputer
+    (0xa420, b"REDACTED"), // ImageUniqueID
+    (0xa430, b""),         // CameraOwnerName
+    (0xa431, b"REDACTED"), // BodySerialNumber
+    (0xa435, b"REDACTED"), // LensSerialNumber
+];
+
+/// GPS tags kept by `--gps-fuzz`: the references and the coordinates.
+const GPS_LATITUDE: u16 = 0x0002;
+const GPS_LONGITUDE: u16 = 0x0004;
+const GPS_POSITION: &[u16] = &[0x0001, GPS_LATITUDE, 0x0003, GPS_LONGITUDE];
+
+/// Tags kept by cleaning: what is needed to render the image, its
+/// orientation and the ICC profile. Everything else goes, including the
+/// EXIF, GPS, IPTC, XMP and Photoshop blocks, descriptions, dates and
+/// camera or software names.
+const KEEP: &[u16] = &[
+    0x00fe, // NewSubfileType
+    0x00ff, // SubfileType
+    0x0100, // ImageWidth
+    0x0101, // ImageLength
+    0x0102, // BitsPerSample
+    0x0103, // Compression
+    0x0106, // PhotometricInterpretation
+    0x0107, // Threshholding
+    0x0108, // CellWidth
+    0x0109, // CellLength
+    0x010a, // FillOrder
+    STRIP_OFFSETS,
+    0x0112, // Orientation
+    0x0115, // SamplesPerPixel
+    0x0116, // RowsPerStrip
+    STRIP_BYTE_COUNTS,
+    0x0118, // MinSampleValue
+    0x0119, // MaxSampleValue
+    0x011a, // XResolution
+    0x011b, // YResolution
+    0x011c, // PlanarConfiguration
+    0x0128, // ResolutionUnit
+    0x012d, // TransferFunction
+    0x013d, // Predictor
+    0x013e, // WhitePoint
+    0x013f, // PrimaryChromaticities
+    0x0140, // ColorMap
+    0x0141, // HalftoneHints
+    0x0142, // TileWidth
+    0x0143, // TileLength
+    TILE_OFFSETS,
+    TILE_BYTE_COUNTS,
+    SUB_IFDS,
+    0x014c, // InkSet
+    0x0152, // ExtraSamples
+    0x0153, // SampleFormat
+    0x0154, // SMinSampleValue
+    0x0155, // SMaxSampleValue
+    0x015b, // JPEGTables
+    JPEG_IF,
+    JPEG_IF_LENGTH,
+    0x0211, // YCbCrCoefficients
+    0x0212, // YCbCrSubSampling
+    0x0213, // YCbCrPositioning
+    0x0214, // ReferenceBlackWhite
+    0x8773, // ICC profile
+];
+
+/// Tags DNG needs on top of [`KEEP`] to develop the raw image: the CFA
+/// layout, levels, colour calibration, profiles and opcodes. Camera serial
+/// numbers, lens info, original file names, private maker data and the
+/// preview application settings are left out, and so are the extra camera
+/// profiles, which point at IFDs of their own.
+const DNG_KEEP: &[u16] = &[
+    0x828d, // CFARepeatPatternDim
+    0x828e, // CFAPattern
+    0xc612, // DNGVersion
+    0xc613, // DNGBackwardVersion
+    0xc614, // UniqueCameraModel, needed to pick a camera profile
+    0xc616, // CFAPlaneColor
+    0xc617, // CFALayout
+    0xc618, // LinearizationTable
+    0xc619, // BlackLevelRepeatDim
+    0xc61a, // BlackLevel
+    0xc61b, // BlackLevelDeltaH
+    0xc61c, // BlackLevelDeltaV
+    0xc61d, // WhiteLevel
+    0xc61e, // DefaultScale
+    0xc61f, // DefaultCropOrigin
+    0xc620, // DefaultCropSize
+    0xc621, // ColorMatrix1
+    0xc622, // ColorMatrix2
+    0xc623, // CameraCalibration1
+    0xc624, // CameraCalibration2
+    0xc625, // ReductionMatrix1
+    0xc626, // ReductionMatrix2
+    0xc627, // AnalogBalance
+    0xc628, // AsShotNeutral
+    0xc629, // AsShotWhiteXY
+    0xc62a, // BaselineExposure
+    0xc62b, // BaselineNoise
+    0xc62c, // BaselineSharpness
+    0xc62d, // BayerGreenSplit
+    0xc62e, // LinearResponseLimit
+    0xc631, // ChromaBlurRadius
+    0xc632, // AntiAliasStrength
+    0xc633, // ShadowScale
+    0xc65a, // CalibrationIlluminant1
+    0xc65b, // CalibrationIlluminant2
+    0xc65c, // BestQualityScale
+    0xc68d, // ActiveArea
+    0xc68e, // MaskedAreas
+    0xc68f, // AsShotICCProfile
+    0xc690, // AsShotPreProfileMatrix
+    0xc691, // CurrentICCProfile
+    0xc692, // CurrentPreProfileMatrix
+    0xc6bf, // ColorimetricReference
+    0xc6f3, // CameraCalibrationSignature
+    0xc6f4, // ProfileCalibrationSignature
+    0xc6f6, // AsShotProfileName
+    0xc6f7, // NoiseReductionApplied
+    0xc6f8, // ProfileName
+    0xc6f9, // ProfileHueSatMapDims
+    0xc6fa, // ProfileHueSatMapData1
+    0xc6fb, // ProfileHueSatMapData2
+    0xc6fc, // ProfileToneCurve
+    0xc6fd, // ProfileEmbedPolicy
+    0xc714, // ForwardMatrix1
+    0xc715, // ForwardMatrix2
+    0xc71a, // PreviewColorSpace
+    0xc71c, // RawImageDigest
+    0xc71e, // SubTileBlockSize
+    0xc71f, // RowInterleaveFactor
+    0xc725, // ProfileLookTableDims
+    0xc726, // ProfileLookTableData
+    0xc740, // OpcodeList1
+    0xc741, // OpcodeList2
+    0xc74e, // OpcodeList3
+    0xc761, // NoiseProfile
+    0xc791, // OriginalDefaultFinalSize
+    0xc792, // OriginalBestQualityFinalSize
+    0xc793, // OriginalDefaultCropSize
+    0xc7a3, // ProfileHueSatMapEncoding
+    0xc7a4, // ProfileLookTableEncoding
+    0xc7a5, // BaselineExposureOffset
+    0xc7a6, // DefaultBlackRender
+    0xc7a7, // NewRawImageDigest
+    0xc7a8, // RawToPreviewGain
+    0xc7b5, // DefaultUserCrop
+];
+
+/// Tags locating image data: offsets and their byte counts.
+const IMAGE_DATA: &[(u16, u16)] = &[
+    (STRIP_OFFSETS, STRIP_BYTE_COUNTS),
+    (TILE_OFFSETS, TILE_BYTE_COUNTS),
+    (JPEG_IF, JPEG_IF_LENGTH),
+];
+
+/// Bounds on IFDs followed, so a crafted file cannot loop or recurse
+/// without end.
+const MAX_IFDS: usize = 1024;
+const MAX_DEPTH: usize = 4;
+
+const ASCII: u16 = 2;
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+const IFD: u16 = 13;
+
+pub fn is_metadata(tag: u16) -> bool {
+    !KEEP.contains(&tag)
+}
+
+pub fn is_dng_metadata(tag: u16) -> bool {
+    is_metadata(tag) && !DNG_KEEP.contains(&tag)
+}
+
+/// Walk the IFD chain, SubIFDs included, without reading the image data.
+///
+/// Every IFD entry counts as a segment; dropped EXIF, GPS and interop IFDs
+/// are added to the metadata bytes of the tag pointing at them.
+pub fn scan_ifds<R: Read + Seek>(r: &mut R) -> io::Result<HeaderInfo> {
+    scan(r, is_metadata)
+}
+
+/// Like [`scan_ifds`], counting the DNG tags as image data.
+pub fn scan_dng<R: Read + Seek>(r: &mut R) -> io::Result<HeaderInfo> {
+    scan(r, is_dng_metadata)
+}
+
+/// Rewrite `data` keeping only the tags that describe the image.
+///
+/// Strips, tiles and JPEG streams are copied as is; anything not referenced
+/// from a kept tag is left behind.
+pub fn strip_metadata(data: &[u8]) -> io::Result<Vec<u8>> {
+    rewrite(data, is_metadata)
+}
+
+/// Like [`strip_metadata`], keeping the tags needed to develop a DNG.
+pub fn strip_dng(data: &[u8]) -> io::Result<Vec<u8>> {
+    rewrite(data, is_dng_metadata)
+}
+
+/// Blank the GPS IFD of an EXIF block in place, for `--strip gps`: its
+/// entries and the values they point to are zeroed, leaving an empty IFD,
+/// so the offsets of everything else, maker notes included, stay valid.
+///
+/// With `fuzz` the latitude and longitude stay, snapped to its grid, along
+/// with their north/south and east/west references.
+pub fn strip_gps(data: &mut [u8], fuzz: Option<Fuzz>) -> io::Result<()> {
+    let Some(head) = data.get(..8) else {
+        return Err(invalid("truncated header"));
+    };
+    let (order, first) = header(head.try_into().unwrap())?;
+
+    let mut r = Cursor::new(&*data);
+    let (fields, _) = read_ifd(&mut r, order, first)?;
+    let Some(gps) = fields.iter().find(|f| f.tag == GPS_IFD) else {
+        return Ok(());
+    };
+    let off = order.u32(gps.value);
+    let (entries, _) = read_ifd(&mut r, order, off)?;
+
+    let find = |tag| entries.iter().find(|f| f.tag == tag);
+    let coordinates = match (find(GPS_LATITUDE), find(GPS_LONGITUDE)) {
+        (Some(lat), Some(lon)) => fuzz.and_then(|fuzz| {
+            let lat_value = coordinate(data, order, lat)?;
+            let lon_value = coordinate(data, order, lon)?;
+            let (lat_value, lon_value) = fuzz.apply(lat_value, lon_value);
+            Some([(lat, lat_value), (lon, lon_value)])
+        }),
+        _ => None,
+    };
+    let kept = |field: &Field| {
+        coordinates.is_some() && GPS_POSITION.contains(&field.tag)
+    };
+
+    let mut blank = |start: u64, len: u64| {
+        let range = usize::try_from(start).ok().zip(usize::try_from(len).ok());
+        range
+            .and_then(|(start, len)| {
+                data.get_mut(start..start.checked_add(len)?)
+            })
+            .ok_or_else(|| invalid("truncated field value"))
+            .map(|bytes| bytes.fill(0))
+    };
+    for field in entries.iter().filter(|f| !kept(f)) {
+        if field.external_size() > 0 {
+            blank(order.u32(field.value) as u64, field.external_size())?;
+        }
+    }
+    // the entry count, the entries and the link to the next IFD
+    blank(off as u64, 2 + 12 * entries.len() as u64 + 4)?;
+
+    let Some(coordinates) = coordinates else {
+        return Ok(());
+    };
+    for (field, degrees) in coordinates {
+        let mut at = order.u32(field.value) as usize;
+        for (num, denom) in gps::to_dms(degrees) {
+            data[at..at + 4].copy_from_slice(&order.u32_bytes(num));
+            data[at + 4..at + 8].copy_from_slice(&order.u32_bytes(denom));
+            at += 8;
+        }
+    }
+    // the kept entries move to the front of the now empty IFD
+    let kept: Vec<_> = entries.iter().filter(|f| kept(f)).collect();
+    let off = off as usize;
+    data[off..off + 2].copy_from_slice(&order.u16_bytes(kept.len() as u16));
+    for (i, field) in kept.into_iter().enumerate() {
+        let at = off + 2 + 12 * i;
+        data[at..at + 2].copy_from_slice(&order.u16_bytes(field.tag));
+        data[at + 2..at + 4].copy_from_slice(&order.u16_bytes(field.kind));
+        data[at + 4..at + 8].copy_from_slice(&order.u32_bytes(field.count));
+        data[at + 8..at + 12].copy_from_slice(&field.value);
+    }
+
+    Ok(())
+}
+
+/// Remove the maker note of an EXIF block in place, for `--strip
+/// makernotes`: its value is zeroed and its entry taken out of the EXIF
+/// IFD, whose later entries move up. Data only the maker note pointed to
+/// cannot be told apart without knowing each vendor's format, and stays.
+pub fn strip_maker_note(data: &mut [u8]) -> io::Result<()> {
+    let Some(head) = data.get(..8) else {
+        return Err(invalid("truncated header"));
+    };
+    let (order, first) = header(head.try_into().unwrap())?;
+
+    let mut r = Cursor::new(&*data);
+    let (fields, _) = read_ifd(&mut r, order, first)?;
+    let Some(exif) = fields.iter().find(|f| f.tag == EXIF_IFD) else {
+        return Ok(());
+    };
+    let off = order.u32(exif.value) as usize;
+    let (entries, _) = read_ifd(&mut r, order, off as u32)?;
+    let Some(i) = entries.iter().position(|f| f.tag == MAKER_NOTE) else {
+        return Ok(());
+    };
+
+    let note = &entries[i];
+    if note.external_size() > 0 {
+        let start = order.u32(note.value) as usize;
+        usize::try_from(note.external_size())
+            .ok()
+            .and_then(|len| data.get_mut(start..start.checked_add(len)?))
+            .ok_or_else(|| invalid("truncated field value"))?
+            .fill(0);
+    }
+
+    // the entries after it and the link to the next IFD move up
+    let n = entries.len();
+    let start = off + 2 + 12 * i;
+    let end = off + 2 + 12 * n + 4;
+    data.copy_within(start + 12..end, start);
+    data[end - 12..end].fill(0);
+    data[off..off + 2].copy_from_slice(&order.u16_bytes(n as u16 - 1));
+
+    Ok(())
+}
+
+/// Remove the thumbnail of an EXIF block in place: IFD1, the values it
+/// points to and the image data are zeroed and IFD0 no longer links to it.
+///
+/// Returns the length the block can be cut to, which is where the
+/// thumbnail starts when nothing else follows it, as cameras write it.
+pub fn strip_thumbnail(data: &mut [u8]) -> io::Result<usize> {
+    let Some(head) = data.get(..8) else {
+        return Err(invalid("truncated header"));
+    };
+    let (order, first) = header(head.try_into().unwrap())?;
+
+    let mut r = Cursor::new(&*data);
+    let (fields, next) = read_ifd(&mut r, order, first)?;
+    if next == 0 {
+        return Ok(data.len());
+    }
+    let (entries, _) = read_ifd(&mut r, order, next)?;
+
+    let mut ranges = vec![(next as u64, 2 + 12 * entries.len() as u64 + 4)];
+    for field in &entries {
+        if field.external_size() > 0 {
+            ranges
+                .push((order.u32(field.value) as u64, field.external_size()));
+        }
+    }
+    let find = |tag| entries.iter().find(|f| f.tag == tag);
+    for &(off_tag, len_tag) in IMAGE_DATA {
+        let (Some(offsets), Some(lens)) = (find(off_tag), find(len_tag))
+        else {
+            continue;
+        };
+        let offsets = read_value(&mut r, order, offsets)
+            .and_then(|value| numbers(order, offsets.kind, &value))?;
+        let lens = read_value(&mut r, order, lens)
+            .and_then(|value| numbers(order, lens.kind, &value))?;
+        ranges.extend(
+            offsets.into_iter().zip(lens).map(|(o, n)| (o as u64, n as u64)),
+        );
+    }
+
+    for &(start, len) in &ranges {
+        usize::try_from(start)
+            .ok()
+            .zip(usize::try_from(len).ok())
+            .and_then(|(start, len)| {
+                data.get_mut(start..start.checked_add(len)?)
+            })
+            .ok_or_else(|| invalid("truncated thumbnail"))?
+            .fill(0);
+    }
+    let link = first as usize + 2 + 12 * fields.len();
+    data[link..link + 4].fill(0);
+
+    let start = ranges.iter().map(|&(start, _)| start).min().unwrap();
+    let end = ranges.iter().map(|&(start, len)| start + len).max().unwrap();
+    let start = start as usize;
+    // a padding byte may follow the last value
+    if end + 1 >= data.len() as u64 && data[start..].iter().all(|&b| b == 0) {
+        return Ok(start);
+    }
+    Ok(data.len())
+}
+
+/// Copy an EXIF block with `jpeg` as its thumbnail, in a new IFD1 at the
+/// end, replacing the one it had.
+pub fn set_thumbnail(data: &[u8], jpeg: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = data.to_vec();
+    let len = strip_thumbnail(&mut out)?;
+    out.truncate(len);
+    if out.len() % 2 == 1 {
+        out.push(0);
+    }
+    let (order, first) = header(out[..8].try_into().unwrap())?;
+    let (fields, _) = read_ifd(&mut Cursor::new(&out), order, first)?;
+
+    // the entry count, three entries and the link to the next IFD
+    let ifd_size = 2 + 12 * 3 + 4;
+    if out.len() + ifd_size + jpeg.len() > u32::MAX as usize {
+        return Err(invalid("thumbnail does not fit the EXIF block"));
+    }
+    let ifd = out.len() as u32;
+    let entries = [
+        (COMPRESSION, SHORT, [order.u16_bytes(OLD_JPEG), [0; 2]].concat()),
+        (JPEG_IF, LONG, order.u32_bytes(ifd + ifd_size as u32).to_vec()),
+        (JPEG_IF_LENGTH, LONG, order.u32_bytes(jpeg.len() as u32).to_vec()),
+    ];
+    out.extend_from_slice(&order.u16_bytes(entries.len() as u16));
+    for (tag, kind, value) in entries {
+        out.extend_from_slice(&order.u16_bytes(tag));
+        out.extend_from_slice(&order.u16_bytes(kind));
+        out.extend_from_slice(&order.u32_bytes(1));
+        out.extend_from_slice(&value);
+    }
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(jpeg);
+
+    let link = first as usize + 2 + 12 * fields.len();
+    out[link..link + 4].copy_from_slice(&order.u32_bytes(ifd));
+
+    Ok(out)
+}
+
+/// Rewrite the dates of an EXIF block in place for `--date-offset`,
+/// `--date-round` and `--date-remove`. Sub-second parts go when dates are
+/// rounded or removed, and time zone offsets when they are removed; gone
+/// values are filled with blanks.
+pub fn rewrite_dates(data: &mut [u8], dates: &Dates) -> io::Result<()> {
+    if !dates.is_active() {
+        return Ok(());
+    }
+    let (order, ifds) = main_ifds(data)?;
+
+    for (off, fields) in ifds {
+        for (i, field) in fields.iter().enumerate() {
+            let blank = match field.tag {
+                _ if field.kind != ASCII => continue,
+                tag if DATE_TIMES.contains(&tag) => false,
+                tag if SUBSEC_TIMES.contains(&tag) => dates.drops_subsec(),
+                tag if OFFSET_TIMES.contains(&tag) => dates.remove,
+                _ => continue,
+            };
+            let value = value_mut(data, order, off, i, field)?;
+
+            if blank {
+                let len =
+                    value.iter().position(|&b| b == 0).unwrap_or(value.len());
+                value[..len].fill(b' ');
+            } else if let Some(date) = dates.exif(value) {
+                value[..date.len()].copy_from_slice(&date);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace the identifying text of an EXIF block in place for `--redact`:
+/// names go blank and serial numbers read `REDACTED`, cut to the size of
+/// the value they replace, so every field keeps its place.
+pub fn redact(data: &mut [u8]) -> io::Result<()> {
+    let (order, ifds) = main_ifds(data)?;
+
+    for (off, fields) in ifds {
+        for (i, field) in fields.iter().enumerate() {
+            let Some(&(_, placeholder)) =
+                REDACTED.iter().find(|&&(tag, _)| tag == field.tag)
+            else {
+                continue;
+            };
+            if field.kind != ASCII {
+                continue;
+            }
+            let value = value_mut(data, order, off, i, field)?;
+
+            // the text stays NUL terminated
+            let len = placeholder.len().min(value.len().saturating_sub(1));
+            value.fill(0);
+            value[..len].copy_from_slice(&placeholder[..len]);
+        }
+    }
+
+    Ok(())
+}
+
+/// The offset of an IFD and its entries.
+type Ifd = (u32, Vec<Field>);
+
+/// IFD0 and the EXIF IFD of an EXIF block.
+fn main_ifds(data: &[u8]) -> io::Result<(Order, Vec<Ifd>)> {
+    let Some(head) = data.get(..8) else {
+        return Err(invalid("truncated header"));
+    };
+    let (order, first) = header(head.try_into().unwrap())?;
+
+    let mut r = Cursor::new(data);
+    let (ifd0, _) = read_ifd(&mut r, order, first)?;
+    let mut ifds = vec![(first, ifd0)];
+    if let Some(exif) = ifds[0].1.iter().find(|f| f.tag == EXIF_IFD) {
+        let off = order.u32(exif.value);
+        ifds.push((off, read_ifd(&mut r, order, off)?.0));
+    }
+
+    Ok((order, ifds))
+}
+
+/// The value of `field`, entry `i` of the IFD at `off`.
+fn value_mut<'a>(
+    data: &'a mut [u8],
+    order: Order,
+    off: u32,
+    i: usize,
+    field: &Field,
+) -> io::Result<&'a mut [u8]> {
+    // short values sit in the entry itself
+    let size = field.size() as usize;
+    let start = match size {
+        ..=4 => off as usize + 2 + 12 * i + 8,
+        _ => order.u32(field.value) as usize,
+    };
+    start
+        .checked_add(size)
+        .and_then(|end| data.get_mut(start..end))
+        .ok_or_else(|| invalid("truncated field value"))
+}
+
+/// A latitude or longitude in degrees, if `field` holds one.
+fn coordinate(data: &[u8], order: Order, field: &Field) -> Option<f64> {
+    if field.kind != RATIONAL || field.count != 3 {
+        return None;
+    }
+    let start = order.u32(field.value) as usize;
+    let value = data.get(start..start.checked_add(24)?)?;
+
+    let mut dms = [(0, 0); 3];
+    for (part, raw) in dms.iter_mut().zip(value.chunks_exact(8)) {
+        let num = order.u32(raw[..4].try_into().unwrap());
+        let denom = order.u32(raw[4..].try_into().unwrap());
+        *part = (num, denom);
+    }
+    gps::to_degrees(dms)
+}
+
+fn scan<R: Read + Seek>(
+    r: &mut R,
+    is_metadata: fn(u16) -> bool,
+) -> io::Result<HeaderInfo> {
+    let mut head = [0u8; 8];
+    r.read_exact(&mut head)?;
+    let (order, first) = header(&head)?;
+
+    let mut scan = Scan { r, order, is_metadata, seen: HashSet::new() };
+    let mut info = HeaderInfo::default();
+    scan.chain(first, 0, &mut info)?;
+
+    Ok(info)
+}
+
+fn rewrite(data: &[u8], is_metadata: fn(u16) -> bool) -> io::Result<Vec<u8>> {
+    let Some(head) = data.get(..8) else {
+        return Err(invalid("truncated header"));
+    };
+    let (order, first) = header(head.try_into().unwrap())?;
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&head[..4]);
+    out.extend_from_slice(&[0; 4]);
+
+    let mut w = Writer {
+        data,
+        r: Cursor::new(data),
+        order,
+        is_metadata,
+        out,
+        seen: HashSet::new(),
+    };
+    let first = w.chain(first, 0)?;
+    w.patch(4, first);
+
+    Ok(w.out)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Order {
+    Little,
+    Big,
+}
+
+impl Order {
+    fn u16(self, b: [u8; 2]) -> u16 {
+        match self {
+            Order::Little => u16::from_le_bytes(b),
+            Order::Big => u16::from_be_bytes(b),
+        }
+    }
+
+    fn u32(self, b: [u8; 4]) -> u32 {
+        match self {
+            Order::Little => u32::from_le_bytes(b),
+            Order::Big => u32::from_be_bytes(b),
+        }
+    }
+
+    fn u16_bytes(self, v: u16) -> [u8; 2] {
+        match self {
+            Order::Little => v.to_le_bytes(),
+            Order::Big => v.to_be_bytes(),
+        }
+    }
+
+    fn u32_bytes(self, v: u32) -> [u8; 4] {
+        match self {
+            Order::Little => v.to_le_bytes(),
+            Order::Big => v.to_be_bytes(),
+        }
+    }
+}
+
+/// Byte order and offset of the first IFD.
+fn header(head: &[u8; 8]) -> io::Result<(Order, u32)> {
+    let order = match &head[..2] {
+        b"II" => Order::Little,
+        b"MM" => Order::Big,
+        _ => return Err(invalid("missing TIFF header")),
+    };
+    match order.u16([head[2], head[3]]) {
+        42 => {}
+        43 => return Err(invalid("BigTIFF is not supported")),
+        _ => return Err(invalid("missing TIFF header")),
+    }
+
+    Ok((order, order.u32([head[4], head[5], head[6], head[7]])))
+}
+
+/// An IFD entry as stored, with its value or value offset.
+struct Field {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    value: [u8; 4],
+}
+
+impl Field {
+    fn parse(order: Order, raw: &[u8]) -> Self {
+        Field {
+            tag: order.u16([raw[0], raw[1]]),
+            kind: order.u16([raw[2], raw[3]]),
+            count: order.u32([raw[4], raw[5], raw[6], raw[7]]),
+            value: [raw[8], raw[9], raw[10], raw[11]],
+        }
+    }
+
+    /// Size of the value; unknown types are taken as empty.
+    fn size(&self) -> u64 {
+        let unit = match self.kind {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 | 13 => 4,
+            5 | 10 | 12 => 8,
+            _ => 0,
+        };
+        unit * self.count as u64
+    }
+
+    /// Bytes stored outside the entry.
+    fn external_size(&self) -> u64 {
+        match self.size() {
+            n if n > 4 => n,
+            _ => 0,
+        }
+    }
+}
+
+/// Read the IFD at `off`, returning its entries and the next IFD offset.
+fn read_ifd<R: Read + Seek>(
+    r: &mut R,
+    order: Order,
+    off: u32,
+) -> io::Result<(Vec<Field>, u32)> {
+    r.seek(SeekFrom::Start(off as u64))?;
+    let mut n = [0u8; 2];
+    r.read_exact(&mut n)?;
+
+    let mut raw = vec![0u8; order.u16(n) as usize * 12 + 4];
+    r.read_exact(&mut raw)?;
+    let (entries, next) = raw.split_at(raw.len() - 4);
+
+    let fields =
+        entries.chunks_exact(12).map(|e| Field::parse(order, e)).collect();
+    Ok((fields, order.u32(next.try_into().unwrap())))
+}
+
+/// Raw value bytes of `field`, wherever they are stored.
+fn read_value<R: Read + Seek>(
+    r: &mut R,
+    order: Order,
+    field: &Field,
+) -> io::Result<Vec<u8>> {
+    let size = field.size();
+    if size <= 4 {
+        return Ok(field.value[..size as usize].to_vec());
+    }
+
+    r.seek(SeekFrom::Start(order.u32(field.value) as u64))?;
+    read_len(r, size)?.ok_or_else(|| invalid("truncated field value"))
+}
+
+/// Value of an offset or byte count field, which may be SHORT or LONG.
+fn numbers(order: Order, kind: u16, value: &[u8]) -> io::Result<Vec<u32>> {
+    match kind {
+        SHORT => Ok(value
+            .chunks_exact(2)
+            .map(|b| order.u16([b[0], b[1]]) as u32)
+            .collect()),
+        LONG | IFD => Ok(value
+            .chunks_exact(4)
+            .map(|b| order.u32([b[0], b[1], b[2], b[3]]))
+            .collect()),
+        _ => Err(invalid("unexpected type for offsets")),
+    }
+}
+
+struct Scan<'a, R> {
+    r: &'a mut R,
+    order: Order,
+    is_metadata: fn(u16) -> bool,
+    seen: HashSet<u32>,
+}
+
+impl<R: Read + Seek> Scan<'_, R> {
+    fn visit(&mut self, off: u32, depth: usize) -> io::Result<()> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("IFDs nested too deep"));
+        }
+        if !self.seen.insert(off) || self.seen.len() > MAX_IFDS {
+            return Err(invalid("IFD loop or too many IFDs"));
+        }
+        Ok(())
+    }
+
+    fn chain(
+        &mut self,
+        mut off: u32,
+        depth: usize,
+        info: &mut HeaderInfo,
+    ) -> io::Result<()> {
+        while off != 0 {
+            self.visit(off, depth)?;
+            let (fields, next) = read_ifd(self.r, self.order, off)?;
+
+            for field in &fields {
+                info.segments += 1;
+                if field.tag == SUB_IFDS {
+                    let value = read_value(self.r, self.order, field)?;
+                    for sub in numbers(self.order, field.kind, &value)? {
+                        self.chain(sub, depth + 1, info)?;
+                    }
+                } else if (self.is_metadata)(field.tag) {
+                    info.metadata_bytes += 12 + field.external_size();
+                    if matches!(field.tag, EXIF_IFD | GPS_IFD) {
+                        let off = self.order.u32(field.value);
+                        info.metadata_bytes +=
+                            self.ifd_size(off, depth + 1)?;
+                    }
+                }
+            }
+            off = next;
+        }
+
+        Ok(())
+    }
+
+    /// Bytes taken by a metadata IFD and the values it points at.
+    fn ifd_size(&mut self, off: u32, depth: usize) -> io::Result<u64> {
+        self.visit(off, depth)?;
+        let (fields, _) = read_ifd(self.r, self.order, off)?;
+
+        let mut size = 2 + 12 * fields.len() as u64 + 4;
+        for field in &fields {
+            size += field.external_size();
+            if field.tag == INTEROP_IFD {
+                let off = self.order.u32(field.value);
+                size += self.ifd_size(off, depth + 1)?;
+            }
+        }
+
+        Ok(size)
+    }
+}
+
+/// A kept entry with its value, ready to be laid out again.
+struct Entry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    value: Vec<u8>,
+}
+
+struct Writer<'a> {
+    data: &'a [u8],
+    r: Cursor<&'a [u8]>,
+    order: Order,
+    is_metadata: fn(u16) -> bool,
+    out: Vec<u8>,
+    seen: HashSet<u32>,
+}
+
+impl Writer<'_> {
+    /// Write the IFD chain starting at `off`, returning its new offset.
+    fn chain(&mut self, mut off: u32, depth: usize) -> io::Result<u32> {
+        let mut first = 0;
+        let mut link = None;
+
+        while off != 0 {
+            if depth > MAX_DEPTH {
+                return Err(invalid("IFDs nested too deep"));
+            }
+            if !self.seen.insert(off) || self.seen.len() > MAX_IFDS {
+                return Err(invalid("IFD loop or too many IFDs"));
+            }
+
+            let (fields, next) = read_ifd(&mut self.r, self.order, off)?;
+            let (at, next_at) = self.ifd(&fields, depth)?;
+            match link {
+                Some(link) => self.patch(link, at),
+                None => first = at,
+            }
+            link = Some(next_at);
+            off = next;
+        }
+
+        Ok(first)
+    }
+
+    /// Write one IFD after the data it points at.
+    ///
+    /// Returns the offset of the IFD and the position of its next-IFD link.
+    fn ifd(
+        &mut self,
+        fields: &[Field],
+        depth: usize,
+    ) -> io::Result<(u32, usize)> {
+        let mut kept = Vec::new();
+        for field in fields.iter().filter(|f| !(self.is_metadata)(f.tag)) {
+            kept.push(Entry {
+                tag: field.tag,
+                kind: field.kind,
+                count: field.count,
+                value: read_value(&mut self.r, self.order, field)?,
+            });
+        }
+        kept.sort_by_key(|e| e.tag);
+        let find =
+            |kept: &[Entry], tag| kept.iter().position(|e| e.tag == tag);
+
+        for &(offsets, counts) in IMAGE_DATA {
+            let Some(i) = find(&kept, offsets) else {
+                continue;
+            };
+            let Some(j) = find(&kept, counts) else {
+                return Err(invalid("image data without byte counts"));
+            };
+
+            let starts = numbers(self.order, kept[i].kind, &kept[i].value)?;
+            let lens = numbers(self.order, kept[j].kind, &kept[j].value)?;
+            if starts.len() != lens.len() {
+                return Err(invalid("offsets and byte counts do not match"));
+            }
+
+            let mut moved = Vec::with_capacity(starts.len());
+            for (start, len) in starts.into_iter().zip(lens) {
+                let chunk = (start as usize)
+                    .checked_add(len as usize)
+                    .and_then(|end| self.data.get(start as usize..end))
+                    .ok_or_else(|| invalid("image data out of range"))?;
+                moved.push(self.append(chunk)?);
+            }
+            kept[i] = self.longs(offsets, &moved);
+        }
+
+        if let Some(i) = find(&kept, SUB_IFDS) {
+            let subs = numbers(self.order, kept[i].kind, &kept[i].value)?;
+            let mut moved = Vec::with_capacity(subs.len());
+            for sub in subs {
+                moved.push(self.chain(sub, depth + 1)?);
+            }
+            kept[i] = self.longs(SUB_IFDS, &moved);
+        }
+
+        let mut values = Vec::with_capacity(kept.len());
+        for entry in &kept {
+            let mut value = [0u8; 4];
+            if entry.value.len() > 4 {
+                value = self.order.u32_bytes(self.append(&entry.value)?);
+            } else {
+                value[..entry.value.len()].copy_from_slice(&entry.value);
+            }
+            values.push(value);
+        }
+
+        let at = self.append(&self.order.u16_bytes(kept.len() as u16))?;
+        for (entry, value) in kept.iter().zip(values) {
+            self.out.extend_from_slice(&self.order.u16_bytes(entry.tag));
+            self.out.extend_from_slice(&self.order.u16_bytes(entry.kind));
+            self.out.extend_from_slice(&self.order.u32_bytes(entry.count));
+            self.out.extend_from_slice(&value);
+        }
+        let next_at = self.out.len();
+        self.out.extend_from_slice(&[0; 4]);
+
+        Ok((at, next_at))
+    }
+
+    /// A LONG entry holding new offsets.
+    fn longs(&self, tag: u16, offsets: &[u32]) -> Entry {
+        Entry {
+            tag,
+            kind: LONG,
+            count: offsets.len() as u32,
+            value: offsets
+                .iter()
+                .flat_map(|&off| self.order.u32_bytes(off))
+                .collect(),
+        }
+    }
+
+    /// Append `bytes` on a word boundary, returning their offset.
+    fn append(&mut self, bytes: &[u8]) -> io::Result<u32> {
+        if self.out.len() % 2 == 1 {
+            self.out.push(0);
+        }
+        let at = u32::try_from(self.out.len())
+            .map_err(|_| invalid("rewritten file too large for TIFF"))?;
+        self.out.extend_from_slice(bytes);
+
+        Ok(at)
+    }
+
+    fn patch(&mut self, pos: usize, off: u32) {
+        self.out[pos..pos + 4].copy_from_slice(&self.order.u32_bytes(off));
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: &[u8] = include_bytes!("../tests/fixtures/plain.tiff");
+    const META: &[u8] = include_bytes!("../tests/fixtures/meta.tiff");
+    const DNG: &[u8] = include_bytes!("../tests/fixtures/meta.dng");
+
+    /// Tags of every IFD in the chain.
+    fn pages(data: &[u8]) -> Vec<Vec<u16>> {
+        let (order, mut off) = header(data[..8].try_into().unwrap()).unwrap();
+        let mut pages = Vec::new();
+        while off != 0 {
+            let (fields, next) =
+                read_ifd(&mut Cursor::new(data), order, off).unwrap();
+            pages.push(fields.iter().map(|f| f.tag).collect());
+            off = next;
+        }
+        pages
+    }
+
+    /// Image data of every IFD in the chain, strip by strip.
+    fn strips(data: &[u8]) -> Vec<Vec<&[u8]>> {
+        let (order, mut off) = header(data[..8].try_into().unwrap()).unwrap();
+        let mut r = Cursor::new(data);
+        let mut pages = Vec::new();
+        while off != 0 {
+            let (fields, next) = read_ifd(&mut r, order, off).unwrap();
+            let get = |tag| {
+                let field = fields.iter().find(|f| f.tag == tag).unwrap();
+                let value = read_value(&mut Cursor::new(data), order, field);
+                numbers(order, field.kind, &value.unwrap()).unwrap()
+            };
+            let data = get(STRIP_OFFSETS)
+                .into_iter()
+                .zip(get(STRIP_BYTE_COUNTS))
+                .map(|(start, len)| &data[start as usize..][..len as usize])
+                .collect();
+            pages.push(data);
+            off = next;
+        }
+        pages
+    }
+
+    #[test]
+    fn plain_image_is_unchanged() {
+        assert_eq!(strip_metadata(PLAIN).unwrap(), PLAIN);
+        assert_eq!(
+            scan_ifds(&mut Cursor::new(PLAIN)).unwrap().metadata_bytes,
+            0
+        );
+    }
+
+    #[test]
+    fn scan_finds_metadata_tags() {
+        let info = scan_ifds(&mut Cursor::new(META)).unwrap();
+
+        assert_eq!(info.segments, 36);
+        assert!(info.metadata_bytes > 0);
+    }
+
+    #[test]
+    fn strips_metadata_tags() {
+        let cleaned = strip_metadata(META).unwrap();
+        let pages = pages(&cleaned);
+
+        assert_eq!(pages.len(), 2);
+        for tag in [0x010f, 0x0131, 0x02bc, 0x83bb, EXIF_IFD, GPS_IFD] {
+            assert!(!pages[0].contains(&tag), "tag {tag:#x} kept");
+        }
+        // orientation and colour profile stay
+        assert!(pages[0].contains(&0x0112));
+        assert!(pages[0].contains(&0x8773));
+        assert!(!pages[1].contains(&0x0131));
+        assert_eq!(
+            scan_ifds(&mut Cursor::new(&cleaned)).unwrap().metadata_bytes,
+            0
+        );
+    }
+
+    #[test]
+    fn image_data_is_bit_identical() {
+        let cleaned = strip_metadata(META).unwrap();
+
+        assert_eq!(strips(&cleaned), strips(META));
+        assert!(cleaned.len() < META.len());
+        assert_eq!(strip_metadata(&cleaned).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn dng_keeps_raw_image_and_develop_tags() {
+        let cleaned = strip_dng(DNG).unwrap();
+        let (order, first) = header(cleaned[..8].try_into().unwrap()).unwrap();
+        let mut r = Cursor::new(&cleaned[..]);
+        let (fields, _) = read_ifd(&mut r, order, first).unwrap();
+        let tags: Vec<_> = fields.iter().map(|f| f.tag).collect();
+
+        for tag in [0xc612, 0xc614, 0xc621, SUB_IFDS] {
+            assert!(tags.contains(&tag), "tag {tag:#x} dropped");
+        }
+        for tag in [0x010f, 0xc62f, 0xc634, 0xc68b, EXIF_IFD] {
+            assert!(!tags.contains(&tag), "tag {tag:#x} kept");
+        }
+
+        let sub = fields.iter().find(|f| f.tag == SUB_IFDS).unwrap();
+        let (raw, _) = read_ifd(&mut r, order, order.u32(sub.value)).unwrap();
+        let raw_tags: Vec<_> = raw.iter().map(|f| f.tag).collect();
+        assert!(raw_tags.contains(&0x828e));
+        assert!(raw_tags.contains(&0xc61d));
+
+        let strip = raw.iter().find(|f| f.tag == STRIP_OFFSETS).unwrap();
+        let start = order.u32(strip.value) as usize;
+        // the fixture's raw samples are the bytes 100 to 115
+        let samples: Vec<u8> = (100..116).collect();
+        assert_eq!(&cleaned[start..start + 16], samples);
+
+        assert_eq!(
+            scan_dng(&mut Cursor::new(&cleaned)).unwrap().metadata_bytes,
+            0
+        );
+        assert_eq!(strip_dng(&cleaned).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        assert!(strip_metadata(b"not a tiff").is_err());
+        assert!(strip_metadata(&META[..40]).is_err());
+        assert!(strip_metadata(b"II\x2b\0\x08\0\0\0\0\0\0\0").is_err());
+
+        // an IFD linking back to itself
+        let mut looped = PLAIN.to_vec();
+        let first = u32::from_be_bytes(looped[4..8].try_into().unwrap());
+        let n = u16::from_be_bytes(
+            looped[first as usize..][..2].try_into().unwrap(),
+        );
+        let link = first as usize + 2 + 12 * n as usize;
+        looped[link..link + 4].copy_from_slice(&first.to_be_bytes());
+        assert!(strip_metadata(&looped).is_err());
+        assert!(scan_ifds(&mut Cursor::new(&looped)).is_err());
+    }
+}
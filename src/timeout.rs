@@ -0,0 +1,141 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Per-file time limit for `--file-timeout`.
+//!
+//! A thread cannot be interrupted, so the file is processed on a helper
+//! thread and the walker stops waiting for it once the limit is reached.
+//! The helper is left behind; the work it does is expected to check the
+//! deadline before writing anything, so a late finish has no effect.
+
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, bail};
+
+/// Parse `--file-timeout`: a number with an optional `ms`, `s`, `m` or `h`
+/// unit, seconds when there is none.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: u64 =
+        value.parse().map_err(|_| format!("invalid duration '{s}'"))?;
+
+    let secs = |per: u64| {
+        value
+            .checked_mul(per)
+            .map(Duration::from_secs)
+            .ok_or_else(|| format!("duration '{s}' is too long"))
+    };
+    let duration = match unit {
+        "ms" => Duration::from_millis(value),
+        "" | "s" => secs(1)?,
+        "m" => secs(60)?,
+        "h" => secs(60 * 60)?,
+        _ => return Err(format!("unknown unit in duration '{s}'")),
+    };
+    if duration.is_zero() {
+        return Err("duration must be greater than zero".into());
+    }
+
+    Ok(duration)
+}
+
+/// Run `work` on a helper thread, failing once `limit` has passed.
+///
+/// `work` gets the deadline to check before it writes its results.
+pub fn run<T, F>(limit: Duration, work: F) -> anyhow::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(Instant) -> anyhow::Result<T> + Send + 'static,
+{
+    let deadline = Instant::now() + limit;
+    let (tx, rx) = mpsc::channel();
+
+    thread::Builder::new()
+        .name("imgst-file".into())
+        .spawn(move || {
+            // the receiver is gone when the file timed out
+            let _ = tx.send(work(deadline));
+        })
+        .context("failed to spawn file worker")?;
+
+    match rx.recv_timeout(limit) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => {
+            bail!("timed out after {limit:?}, nothing written")
+        }
+        Err(RecvTimeoutError::Disconnected) => bail!("file worker panicked"),
+    }
+}
+
+/// Fail if `deadline` has passed.
+pub fn check(deadline: Option<Instant>) -> anyhow::Result<()> {
+    if deadline.is_some_and(|deadline| Instant::now() > deadline) {
+        bail!("timed out, output not written");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_take_a_unit() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn rejects_zero_and_garbage() {
+        let zero = Err("duration must be greater than zero".to_string());
+        for s in ["0", "0s", "0ms", "0h"] {
+            assert_eq!(parse_duration(s), zero, "{s}");
+        }
+        for s in ["", "s", "abc", "-5s", " 5s"] {
+            assert_eq!(
+                parse_duration(s),
+                Err(format!("invalid duration '{s}'")),
+                "{s}"
+            );
+        }
+        for s in ["5d", "5 s", "5sec", "5S", "1.5s"] {
+            assert_eq!(
+                parse_duration(s),
+                Err(format!("unknown unit in duration '{s}'")),
+                "{s}"
+            );
+        }
+        let long = format!("{}h", u64::MAX / 60);
+        assert!(parse_duration(&long).is_err());
+    }
+
+    #[test]
+    fn deadline_check_fails_once_passed() {
+        assert!(check(None).is_ok());
+        assert!(check(Some(Instant::now() + Duration::from_secs(60))).is_ok());
+        assert!(
+            check(Some(Instant::now() - Duration::from_millis(1))).is_err()
+        );
+    }
+}
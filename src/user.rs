@@ -0,0 +1,215 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Dropping root privileges for `--run-as`.
+//!
+//! A service started as root hands the run over to an unprivileged user
+//! right after the arguments are checked, before the output tree is
+//! created, so every file written belongs to that user. The supplementary
+//! groups of root are cleared and only the given group is kept.
+
+use std::{ffi::CString, io, mem, ptr};
+
+/// The user and group to switch to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunAs {
+    pub uid: libc::uid_t,
+    pub gid: libc::gid_t,
+}
+
+/// Size of the buffer for the strings of a passwd or group entry.
+const ENTRY_BUF: usize = 16 * 1024;
+
+/// Parse `--run-as`: `user:group`, or `user` for their primary group, each
+/// a name or a numeric id.
+pub fn parse_run_as(s: &str) -> Result<RunAs, String> {
+    let (user, group) = match s.split_once(':') {
+        Some((user, group)) => (user, Some(group)),
+        None => (s, None),
+    };
+    if user.is_empty() || group.is_some_and(str::is_empty) {
+        return Err(format!("invalid user and group '{s}'"));
+    }
+
+    let (uid, primary) = match user.parse() {
+        Ok(uid) => (uid, passwd_by_uid(uid).map(|(_, gid)| gid)),
+        Err(_) => passwd_by_name(user)
+            .map(|(uid, gid)| (uid, Some(gid)))
+            .ok_or_else(|| format!("unknown user '{user}'"))?,
+    };
+    let gid = match group {
+        Some(group) => match group.parse() {
+            Ok(gid) => gid,
+            Err(_) => group_by_name(group)
+                .ok_or_else(|| format!("unknown group '{group}'"))?,
+        },
+        None => primary.ok_or_else(|| {
+            format!("user {uid} has no passwd entry, give a group too")
+        })?,
+    };
+
+    Ok(RunAs { uid, gid })
+}
+
+/// Switch the process to `to` for good.
+pub fn drop_to(to: RunAs) -> io::Result<()> {
+    // SAFETY: no arguments
+    if unsafe { libc::geteuid() } != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "--run-as needs imgst to be started as root",
+        ));
+    }
+
+    let check = |ret: libc::c_int| match ret {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    };
+    // SAFETY: the group list is the one gid, which outlives the call
+    check(unsafe { libc::setgroups(1, &to.gid) })?;
+    // SAFETY: plain integer arguments
+    check(unsafe { libc::setgid(to.gid) })?;
+    // SAFETY: plain integer arguments
+    check(unsafe { libc::setuid(to.uid) })?;
+
+    // SAFETY: plain integer arguments; this must fail now
+    if to.uid != 0 && unsafe { libc::setuid(0) } == 0 {
+        return Err(io::Error::other("root could be regained after setuid"));
+    }
+
+    Ok(())
+}
+
+fn passwd_by_name(name: &str) -> Option<(libc::uid_t, libc::gid_t)> {
+    let name = CString::new(name).ok()?;
+    passwd(|pwd, buf, result| {
+        // SAFETY: the name is nul-terminated, the rest are valid out
+        // pointers and the buffer length is its own
+        unsafe {
+            libc::getpwnam_r(
+                name.as_ptr(),
+                pwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                result,
+            )
+        }
+    })
+}
+
+fn passwd_by_uid(uid: libc::uid_t) -> Option<(libc::uid_t, libc::gid_t)> {
+    passwd(|pwd, buf, result| {
+        // SAFETY: valid out pointers and the buffer length is its own
+        unsafe {
+            libc::getpwuid_r(uid, pwd, buf.as_mut_ptr(), buf.len(), result)
+        }
+    })
+}
+
+fn passwd(
+    lookup: impl FnOnce(
+        &mut libc::passwd,
+        &mut [libc::c_char],
+        &mut *mut libc::passwd,
+    ) -> libc::c_int,
+) -> Option<(libc::uid_t, libc::gid_t)> {
+    // SAFETY: passwd is plain data, all zeros is a valid value
+    let mut pwd: libc::passwd = unsafe { mem::zeroed() };
+    let mut buf = vec![0; ENTRY_BUF];
+    let mut result = ptr::null_mut();
+
+    let ret = lookup(&mut pwd, &mut buf, &mut result);
+    (ret == 0 && !result.is_null()).then_some((pwd.pw_uid, pwd.pw_gid))
+}
+
+fn group_by_name(name: &str) -> Option<libc::gid_t> {
+    let name = CString::new(name).ok()?;
+    // SAFETY: group is plain data, all zeros is a valid value
+    let mut grp: libc::group = unsafe { mem::zeroed() };
+    let mut buf = vec![0; ENTRY_BUF];
+    let mut result = ptr::null_mut();
+
+    // SAFETY: the name is nul-terminated, the rest are valid out pointers
+    // and the buffer length is its own
+    let ret = unsafe {
+        libc::getgrnam_r(
+            name.as_ptr(),
+            &mut grp,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    (ret == 0 && !result.is_null()).then_some(grp.gr_gid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // root and the daemon group are 0 and 1 on Linux, macOS and the BSDs
+
+    #[test]
+    fn names_and_ids_are_looked_up() {
+        let root = RunAs { uid: 0, gid: 0 };
+        assert_eq!(parse_run_as("root"), Ok(root));
+        assert_eq!(parse_run_as("0"), Ok(root));
+        assert_eq!(parse_run_as("0:0"), Ok(root));
+        assert_eq!(parse_run_as("root:daemon"), Ok(RunAs { uid: 0, gid: 1 }));
+        // ids need no entry when both are given
+        assert_eq!(
+            parse_run_as("4000000000:4000000001"),
+            Ok(RunAs { uid: 4_000_000_000, gid: 4_000_000_001 })
+        );
+    }
+
+    #[test]
+    fn unknown_names_are_refused() {
+        let unknown_user = "imgst-no-such-user";
+        assert_eq!(
+            parse_run_as(unknown_user),
+            Err(format!("unknown user '{unknown_user}'"))
+        );
+        for user in ["-1", "4294967296", "ro\0ot"] {
+            assert_eq!(
+                parse_run_as(user),
+                Err(format!("unknown user '{user}'"))
+            );
+        }
+        assert_eq!(
+            parse_run_as("root:imgst-no-such-group"),
+            Err("unknown group 'imgst-no-such-group'".into())
+        );
+        assert_eq!(
+            parse_run_as("root:daemon:daemon"),
+            Err("unknown group 'daemon:daemon'".into())
+        );
+        assert_eq!(
+            parse_run_as("4000000000"),
+            Err("user 4000000000 has no passwd entry, give a group too".into())
+        );
+    }
+
+    #[test]
+    fn empty_parts_are_refused() {
+        for s in ["", ":", "root:", ":0", ":daemon"] {
+            assert_eq!(
+                parse_run_as(s),
+                Err(format!("invalid user and group '{s}'"))
+            );
+        }
+    }
+}
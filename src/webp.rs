@@ -0,0 +1,263 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! WebP (RIFF) chunk helpers.
+//!
+//! Cleaning drops the EXIF and XMP chunks, clears their flags in the VP8X
+//! header and fixes up the RIFF size. The VP8/VP8L/ANMF chunks are copied
+//! as is, so lossy and lossless image data stays byte-identical. Data after
+//! the end of the RIFF container goes too.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::{
+    format::{HeaderInfo, read_len},
+    jpeg::EXIF_IDENT,
+};
+
+const VP8X: &[u8; 4] = b"VP8X";
+const EXIF: &[u8; 4] = b"EXIF";
+const XMP: &[u8; 4] = b"XMP ";
+
+const ICCP: &[u8; 4] = b"ICCP";
+
+/// VP8X flag bits announcing ICCP, EXIF and XMP chunks.
+const FLAG_ICC: u8 = 0x20;
+const FLAG_EXIF: u8 = 0x08;
+const FLAG_XMP: u8 = 0x04;
+
+/// Chunks removed by cleaning. The colour profile (ICCP) is kept, like
+/// ICC profiles in JPEG, unless `--icc strip` asks otherwise.
+pub fn is_metadata(fourcc: &[u8; 4]) -> bool {
+    fourcc == EXIF || fourcc == XMP
+}
+
+/// Walk the chunks of the RIFF container without reading the image data.
+pub fn scan_chunks<R: Read + Seek>(r: &mut R) -> io::Result<HeaderInfo> {
+    let mut header = [0u8; 12];
+    r.read_exact(&mut header)?;
+    let riff_size = riff_header(&header)?;
+
+    let mut info = HeaderInfo::default();
+    let mut pos = 4;
+
+    while pos < riff_size {
+        let mut chunk = [0u8; 8];
+        r.read_exact(&mut chunk)?;
+        let fourcc = [chunk[0], chunk[1], chunk[2], chunk[3]];
+        let size =
+            u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        let padded = size as u64 + (size & 1) as u64;
+
+        if &fourcc == EXIF && info.exif.is_none() {
+            let mut exif = read_len(r, size as u64)?
+                .ok_or_else(|| invalid("truncated chunk"))?;
+            r.seek(SeekFrom::Current((size & 1) as i64))?;
+            // some writers keep the JPEG APP1 identifier
+            if exif.starts_with(EXIF_IDENT) {
+                exif.drain(..EXIF_IDENT.len());
+            }
+            info.exif = Some(exif);
+        } else {
+            r.seek(SeekFrom::Current(padded as i64))?;
+        }
+
+        info.segments += 1;
+        if is_metadata(&fourcc) {
+            info.metadata_bytes += 8 + padded;
+        }
+        pos += 8 + padded;
+    }
+
+    let len = r.seek(SeekFrom::End(0))?;
+    info.trailer_bytes = len.saturating_sub(8 + riff_size);
+    info.metadata_bytes += info.trailer_bytes;
+
+    Ok(info)
+}
+
+/// Copy `data` without its EXIF and XMP chunks, without its colour profile
+/// unless `icc` is set and without what follows the RIFF container unless
+/// `trailer` is.
+pub fn strip_metadata(
+    data: &[u8],
+    icc: bool,
+    trailer: bool,
+) -> io::Result<Vec<u8>> {
+    let dropped = FLAG_EXIF | FLAG_XMP | if icc { 0 } else { FLAG_ICC };
+    let header = data.get(..12).ok_or_else(|| invalid("truncated header"))?;
+    // a missing padding byte at the very end is tolerated
+    let end = (8 + riff_header(header)? as usize).min(data.len());
+    let body = &data[12..end];
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(header);
+
+    let mut pos = 0;
+    while pos < body.len() {
+        let Some(chunk) = body.get(pos..pos + 8) else {
+            return Err(invalid("truncated chunk"));
+        };
+        let fourcc = [chunk[0], chunk[1], chunk[2], chunk[3]];
+        let size =
+            u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        let data_end = pos + 8 + size as usize;
+        if data_end > body.len() {
+            return Err(invalid("truncated chunk"));
+        }
+        let next = (data_end + (size & 1) as usize).min(body.len());
+        let chunk = &body[pos..next];
+
+        if &fourcc == VP8X {
+            let flags_at = out.len() + 8;
+            out.extend_from_slice(chunk);
+            if let Some(flags) = out.get_mut(flags_at) {
+                *flags &= !dropped;
+            }
+        } else if !is_metadata(&fourcc) && (icc || &fourcc != ICCP) {
+            out.extend_from_slice(chunk);
+        }
+        pos = next;
+    }
+
+    let riff_size = (out.len() - 8) as u32;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    if trailer {
+        out.extend_from_slice(&data[end..]);
+    }
+
+    Ok(out)
+}
+
+/// Check the RIFF/WEBP header and return the RIFF size.
+fn riff_header(header: &[u8]) -> io::Result<u64> {
+    if &header[..4] != b"RIFF" || &header[8..12] != b"WEBP" {
+        return Err(invalid("missing RIFF/WEBP header"));
+    }
+
+    let size =
+        u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    if size < 4 {
+        return Err(invalid("invalid RIFF size"));
+    }
+
+    Ok(size as u64)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const PLAIN: &[u8] = include_bytes!("../tests/fixtures/plain.webp");
+    const META: &[u8] = include_bytes!("../tests/fixtures/meta.webp");
+
+    fn chunks(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+        let mut chunks = Vec::new();
+        let mut pos = 12;
+        while pos < data.len() {
+            let size =
+                u32::from_le_bytes(data[pos + 4..pos + 8].try_into().unwrap());
+            let end = pos + 8 + size as usize;
+            chunks.push((&data[pos..pos + 4], &data[pos + 8..end]));
+            pos = end + (size & 1) as usize;
+        }
+        chunks
+    }
+
+    #[test]
+    fn plain_image_is_unchanged() {
+        assert_eq!(strip_metadata(PLAIN, true, false).unwrap(), PLAIN);
+    }
+
+    #[test]
+    fn scan_finds_exif_and_xmp() {
+        let info = scan_chunks(&mut Cursor::new(META)).unwrap();
+
+        assert_eq!(info.segments, 5);
+        assert!(info.metadata_bytes > 0);
+        assert!(info.exif.unwrap().starts_with(b"MM\0\x2a"));
+    }
+
+    #[test]
+    fn strips_exif_and_xmp_chunks() {
+        let cleaned = strip_metadata(META, true, false).unwrap();
+        let chunks = chunks(&cleaned);
+        let fourccs: Vec<_> = chunks.iter().map(|(f, _)| *f).collect();
+
+        assert_eq!(fourccs, [&b"VP8X"[..], b"ICCP", b"VP8L"]);
+        // only the ICC flag is left
+        assert_eq!(chunks[0].1[0], 0x20);
+        assert_eq!(
+            u32::from_le_bytes(cleaned[4..8].try_into().unwrap()) as usize,
+            cleaned.len() - 8
+        );
+        assert_eq!(
+            scan_chunks(&mut Cursor::new(&cleaned)).unwrap().metadata_bytes,
+            0
+        );
+    }
+
+    #[test]
+    fn profile_goes_when_asked() {
+        let cleaned = strip_metadata(META, false, false).unwrap();
+        let chunks = chunks(&cleaned);
+        let fourccs: Vec<_> = chunks.iter().map(|(f, _)| *f).collect();
+
+        assert_eq!(fourccs, [&b"VP8X"[..], b"VP8L"]);
+        assert_eq!(chunks[0].1[0], 0);
+        assert_eq!(strip_metadata(&cleaned, false, false).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn image_data_is_bit_identical() {
+        let cleaned = strip_metadata(META, true, false).unwrap();
+        let vp8l = |data| chunks(data).into_iter().find(|(f, _)| f == b"VP8L");
+
+        assert_eq!(vp8l(&cleaned), vp8l(META));
+        assert_eq!(strip_metadata(&cleaned, true, false).unwrap(), cleaned);
+    }
+
+    #[test]
+    fn data_after_the_container_goes_unless_kept() {
+        let appended = [PLAIN, b"appended"].concat();
+        let info = scan_chunks(&mut Cursor::new(&appended)).unwrap();
+        assert_eq!(info.trailer_bytes, 8);
+        assert_eq!(info.metadata_bytes, 8);
+
+        assert_eq!(strip_metadata(&appended, true, false).unwrap(), PLAIN);
+        assert_eq!(strip_metadata(&appended, true, true).unwrap(), appended);
+        assert_eq!(
+            scan_chunks(&mut Cursor::new(PLAIN)).unwrap().trailer_bytes,
+            0
+        );
+    }
+
+    #[test]
+    fn rejects_broken_streams() {
+        assert!(strip_metadata(b"not a webp file", true, false).is_err());
+        assert!(strip_metadata(&META[..40], true, false).is_err());
+
+        // an EXIF chunk claiming nearly 4 GiB
+        let huge = b"RIFF\x14\0\0\0WEBPEXIF\xf0\xff\xff\xffMM\0\x2a";
+        assert!(scan_chunks(&mut Cursor::new(&huge[..])).is_err());
+    }
+}
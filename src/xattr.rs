@@ -0,0 +1,77 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Extended attributes carried over by copies.
+//!
+//! On macOS `fs::copy` clones the source with its extended attributes, so
+//! an unchanged copy of a screenshot still has `kMDItemScreenCapture`, the
+//! window title and the download origin (`kMDItemWhereFroms`). Elsewhere
+//! the copy only carries the file contents and permissions.
+
+use std::{io, path::Path};
+
+/// Remove every extended attribute of `path`.
+#[cfg(target_os = "macos")]
+pub fn strip(path: &Path) -> io::Result<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let opts = libc::XATTR_NOFOLLOW;
+
+    // SAFETY: a null buffer only asks for the size of the name list
+    let size = unsafe {
+        libc::listxattr(path.as_ptr(), std::ptr::null_mut(), 0, opts)
+    };
+    if size < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if size == 0 {
+        return Ok(());
+    }
+
+    let mut names = vec![0u8; size as usize];
+    // SAFETY: the buffer is as large as the size passed
+    let size = unsafe {
+        libc::listxattr(
+            path.as_ptr(),
+            names.as_mut_ptr().cast(),
+            names.len(),
+            opts,
+        )
+    };
+    if size < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    names.truncate(size as usize);
+
+    // the list is a sequence of nul-terminated names
+    for name in names.split_inclusive(|&b| b == 0) {
+        // SAFETY: `name` ends with the nul from the list
+        let ret = unsafe {
+            libc::removexattr(path.as_ptr(), name.as_ptr().cast(), opts)
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn strip(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,615 @@
+//
+// Copyright (c) 2025 murilo ijanc' <murilo@ijanc.org>
+//
+// Permission to use, copy, modify, and distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+//
+
+//! Markup filtering for the XML based formats.
+//!
+//! Only the markup is tokenized: start and end tags, comments, CDATA,
+//! processing instructions and declarations. Dropped elements go with
+//! their content and dropped attributes with the whitespace before them;
+//! everything else is copied byte for byte. The input is not checked to be
+//! well-formed XML beyond its tags being balanced.
+
+use std::io;
+
+use crate::format::HeaderInfo;
+
+/// What [`filter`] drops.
+pub struct Rules {
+    /// Elements dropped with their content, by qualified name.
+    pub element: fn(&[u8]) -> bool,
+    /// Attributes dropped from the elements kept, by qualified name.
+    pub attribute: fn(&[u8]) -> bool,
+    /// Whether comments are dropped too.
+    pub comments: bool,
+}
+
+/// The output of [`filter`].
+pub struct Filtered<'a> {
+    pub data: Vec<u8>,
+    /// Start tags looked at count as segments, dropped markup as metadata.
+    pub info: HeaderInfo,
+    /// Qualified names of every start tag, dropped ones included.
+    pub names: Vec<&'a [u8]>,
+}
+
+/// Copy `data` without the elements and attributes `rules` drop.
+pub fn filter<'a>(data: &'a [u8], rules: &Rules) -> io::Result<Filtered<'a>> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut info = HeaderInfo::default();
+    let mut names = Vec::new();
+    // kept elements open, and depth inside a dropped element, zero when
+    // copying
+    let mut depth = 0usize;
+    let mut skip = 0usize;
+    let mut drop_start = 0;
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let Some(at) = find(data, pos, b"<") else {
+            if skip == 0 {
+                out.extend_from_slice(&data[pos..]);
+            }
+            break;
+        };
+        if skip == 0 {
+            out.extend_from_slice(&data[pos..at]);
+        }
+        let rest = &data[at..];
+
+        // markup other than tags and comments is copied as is
+        let verbatim = if rest.starts_with(b"<![CDATA[") {
+            Some(end_of(data, at, b"]]>")?)
+        } else if rest.starts_with(b"<?") {
+            Some(end_of(data, at, b"?>")?)
+        } else if rest.starts_with(b"<!") && !rest.starts_with(b"<!--") {
+            Some(declaration_end(data, at)?)
+        } else {
+            None
+        };
+        if let Some(end) = verbatim {
+            if skip == 0 {
+                out.extend_from_slice(&data[at..end]);
+            }
+            pos = end;
+            continue;
+        }
+
+        let end;
+        if rest.starts_with(b"<!--") {
+            end = end_of(data, at, b"-->")?;
+            if skip > 0 || !rules.comments {
+                if skip == 0 {
+                    out.extend_from_slice(&data[at..end]);
+                }
+                pos = end;
+                continue;
+            }
+            drop_start = at;
+        } else if rest.starts_with(b"</") {
+            end = end_of(data, at, b">")?;
+            match skip {
+                0 => {
+                    depth = depth
+                        .checked_sub(1)
+                        .ok_or_else(|| invalid("unbalanced end tag"))?;
+                    out.extend_from_slice(&data[at..end]);
+                    pos = end;
+                    continue;
+                }
+                1 => skip = 0,
+                _ => {
+                    skip -= 1;
+                    pos = end;
+                    continue;
+                }
+            }
+        } else {
+            let tag = Tag::parse(data, at)?;
+            info.segments += 1;
+            names.push(tag.name);
+            end = tag.end;
+
+            if skip > 0 {
+                skip += usize::from(!tag.empty);
+                pos = end;
+                continue;
+            }
+            if !(rules.element)(tag.name) {
+                depth += usize::from(!tag.empty);
+                let kept = tag.without_attrs(rules.attribute);
+                info.metadata_bytes += (end - at - kept.len()) as u64;
+                out.extend_from_slice(&kept);
+                pos = end;
+                continue;
+            }
+            drop_start = at;
+            if !tag.empty {
+                skip = 1;
+                pos = end;
+                continue;
+            }
+        }
+
+        // a dropped comment or element ends here
+        info.metadata_bytes += (end - drop_start) as u64;
+        pos = trim_line(&mut out, data, end);
+    }
+
+    if skip > 0 || depth > 0 {
+        return Err(invalid("unterminated element"));
+    }
+    Ok(Filtered { data: out, info, names })
+}
+
+/// A value in the markup, from [`values`]: an attribute or the text of an
+/// element without children.
+pub struct Value<'a> {
+    /// Qualified names of the elements it is in, outermost first, then its
+    /// own.
+    pub path: Vec<&'a [u8]>,
+    /// The value as written, entities and all.
+    pub text: &'a [u8],
+    /// Where it is in the input: the whole element, or the attribute with
+    /// the whitespace before it.
+    pub span: (usize, usize),
+    /// Where `text` is in the input.
+    pub text_span: (usize, usize),
+}
+
+/// Every attribute and text-only element of `data`, in document order but
+/// for the elements, which come when they close.
+pub fn values(data: &[u8]) -> io::Result<Vec<Value<'_>>> {
+    let mut values = Vec::new();
+    // open elements: name, start, end of the start tag, whether the
+    // content holds markup
+    let mut open: Vec<(&[u8], usize, usize, bool)> = Vec::new();
+    let mut pos = 0;
+
+    while let Some(at) = find(data, pos, b"<") {
+        let rest = &data[at..];
+        let skipped = if rest.starts_with(b"<![CDATA[") {
+            Some(end_of(data, at, b"]]>")?)
+        } else if rest.starts_with(b"<!--") {
+            Some(end_of(data, at, b"-->")?)
+        } else if rest.starts_with(b"<?") {
+            Some(end_of(data, at, b"?>")?)
+        } else if rest.starts_with(b"<!") {
+            Some(declaration_end(data, at)?)
+        } else {
+            None
+        };
+        if let Some(end) = skipped {
+            if let Some(parent) = open.last_mut() {
+                parent.3 = true;
+            }
+            pos = end;
+            continue;
+        }
+
+        if rest.starts_with(b"</") {
+            let end = end_of(data, at, b">")?;
+            let Some((name, start, content, markup)) = open.pop() else {
+                return Err(invalid("unbalanced end tag"));
+            };
+            if !markup {
+                let mut path: Vec<_> = open.iter().map(|e| e.0).collect();
+                path.push(name);
+                values.push(Value {
+                    path,
+                    text: &data[content..at],
+                    span: (start, end),
+                    text_span: (content, at),
+                });
+            }
+            pos = end;
+            continue;
+        }
+
+        let tag = Tag::parse(data, at)?;
+        if let Some(parent) = open.last_mut() {
+            parent.3 = true;
+        }
+        for &(start, end) in &tag.attrs {
+            let attr = data[start..end].trim_ascii_start();
+            let name_len = attr.iter().position(|&b| !is_name(b)).unwrap_or(0);
+            let quote = attr.iter().position(|&b| b == b'"' || b == b'\'');
+            let Some(quote) = quote else { continue };
+            let mut path: Vec<_> = open.iter().map(|e| e.0).collect();
+            path.extend([tag.name, &attr[..name_len]]);
+            let text_start = end - attr.len() + quote + 1;
+            values.push(Value {
+                path,
+                text: &data[text_start..end - 1],
+                span: (start, end),
+                text_span: (text_start, end - 1),
+            });
+        }
+        if !tag.empty {
+            open.push((tag.name, at, tag.end, false));
+        }
+        pos = tag.end;
+    }
+
+    if !open.is_empty() {
+        return Err(invalid("unterminated element"));
+    }
+    Ok(values)
+}
+
+/// Copy `data` without the `spans`, which must be sorted and apart, as
+/// [`filter`] drops elements and attributes.
+pub fn remove(data: &[u8], spans: &[(usize, usize)]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut pos = 0;
+
+    for &(start, end) in spans {
+        out.extend_from_slice(&data[pos..start]);
+        pos = trim_line(&mut out, data, end);
+    }
+    out.extend_from_slice(&data[pos..]);
+
+    out
+}
+
+/// Copy `data` with the text at each span, sorted and apart, replaced by
+/// what goes with it.
+pub fn replace(data: &[u8], edits: &[((usize, usize), Vec<u8>)]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut pos = 0;
+
+    for ((start, end), text) in edits {
+        out.extend_from_slice(&data[pos..*start]);
+        out.extend_from_slice(text);
+        pos = *end;
+    }
+    out.extend_from_slice(&data[pos..]);
+
+    out
+}
+
+/// A start tag, `<name attrs>` or `<name attrs/>`.
+struct Tag<'a> {
+    data: &'a [u8],
+    start: usize,
+    name: &'a [u8],
+    /// Attribute ranges, each with the whitespace before it.
+    attrs: Vec<(usize, usize)>,
+    /// Start of the `>` or `/>` closing the tag.
+    close: usize,
+    end: usize,
+    empty: bool,
+}
+
+impl<'a> Tag<'a> {
+    fn parse(data: &'a [u8], start: usize) -> io::Result<Self> {
+        let unterminated = || invalid("unterminated tag");
+        let mut pos = start + 1;
+        while data.get(pos).is_some_and(|&b| is_name(b)) {
+            pos += 1;
+        }
+        let name = &data[start + 1..pos];
+        if name.is_empty() {
+            return Err(invalid("invalid tag name"));
+        }
+
+        let mut attrs = Vec::new();
+        loop {
+            let attr_start = pos;
+            while data.get(pos).is_some_and(|b| b.is_ascii_whitespace()) {
+                pos += 1;
+            }
+            match data.get(pos).ok_or_else(unterminated)? {
+                b'>' => {
+                    return Ok(Tag {
+                        data,
+                        start,
+                        name,
+                        attrs,
+                        close: attr_start,
+                        end: pos + 1,
+                        empty: false,
+                    });
+                }
+                b'/' if data.get(pos + 1) == Some(&b'>') => {
+                    return Ok(Tag {
+                        data,
+                        start,
+                        name,
+                        attrs,
+                        close: attr_start,
+                        end: pos + 2,
+                        empty: true,
+                    });
+                }
+                _ => {}
+            }
+
+            // name, '=', quoted value
+            let name_start = pos;
+            while data.get(pos).is_some_and(|&b| is_name(b)) {
+                pos += 1;
+            }
+            if pos == name_start {
+                return Err(invalid("invalid attribute"));
+            }
+            while data.get(pos).is_some_and(|b| b.is_ascii_whitespace()) {
+                pos += 1;
+            }
+            if data.get(pos) != Some(&b'=') {
+                return Err(invalid("attribute without a value"));
+            }
+            pos += 1;
+            while data.get(pos).is_some_and(|b| b.is_ascii_whitespace()) {
+                pos += 1;
+            }
+            let quote = *data.get(pos).ok_or_else(unterminated)?;
+            if quote != b'"' && quote != b'\'' {
+                return Err(invalid("unquoted attribute value"));
+            }
+            let len = data[pos + 1..]
+                .iter()
+                .position(|&b| b == quote)
+                .ok_or_else(unterminated)?;
+            pos += len + 2;
+            attrs.push((attr_start, pos));
+        }
+    }
+
+    /// The tag with the attributes `dropped` picks left out.
+    fn without_attrs(&self, dropped: fn(&[u8]) -> bool) -> Vec<u8> {
+        let data = self.data;
+        let mut out =
+            data[self.start..self.start + 1 + self.name.len()].to_vec();
+
+        for &(start, end) in &self.attrs {
+            let attr = data[start..end].trim_ascii_start();
+            let name_len = attr.iter().position(|&b| !is_name(b)).unwrap_or(0);
+            if !dropped(&attr[..name_len]) {
+                out.extend_from_slice(&data[start..end]);
+            }
+        }
+        out.extend_from_slice(&data[self.close..self.end]);
+
+        out
+    }
+}
+
+/// `s` as text or an attribute value.
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+pub fn local_name(name: &[u8]) -> &[u8] {
+    match name.iter().rposition(|&b| b == b':') {
+        Some(colon) => &name[colon + 1..],
+        None => name,
+    }
+}
+
+fn is_name(b: u8) -> bool {
+    b.is_ascii_alphanumeric()
+        || matches!(b, b':' | b'_' | b'-' | b'.')
+        || b >= 0x80
+}
+
+/// Drop the indentation left before a removed construct and the line break
+/// after it, when it stood on a line of its own; returns where to go on.
+fn trim_line(out: &mut Vec<u8>, data: &[u8], end: usize) -> usize {
+    let line = out.iter().rposition(|&b| b == b'\n').map_or(0, |n| n + 1);
+    if !out[line..].iter().all(|&b| b == b' ' || b == b'\t') {
+        return end;
+    }
+    let rest = &data[end..];
+    let indent = rest.iter().take_while(|&&b| b == b' ' || b == b'\t');
+    let skip = indent.count();
+    match &rest[skip..] {
+        [b'\r', b'\n', ..] => {
+            out.truncate(line);
+            end + skip + 2
+        }
+        [b'\n', ..] => {
+            out.truncate(line);
+            end + skip + 1
+        }
+        _ => end,
+    }
+}
+
+fn find(data: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    data[from..]
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|at| from + at)
+}
+
+/// The end of the construct at `at`, which closes with `close`.
+fn end_of(data: &[u8], at: usize, close: &[u8]) -> io::Result<usize> {
+    find(data, at + 1, close)
+        .map(|end| end + close.len())
+        .ok_or_else(|| invalid("unterminated markup"))
+}
+
+/// The end of a `<!DOCTYPE ...>`, which may hold an internal subset.
+fn declaration_end(data: &[u8], at: usize) -> io::Result<usize> {
+    let mut in_subset = false;
+    for (i, &b) in data[at..].iter().enumerate() {
+        match b {
+            b'[' => in_subset = true,
+            b']' => in_subset = false,
+            b'>' if !in_subset => return Ok(at + i + 1),
+            _ => {}
+        }
+    }
+    Err(invalid("unterminated declaration"))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &[u8] = b"<r a=\"1 > 2\" b='x'><!-- c > d --><?pi x > y?>\
+        <![CDATA[<x:y>]]><x:y z:w=\"v\">text</x:y><e/></r>";
+
+    fn rules() -> Rules {
+        Rules {
+            element: |name| name == b"x:y",
+            attribute: |name| name == b"b",
+            comments: true,
+        }
+    }
+
+    #[test]
+    fn markup_is_skipped_not_parsed() {
+        let filtered = filter(DOC, &rules()).unwrap();
+        assert_eq!(
+            filtered.data,
+            b"<r a=\"1 > 2\"><?pi x > y?><![CDATA[<x:y>]]><e/></r>"
+        );
+        assert_eq!(filtered.names, [&b"r"[..], b"x:y", b"e"]);
+        assert_eq!(filtered.info.segments, 3);
+        let dropped = DOC.len() - filtered.data.len();
+        assert_eq!(filtered.info.metadata_bytes, dropped as u64);
+
+        let kept = Rules { comments: false, ..rules() };
+        let comment = b"<!-- c > d -->";
+        let filtered = filter(DOC, &kept).unwrap();
+        assert!(filtered.data.windows(comment.len()).any(|w| w == comment));
+    }
+
+    #[test]
+    fn dropped_lines_go_with_their_indentation() {
+        let data = b"<r>\n  <x:y>\n    <x:y/>\n  </x:y>\n  <k/>\n</r>\n";
+        let filtered = filter(data, &rules()).unwrap();
+        assert_eq!(filtered.data, b"<r>\n  <k/>\n</r>\n");
+    }
+
+    #[test]
+    fn values_are_attributes_and_text_only_elements() {
+        let found = values(DOC).unwrap();
+        let paths: Vec<_> =
+            found.iter().map(|v| (v.path.join(&b'/'), v.text)).collect();
+        assert_eq!(
+            paths,
+            [
+                (b"r/a".to_vec(), &b"1 > 2"[..]),
+                (b"r/b".to_vec(), b"x"),
+                (b"r/x:y/z:w".to_vec(), b"v"),
+                (b"r/x:y".to_vec(), b"text"),
+            ]
+        );
+        for value in &found {
+            let (start, end) = value.text_span;
+            assert_eq!(&DOC[start..end], value.text);
+        }
+
+        // CDATA, comments and instructions are content, not text
+        for data in [
+            &b"<s><![CDATA[a]]></s>"[..],
+            b"<s>a<!-- b --></s>",
+            b"<s><?pi?>a</s>",
+        ] {
+            assert!(values(data).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn values_can_be_removed_or_replaced() {
+        let found = values(DOC).unwrap();
+        let edits: Vec<_> = found[2..]
+            .iter()
+            .map(|v| (v.text_span, v.text.to_ascii_uppercase()))
+            .collect();
+        assert_eq!(
+            replace(DOC, &edits),
+            b"<r a=\"1 > 2\" b='x'><!-- c > d --><?pi x > y?>\
+                <![CDATA[<x:y>]]><x:y z:w=\"V\">TEXT</x:y><e/></r>"
+        );
+
+        let spans = [found[1].span, found[3].span];
+        let removed = remove(DOC, &spans);
+        assert_eq!(
+            removed,
+            b"<r a=\"1 > 2\"><!-- c > d --><?pi x > y?>\
+                <![CDATA[<x:y>]]><e/></r>"
+        );
+    }
+
+    #[test]
+    fn broken_markup_is_an_error() {
+        for data in [
+            &b"<r a>"[..],
+            b"<r a=1>",
+            b"<r =\"1\">",
+            b"< r>",
+            b"<>",
+            b"<r>text",
+            b"<r></r",
+            b"<r><!DOCTYPE x [ > ]</r>",
+        ] {
+            let text = String::from_utf8_lossy(data);
+            assert!(filter(data, &rules()).is_err(), "{text}");
+            assert!(values(data).is_err(), "{text}");
+        }
+        assert!(filter(b"<r></r></r>", &rules()).is_err());
+        assert!(values(b"<r></r></r>").is_err());
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        // the root closes at the very end, so every cut is unterminated
+        for len in 1..DOC.len() {
+            let data = &DOC[..len];
+            let text = String::from_utf8_lossy(data);
+            assert!(filter(data, &rules()).is_err(), "{text}");
+            assert!(values(data).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn deep_nesting_does_not_recurse() {
+        let depth = 100_000;
+        let mut data = b"<a>".repeat(depth);
+        data.extend_from_slice(b"deep");
+        data.extend_from_slice(&b"</a>".repeat(depth));
+
+        let found = values(&data).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path.len(), depth);
+        assert_eq!(found[0].text, b"deep");
+
+        let drop_a = Rules {
+            element: |name| name == b"a",
+            attribute: |_| false,
+            comments: false,
+        };
+        assert!(filter(&data, &drop_a).unwrap().data.is_empty());
+        assert!(filter(&data[..data.len() - 1], &drop_a).is_err());
+    }
+}